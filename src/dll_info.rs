@@ -1,17 +1,22 @@
 use std::ffi::OsStr;
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use egui::{Ui, Vec2, Window};
+use egui::{CollapsingHeader, Color32, ComboBox, Grid, ScrollArea, TextEdit, Ui, Vec2, Window};
 use egui_extras::{Column, TableBuilder};
 use obfstr::obfstr;
-use pelite::image::{
-    IMAGE_FILE_32BIT_MACHINE, IMAGE_FILE_MACHINE_AMD64, IMAGE_FILE_MACHINE_I386,
-    IMAGE_FILE_MACHINE_IA64,
-};
+use pelite::image::IMAGE_FILE_DLL;
 use pelite::{FileMap, PeFile};
 use rfd::FileDialog;
+use tracing::{info, warn};
 
 use crate::emoji_button_widget::EmojiButtonWidget;
+use crate::utils::authenticode::SignatureCheck;
+use crate::utils::clr_metadata::{ClrInfo, inspect_clr};
+use crate::utils::elf_info::{ElfDetails, inspect_elf, is_elf_file};
+use crate::utils::injection_history::{PinPolicy, file_sha256};
+use crate::utils::managed_injection::ManagedCall;
+use crate::utils::pe_machine::{ClrPlatform, PeArchitecture};
 
 #[derive(Debug, Clone)]
 pub struct DllInfo {
@@ -20,6 +25,26 @@ pub struct DllInfo {
     pub(crate) dll_path: String,
     pub(crate) dll_arch: String,
     pub(crate) index: usize,
+    // Sequencing: how long to wait before injecting this DLL, and a module
+    // that has to be loaded in the target first (empty for none).
+    pub(crate) delay_ms: u64,
+    pub(crate) wait_for_module: String,
+    // Content hash and size taken when the DLL was added. Duplicates are
+    // detected by hash, and a pinned DLL is checked against it before
+    // every injection.
+    pub(crate) sha256: Option<String>,
+    pub(crate) file_size: u64,
+    pub(crate) pin: Option<PinPolicy>,
+    // Authenticode only exists for PE files; None until the background
+    // check of a PE file has finished.
+    pub(crate) signature: Option<SignatureCheck>,
+    pub(crate) pe_arch: Option<PeArchitecture>,
+    // Set for shared objects, which have no PE headers to inspect.
+    pub(crate) elf: Option<ElfDetails>,
+    // Set for .NET assemblies. `managed` is the method to run when the
+    // assembly is injected through the CLR instead of LoadLibrary.
+    pub(crate) clr: Option<ClrInfo>,
+    pub(crate) managed: Option<ManagedCall>,
 }
 
 impl DllInfo {
@@ -31,7 +56,23 @@ impl DllInfo {
         dll_arch: String,
         index: usize,
     ) -> Self {
-        DllInfo { switch, dll_name, dll_path, dll_arch, index }
+        DllInfo {
+            switch,
+            dll_name,
+            dll_path,
+            dll_arch,
+            index,
+            delay_ms: 0,
+            wait_for_module: String::new(),
+            sha256: None,
+            file_size: 0,
+            pin: None,
+            signature: None,
+            pe_arch: None,
+            elf: None,
+            clr: None,
+            managed: None,
+        }
     }
 }
 
@@ -44,20 +85,178 @@ impl Default for DllInfo {
             dll_path: String::from("undefined"),
             dll_arch: String::from("undefined"),
             index: 0usize,
+            delay_ms: 0,
+            wait_for_module: String::new(),
+            sha256: None,
+            file_size: 0,
+            pin: None,
+            signature: None,
+            pe_arch: None,
+            elf: None,
+            clr: None,
+            managed: None,
         }
     }
 }
 
 // Function to determine DLL architecture using pelite
-pub fn get_dll_architecture(path: &Path) -> String {
-    let file_map = FileMap::open(path).expect(obfstr!("Failed to open the file."));
-    let pe = PeFile::from_bytes(&file_map).expect(obfstr!("Failed to parse the PE file."));
-    match pe.file_header().Machine {
-        IMAGE_FILE_MACHINE_AMD64 => "x64/AMD64".to_string(),
-        IMAGE_FILE_MACHINE_I386 => "x86/I386".to_string(),
-        IMAGE_FILE_MACHINE_IA64 => "x64/IA64".to_string(),
-        IMAGE_FILE_32BIT_MACHINE => "x32/32BIT".to_string(),
-        _ => "unknown".to_string(),
+pub fn get_dll_architecture(path: &Path) -> Result<PeArchitecture, String> {
+    let file_map = FileMap::open(path).map_err(|err| err.to_string())?;
+    let pe = PeFile::from_bytes(&file_map).map_err(|err| err.to_string())?;
+    if pe.file_header().Characteristics & IMAGE_FILE_DLL == 0 {
+        return Err(obfstr!("not a DLL").to_owned());
+    }
+    Ok(PeArchitecture::from_pe(pe))
+}
+
+// What happened to each file offered to the inject list, shown to the user
+// after a dialog pick or a drop.
+#[derive(Debug, Default)]
+pub struct DllAddSummary {
+    pub added: Vec<String>,
+    pub duplicates: Vec<String>,
+    // (path, reason)
+    pub invalid: Vec<(String, String)>,
+}
+
+impl DllAddSummary {
+    // Nothing worth a popup: a clean add of a single DLL.
+    fn is_quiet(&self) -> bool {
+        self.added.len() <= 1 && self.duplicates.is_empty() && self.invalid.is_empty()
+    }
+}
+
+// Add files and folders (searched recursively for .dll files) to the inject
+// list, skipping paths already in it and files that are not valid DLLs.
+pub fn add_dll_paths(
+    dll_list_vector: &mut Vec<DllInfo>,
+    paths: impl IntoIterator<Item = PathBuf>,
+) -> DllAddSummary {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            collect_dll_files(&path, 0, &mut files);
+        } else {
+            files.push(path);
+        }
+    }
+
+    let mut summary = DllAddSummary::default();
+    for path in files {
+        let file_name = path
+            .file_name()
+            .unwrap_or_else(|| OsStr::new("undefined"))
+            .to_string_lossy()
+            .into_owned();
+        let file_path = path.to_string_lossy().into_owned();
+
+        let (sha256, file_size) =
+            match file_sha256(&path).and_then(|sha256| Ok((sha256, fs::metadata(&path)?.len()))) {
+                Ok(hash) => hash,
+                Err(err) => {
+                    summary.invalid.push((file_path, err.to_string()));
+                    continue;
+                },
+            };
+
+        // Check if the DLL is already in the list by comparing contents, so
+        // the same file under another path or through a link is caught too
+        if let Some(existing) = dll_list_vector
+            .iter()
+            .find(|dll| dll.sha256.as_ref() == Some(&sha256) || dll.dll_path == file_path)
+        {
+            summary.duplicates.push(if existing.dll_path == file_path {
+                file_path
+            } else {
+                format!("{} (same file as {})", file_path, existing.dll_path)
+            });
+            continue;
+        }
+        let inspected = if is_elf_file(&path) {
+            inspect_elf(&path).map(|details| (details.arch(), None, Some(details)))
+        } else {
+            get_dll_architecture(&path).map(|arch| (arch.to_string(), Some(arch), None))
+        };
+        match inspected {
+            Ok((file_arch, pe_arch, elf)) => {
+                let clr = pe_arch
+                    .as_ref()
+                    .filter(|arch| arch.clr.is_some())
+                    .and_then(|_| {
+                        inspect_clr(&path)
+                            .map_err(|err| {
+                                warn!("Failed to read the metadata of {}: {}", file_path, err)
+                            })
+                            .ok()
+                    })
+                    .flatten();
+                dll_list_vector.push(DllInfo {
+                    sha256: Some(sha256),
+                    file_size,
+                    pe_arch,
+                    elf,
+                    clr,
+                    ..DllInfo::new(
+                        false,
+                        file_name,
+                        file_path.clone(),
+                        file_arch,
+                        dll_list_vector.len() + 1,
+                    )
+                });
+                summary.added.push(file_path);
+            },
+            Err(reason) => summary.invalid.push((file_path, reason)),
+        }
+    }
+
+    info!(
+        added = summary.added.len(),
+        duplicates = summary.duplicates.len(),
+        invalid = summary.invalid.len(),
+        "Added DLLs to the inject list"
+    );
+    for (path, reason) in &summary.invalid {
+        warn!("Rejected {}: {}", path, reason);
+    }
+    summary
+}
+
+const MAX_FOLDER_DEPTH: usize = 16;
+
+// .dll, .so and versioned .so.N names.
+fn is_library_path(path: &Path) -> bool {
+    let name = path.file_name().map(|name| name.to_string_lossy().to_lowercase());
+    name.is_some_and(|name| {
+        name.ends_with(".dll") || name.ends_with(".so") || name.contains(".so.")
+    })
+}
+
+// Symlinks and junctions below the chosen folder are not followed, so a link
+// back up the tree cannot recurse forever; the depth cap bounds very deep
+// trees.
+fn collect_dll_files(directory: &Path, depth: usize, files: &mut Vec<PathBuf>) {
+    if depth > MAX_FOLDER_DEPTH {
+        warn!("Not searching {}: nested too deeply", directory.display());
+        return;
+    }
+    let mut entries: Vec<(PathBuf, bool)> = match fs::read_dir(directory) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| Some((entry.path(), entry.file_type().ok()?.is_dir())))
+            .collect(),
+        Err(err) => {
+            warn!("Failed to read {}: {}", directory.display(), err);
+            return;
+        },
+    };
+    entries.sort();
+    for (path, is_dir) in entries {
+        if is_dir {
+            collect_dll_files(&path, depth + 1, files);
+        } else if is_library_path(&path) {
+            files.push(path);
+        }
     }
 }
 
@@ -125,54 +324,65 @@ pub fn enable_disable_dll(
 pub fn open_file_dialog_and_add_dll(
     ui: &mut Ui,
     dll_list_vector: &mut Vec<DllInfo>,
-    show_popup: &mut bool,
+    add_summary: &mut Option<DllAddSummary>,
 ) {
     let add_dll_resp = ui.add(
         EmojiButtonWidget::new(obfstr!("➕📚 Add DLL")).min_size(Vec2::from([200.0f32, 10.0f32])),
     );
 
     if add_dll_resp.clicked() {
-        if let Some(path) =
-            FileDialog::new().add_filter(obfstr!("DLL Files"), &[obfstr!("dll")]).pick_file()
+        if let Some(paths) = FileDialog::new()
+            .add_filter(obfstr!("DLL Files"), &[obfstr!("dll")])
+            .add_filter(obfstr!("Shared Objects"), &[obfstr!("so")])
+            .pick_files()
         {
-            let file_name = path
-                .file_name()
-                .unwrap_or_else(|| OsStr::new("undefined"))
-                .to_string_lossy()
-                .into_owned();
-            let file_path = path.to_string_lossy().into_owned();
-
-            // Check if the DLL is already in the list by comparing paths
-            let already_exists = dll_list_vector.iter().any(|dll| dll.dll_path == file_path);
-            if !already_exists {
-                let file_arch = get_dll_architecture(&path);
-
-                dll_list_vector.push(DllInfo::new(
-                    false,
-                    file_name,
-                    file_path,
-                    file_arch,
-                    dll_list_vector.len() + 1,
-                ));
-            } else {
-                // Show the popup if the DLL is already in the list
-                *show_popup = true;
-            }
+            let summary = add_dll_paths(dll_list_vector, paths);
+            *add_summary = Some(summary).filter(|summary| !summary.is_quiet());
         }
     }
 
-    // Display the popup if needed
-    if *show_popup {
-        Window::new("Error")
-            .collapsible(false)
-            .resizable(false)
-            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
-            .show(ui.ctx(), |ui| {
+    dll_add_summary_window(ui.ctx(), add_summary);
+}
+
+// Display the summary popup if needed
+pub fn dll_add_summary_window(ctx: &egui::Context, add_summary: &mut Option<DllAddSummary>) {
+    let Some(summary) = add_summary.as_ref() else {
+        return;
+    };
+    let mut open = true;
+    Window::new(obfstr!("Add DLLs"))
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            if summary.added.is_empty() && summary.invalid.is_empty() {
+                // The old single-file message, kept for the common case.
                 ui.label(obfstr!("This DLL is already added."));
-                if ui.button("OK").clicked() {
-                    *show_popup = false;
+            } else {
+                ui.label(format!(
+                    "Added {}, skipped {} already in the list, rejected {} invalid.",
+                    summary.added.len(),
+                    summary.duplicates.len(),
+                    summary.invalid.len()
+                ));
+            }
+            ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                for path in &summary.added {
+                    ui.colored_label(Color32::GREEN, format!("Added: {}", path));
+                }
+                for path in &summary.duplicates {
+                    ui.colored_label(Color32::GRAY, format!("Already added: {}", path));
+                }
+                for (path, reason) in &summary.invalid {
+                    ui.colored_label(Color32::RED, format!("Invalid: {} ({})", path, reason));
                 }
             });
+            if ui.button("OK").clicked() {
+                open = false;
+            }
+        });
+    if !open {
+        *add_summary = None;
     }
 }
 
@@ -180,7 +390,7 @@ pub fn dll_list_buttons_column(
     ui: &mut Ui,
     dll_list_vector: &mut Vec<DllInfo>,
     selected_row: &mut Option<usize>,
-    show_popup_error_dll_already_added: &mut bool,
+    add_summary: &mut Option<DllAddSummary>,
 ) {
     TableBuilder::new(ui)
         .striped(true)
@@ -190,11 +400,7 @@ pub fn dll_list_buttons_column(
             // Row 1: Add DLL Button
             body.row(15.0, |mut row| {
                 row.col(|ui| {
-                    open_file_dialog_and_add_dll(
-                        ui,
-                        dll_list_vector,
-                        show_popup_error_dll_already_added,
-                    );
+                    open_file_dialog_and_add_dll(ui, dll_list_vector, add_summary);
                 });
             });
             body.row(10.0, |mut row| {
@@ -226,3 +432,122 @@ pub fn dll_list_buttons_column(
             });
         });
 }
+
+pub fn elf_details_pane(ui: &mut Ui, details: &ElfDetails) {
+    CollapsingHeader::new(obfstr!("ELF details")).default_open(true).show(ui, |ui| {
+        Grid::new("ElfDetailsGrid").num_columns(2).show(ui, |ui| {
+            ui.label("Class");
+            ui.label(format!("ELF{}", details.class));
+            ui.end_row();
+            ui.label("Machine");
+            ui.label(&details.machine);
+            ui.end_row();
+            ui.label("SONAME");
+            ui.label(details.soname.as_deref().unwrap_or("none"));
+            ui.end_row();
+            ui.label("Constructors");
+            ui.label(
+                details
+                    .constructors
+                    .iter()
+                    .map(|offset| format!("{:#x}", offset))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            );
+            ui.end_row();
+        });
+        CollapsingHeader::new(format!("Needed libraries ({})", details.needed.len()))
+            .id_source("ElfNeeded")
+            .show(ui, |ui| {
+                for library in &details.needed {
+                    ui.label(library);
+                }
+            });
+        CollapsingHeader::new(format!("Exported symbols ({})", details.exports.len()))
+            .id_source("ElfExports")
+            .show(ui, |ui| {
+                ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                    for export in &details.exports {
+                        ui.monospace(export);
+                    }
+                });
+            });
+    });
+}
+
+// CLR header details and the managed injection settings of a .NET assembly.
+pub fn clr_details_pane(ui: &mut Ui, clr: &ClrInfo, managed: &mut Option<ManagedCall>) {
+    CollapsingHeader::new(obfstr!(".NET assembly")).default_open(true).show(ui, |ui| {
+        Grid::new("ClrDetailsGrid").num_columns(2).show(ui, |ui| {
+            ui.label("Runtime");
+            ui.label(&clr.runtime_version);
+            ui.end_row();
+            ui.label("Platform");
+            ui.label(match clr.platform {
+                ClrPlatform::AnyCpu => "AnyCPU",
+                ClrPlatform::AnyCpu32BitPreferred => "AnyCPU, 32-bit preferred",
+                ClrPlatform::X86Only => "x86 only",
+                ClrPlatform::Native => "platform specific",
+            });
+            ui.end_row();
+            ui.label("IL only");
+            ui.label(if clr.il_only { "yes" } else { "no (mixed mode)" });
+            ui.end_row();
+        });
+
+        let mut enabled = managed.is_some();
+        ui.checkbox(&mut enabled, obfstr!("Managed injection")).on_hover_text(obfstr!(
+            "Start the CLR in the target and call a static int Method(string) instead of loading \
+             the assembly with LoadLibrary"
+        ));
+        match (enabled, managed.is_some()) {
+            (true, false) => {
+                let entry = clr.entry_points.first();
+                *managed = Some(ManagedCall {
+                    type_name: entry.map(|entry| entry.type_name.clone()).unwrap_or_default(),
+                    method: entry.map(|entry| entry.method.clone()).unwrap_or_default(),
+                    argument: String::new(),
+                    runtime_version: clr.hosting_runtime().to_owned(),
+                });
+            },
+            (false, true) => *managed = None,
+            _ => {},
+        }
+        let Some(call) = managed.as_mut() else {
+            return;
+        };
+
+        let selected = format!("{}::{}", call.type_name, call.method);
+        ComboBox::from_id_source("ClrEntryPointCombo")
+            .selected_text(&selected)
+            .width(300.0)
+            .show_ui(ui, |ui| {
+                for entry in &clr.entry_points {
+                    let text = entry.to_string();
+                    if ui.selectable_label(text == selected, &text).clicked() {
+                        call.type_name = entry.type_name.clone();
+                        call.method = entry.method.clone();
+                    }
+                }
+            })
+            .response
+            .on_hover_text(format!(
+                "{} static int Method(string) candidates found",
+                clr.entry_points.len()
+            ));
+        Grid::new("ClrCallGrid").num_columns(2).show(ui, |ui| {
+            ui.label("Type");
+            ui.add(TextEdit::singleline(&mut call.type_name).desired_width(300.0));
+            ui.end_row();
+            ui.label("Method");
+            ui.add(TextEdit::singleline(&mut call.method).desired_width(300.0));
+            ui.end_row();
+            ui.label("Argument");
+            ui.add(TextEdit::singleline(&mut call.argument).desired_width(300.0));
+            ui.end_row();
+            ui.label("Runtime");
+            ui.add(TextEdit::singleline(&mut call.runtime_version).desired_width(300.0));
+            ui.end_row();
+        });
+    });
+}
@@ -50,13 +50,6 @@ impl Widget for EmojiButtonWidget {
         let label_response = ui.put(response.rect, emoji_label);
 
         // Combine the responses
-        let combined_response = response.union(label_response);
-
-        // Check for clicks
-        if combined_response.clicked() {
-            println!("Button with custom label was clicked!");
-        }
-
-        combined_response
+        response.union(label_response)
     }
 }
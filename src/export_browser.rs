@@ -0,0 +1,205 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use egui::{Color32, Ui};
+use egui_extras::{Column, TableBuilder};
+use tracing::error;
+
+use crate::utils::pe_exports::{
+    ExportEntry, ForwarderResolver, ModuleExports, read_exports, system_module_locator,
+};
+
+type ExportsResult = Result<ModuleExports, String>;
+
+// The export table of a DLL from the inject list or a module loaded in the
+// target, in a window of its own. Resolving forwarders reads the DLLs they
+// point to, so the table is read on a background thread.
+pub struct ExportBrowser {
+    pub visible: bool,
+    title: String,
+    path: PathBuf,
+    loading: Option<Receiver<ExportsResult>>,
+    exports: ExportsResult,
+    search: String,
+    demangle: bool,
+}
+
+impl ExportBrowser {
+    // Forwarders resolve to the `loaded` modules (name, path) first, e.g.
+    // those of the target, then to where Windows would look for them.
+    pub fn open(path: &Path, loaded: Vec<(String, String)>) -> Self {
+        let title = path
+            .file_name()
+            .map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned());
+        let mut browser = ExportBrowser {
+            visible: true,
+            title,
+            path: path.to_path_buf(),
+            loading: None,
+            exports: Err("nothing read yet".to_owned()),
+            search: String::new(),
+            demangle: true,
+        };
+        let (result_tx, result_rx) = mpsc::channel();
+        let path = path.to_path_buf();
+        let spawned = thread::Builder::new().name("export-read".to_owned()).spawn(move || {
+            let system = system_module_locator(&path);
+            let locate = |name: &str| {
+                loaded
+                    .iter()
+                    .find(|(module, _)| module.eq_ignore_ascii_case(name))
+                    .map(|(_, path)| PathBuf::from(path))
+                    .or_else(|| system(name))
+            };
+            let _ = result_tx.send(read_exports(&path, &mut ForwarderResolver::new(&locate)));
+        });
+        match spawned {
+            Ok(_) => browser.loading = Some(result_rx),
+            Err(err) => error!("Failed to spawn the export reader thread: {}", err),
+        }
+        browser
+    }
+
+    fn poll(&mut self) {
+        let Some(loading) = &self.loading else {
+            return;
+        };
+        self.exports = match loading.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err("the reader ended without a result".to_owned()),
+        };
+        self.loading = None;
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        self.poll();
+        if self.loading.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+        let mut open = self.visible;
+        egui::Window::new(format!("Exports of {}", self.title))
+            .id(egui::Id::new("ExportBrowser"))
+            .open(&mut open)
+            .default_size([700.0, 400.0])
+            .show(ctx, |ui| self.contents(ui));
+        self.visible = open;
+    }
+
+    fn contents(&mut self, ui: &mut Ui) {
+        ui.label(self.path.display().to_string());
+        if self.loading.is_some() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Reading exports and resolving forwarders…");
+            });
+            return;
+        }
+        let exports = match &self.exports {
+            Ok(exports) => exports,
+            Err(err) => {
+                ui.colored_label(Color32::LIGHT_RED, format!("Failed to read exports: {}", err));
+                return;
+            },
+        };
+        let shown: Vec<&ExportEntry> =
+            exports.entries.iter().filter(|entry| entry.matches(&self.search)).collect();
+
+        ui.horizontal(|ui| {
+            ui.label("🔍");
+            ui.add(egui::TextEdit::singleline(&mut self.search).desired_width(200.0));
+            ui.checkbox(&mut self.demangle, "Demangle");
+            if ui.button("📋 Copy").on_hover_text("Copy the shown exports").clicked() {
+                let text = shown.iter().map(|entry| entry.to_line()).collect::<Vec<_>>().join("\n");
+                ui.ctx().copy_text(text);
+            }
+            ui.label(format!(
+                "{} of {} exports{}",
+                shown.len(),
+                exports.entries.len(),
+                exports
+                    .dll_name
+                    .as_ref()
+                    .map_or(String::new(), |name| format!(", linked as {}", name))
+            ));
+        });
+        ui.separator();
+
+        let demangle = self.demangle;
+        TableBuilder::new(ui)
+            .striped(true)
+            .resizable(true)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .column(Column::initial(60.0)) // Ordinal
+            .column(Column::initial(260.0).clip(true)) // Name
+            .column(Column::initial(90.0)) // RVA
+            .column(Column::remainder().clip(true)) // Forwarded to
+            .header(20.0, |mut header| {
+                for title in ["Ordinal", "Name", "RVA", "Forwarded to"] {
+                    header.col(|ui| {
+                        ui.strong(title);
+                    });
+                }
+            })
+            .body(|body| {
+                body.rows(18.0, shown.len(), |mut row| {
+                    let entry = shown[row.index()];
+                    row.col(|ui| {
+                        ui.monospace(entry.ordinal.to_string());
+                    });
+                    row.col(|ui| {
+                        let name = match (&entry.name, &entry.demangled) {
+                            (_, Some(demangled)) if demangle => demangled.as_str(),
+                            (Some(name), _) => name.as_str(),
+                            (None, _) => "(by ordinal)",
+                        };
+                        let response = ui.monospace(name);
+                        if let Some(mangled) =
+                            entry.name.as_ref().filter(|_| entry.demangled.is_some())
+                        {
+                            response.clone().on_hover_text(mangled);
+                        }
+                        response.context_menu(|ui| {
+                            if ui.button("Copy name").clicked() {
+                                ui.ctx().copy_text(entry.name.clone().unwrap_or_default());
+                                ui.close_menu();
+                            }
+                            if let Some(demangled) = entry.demangled.as_ref() {
+                                if ui.button("Copy demangled name").clicked() {
+                                    ui.ctx().copy_text(demangled.clone());
+                                    ui.close_menu();
+                                }
+                            }
+                            if ui.button("Copy row").clicked() {
+                                ui.ctx().copy_text(entry.to_line());
+                                ui.close_menu();
+                            }
+                        });
+                    });
+                    row.col(|ui| {
+                        ui.monospace(format!("{:#x}", entry.rva));
+                    });
+                    row.col(|ui| match entry.forwarder.as_ref() {
+                        None => {
+                            ui.weak("—");
+                        },
+                        Some(forwarder) => match &forwarder.resolved {
+                            Ok(resolved) => {
+                                ui.label(format!(
+                                    "{} → {}+{:#x}",
+                                    forwarder.target, resolved.module, resolved.rva
+                                ))
+                                .on_hover_text(resolved.chain.join(" → "));
+                            },
+                            Err(err) => {
+                                ui.colored_label(Color32::YELLOW, &forwarder.target)
+                                    .on_hover_text(err);
+                            },
+                        },
+                    });
+                });
+            });
+    }
+}
@@ -0,0 +1,299 @@
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use egui::{Color32, RichText, Ui};
+use tracing::{error, info};
+
+use crate::utils::hook_scan::{CodePatch, IatHook, ModuleScan, scan_hooks};
+use crate::utils::process_details::format_bytes;
+use crate::utils::target_process::TargetProcess;
+use crate::utils::text_assembler::{DisassembledInstruction, hex_bytes};
+
+const HOOKED: Color32 = Color32::from_rgb(255, 165, 0);
+
+type ScanResult = Result<Vec<ModuleScan>, String>;
+
+// Instructions overlapping the patch are highlighted.
+fn listing(ui: &mut Ui, code: &[DisassembledInstruction], patch: &CodePatch) {
+    let patched = patch.range();
+    for instruction in code {
+        let start = instruction.address as usize;
+        let end = start + instruction.bytes.len();
+        let text = RichText::new(format!(
+            "{:x}  {:<24} {}",
+            instruction.address,
+            hex_bytes(&instruction.bytes),
+            instruction.text
+        ))
+        .monospace();
+        ui.label(if start < patched.end && patched.start < end {
+            text.color(HOOKED)
+        } else {
+            text
+        });
+    }
+}
+
+fn address_label(ui: &mut Ui, address: usize, view: &mut Option<usize>) {
+    ui.label(RichText::new(format!("{:#x}", address)).monospace()).context_menu(|ui| {
+        if ui.button("View memory").clicked() {
+            *view = Some(address);
+            ui.close_menu();
+        }
+        if ui.button("Copy address").clicked() {
+            ui.ctx().copy_text(format!("{:#x}", address));
+            ui.close_menu();
+        }
+    });
+}
+
+// Inline hooks and redirected imports in the modules of the selected process,
+// found by comparing them with their files on disk. The scan reads every code
+// section, so it runs on a background thread.
+pub struct HookInspector {
+    pub visible: bool,
+    target: TargetProcess,
+    scanning: Option<Receiver<ScanResult>>,
+    result: ScanResult,
+    only_findings: bool,
+    search: String,
+}
+
+impl HookInspector {
+    pub fn open(target: &TargetProcess) -> Self {
+        let mut inspector = HookInspector {
+            visible: true,
+            target: target.clone(),
+            scanning: None,
+            result: Err("nothing scanned yet".to_owned()),
+            only_findings: true,
+            search: String::new(),
+        };
+        inspector.rescan();
+        inspector
+    }
+
+    pub fn target(&self) -> &TargetProcess {
+        &self.target
+    }
+
+    fn rescan(&mut self) {
+        let (result_tx, result_rx) = mpsc::channel();
+        let target = self.target.clone();
+        let spawned = thread::Builder::new().name("hook-scan".to_owned()).spawn(move || {
+            let result = target
+                .resolve_live()
+                .map_err(|err| err.to_string())
+                .and_then(|process| scan_hooks(&process));
+            let _ = result_tx.send(result);
+        });
+        match spawned {
+            Ok(_) => self.scanning = Some(result_rx),
+            Err(err) => error!("Failed to spawn the hook scan thread: {}", err),
+        }
+    }
+
+    fn poll(&mut self) {
+        let Some(scanning) = &self.scanning else {
+            return;
+        };
+        self.result = match scanning.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err("the scan ended without a result".to_owned()),
+        };
+        self.scanning = None;
+        if let Ok(modules) = &self.result {
+            info!(
+                "Hook scan of {}: {} patched code sites, {} redirected imports",
+                self.target.process().name,
+                modules.iter().map(|module| module.patches.len()).sum::<usize>(),
+                modules.iter().map(|module| module.iat_hooks.len()).sum::<usize>()
+            );
+        }
+    }
+
+    fn shows(&self, module: &ModuleScan) -> bool {
+        let search = self.search.to_lowercase();
+        (!self.only_findings || module.has_findings() || module.error.is_some())
+            && (search.is_empty()
+                || module.name.to_lowercase().contains(&search)
+                || module.patches.iter().any(|patch| {
+                    patch
+                        .symbol
+                        .as_ref()
+                        .is_some_and(|symbol| symbol.to_lowercase().contains(&search))
+                })
+                || module.iat_hooks.iter().any(|hook| hook.import.to_lowercase().contains(&search)))
+    }
+
+    // Returns an address to open in the memory viewer.
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<usize> {
+        self.poll();
+        if self.scanning.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+        let mut open = self.visible;
+        let mut view = None;
+        egui::Window::new(format!(
+            "Hooks in {} (PID {})",
+            self.target.process().name,
+            self.target.process().pid
+        ))
+        .id(egui::Id::new("HookInspector"))
+        .open(&mut open)
+        .default_size([820.0, 520.0])
+        .show(ctx, |ui| view = self.contents(ui));
+        self.visible = open;
+        view
+    }
+
+    fn contents(&mut self, ui: &mut Ui) -> Option<usize> {
+        ui.horizontal(|ui| {
+            let scanning = self.scanning.is_some();
+            if ui.add_enabled(!scanning, egui::Button::new("🔄 Rescan")).clicked() {
+                self.rescan();
+            }
+            if scanning {
+                ui.spinner();
+                ui.label("Comparing modules with their files…");
+            }
+            ui.label("🔍");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.search)
+                    .desired_width(160.0)
+                    .hint_text("module, export or import"),
+            );
+            ui.checkbox(&mut self.only_findings, "Only modules with findings or errors");
+        });
+        let modules = match &self.result {
+            Ok(modules) => modules,
+            Err(err) => {
+                if self.scanning.is_none() {
+                    ui.colored_label(Color32::LIGHT_RED, err);
+                }
+                return None;
+            },
+        };
+
+        let unchecked = modules.iter().filter(|module| module.error.is_some()).count();
+        ui.label(format!(
+            "{} modules, {} of code and {} imports compared: {} patches, {} redirected imports",
+            modules.len(),
+            format_bytes(modules.iter().map(|module| module.code_bytes as u64).sum()),
+            modules.iter().map(|module| module.imports_checked).sum::<usize>(),
+            modules.iter().map(|module| module.patches.len()).sum::<usize>(),
+            modules.iter().map(|module| module.iat_hooks.len()).sum::<usize>()
+        ));
+        if unchecked > 0 {
+            ui.colored_label(
+                Color32::YELLOW,
+                format!(
+                    "{} modules were not fully compared, expand them for the reason",
+                    unchecked
+                ),
+            );
+        }
+        ui.separator();
+
+        let mut view = None;
+        egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+            for module in modules.iter().filter(|module| self.shows(module)) {
+                let mut title = RichText::new(format!(
+                    "{} at {:#x}: {} patches, {} redirected imports",
+                    module.name,
+                    module.base,
+                    module.patches.len(),
+                    module.iat_hooks.len()
+                ));
+                if module.has_findings() {
+                    title = title.color(HOOKED);
+                } else if module.error.is_some() {
+                    title = title.color(Color32::YELLOW);
+                }
+                egui::CollapsingHeader::new(title)
+                    .id_source(("HookModule", module.base))
+                    .show(ui, |ui| module_details(ui, module, &mut view));
+            }
+        });
+        view
+    }
+}
+
+fn module_details(ui: &mut Ui, module: &ModuleScan, view: &mut Option<usize>) {
+    ui.weak(&module.path);
+    if let Some(err) = &module.error {
+        ui.colored_label(Color32::YELLOW, format!("Not compared: {}", err));
+    }
+    for patch in &module.patches {
+        let title = format!(
+            "{} {} ({} bytes){}",
+            patch.section,
+            patch.symbol.as_deref().unwrap_or(""),
+            patch.current.len(),
+            patch
+                .destination
+                .as_ref()
+                .map_or(String::new(), |destination| format!(" → {}", destination.describe()))
+        );
+        egui::CollapsingHeader::new(RichText::new(title).monospace())
+            .id_source(("HookPatch", patch.address))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Patch at");
+                    address_label(ui, patch.address, view);
+                    if ui.small_button("View memory").clicked() {
+                        *view = Some(patch.address);
+                    }
+                    if let Some(destination) = &patch.destination {
+                        ui.label("jumps to");
+                        address_label(ui, destination.address, view);
+                        if destination.module.is_none() {
+                            ui.colored_label(HOOKED, "not backed by a module");
+                        }
+                    }
+                });
+                ui.label(
+                    RichText::new(format!(
+                        "disk:   {}\nmemory: {}",
+                        hex_bytes(&patch.original),
+                        hex_bytes(&patch.current)
+                    ))
+                    .monospace(),
+                );
+                ui.columns(2, |columns| {
+                    columns[0].strong("On disk");
+                    listing(&mut columns[0], &patch.original_code, patch);
+                    columns[1].strong("In memory");
+                    listing(&mut columns[1], &patch.current_code, patch);
+                });
+            });
+    }
+    if !module.iat_hooks.is_empty() {
+        ui.strong("Redirected imports");
+        egui::Grid::new(("HookIat", module.base)).striped(true).show(ui, |ui| {
+            for title in ["Import", "Slot", "Points to", "Expected"] {
+                ui.strong(title);
+            }
+            ui.end_row();
+            for hook in &module.iat_hooks {
+                iat_row(ui, hook, view);
+            }
+        });
+    }
+}
+
+fn iat_row(ui: &mut Ui, hook: &IatHook, view: &mut Option<usize>) {
+    ui.monospace(&hook.import);
+    address_label(ui, hook.slot, view);
+    let current = RichText::new(hook.current.describe()).monospace().color(HOOKED);
+    ui.label(current).context_menu(|ui| {
+        if ui.button("View memory").clicked() {
+            *view = Some(hook.current.address);
+            ui.close_menu();
+        }
+    });
+    address_label(ui, hook.expected, view);
+    ui.end_row();
+}
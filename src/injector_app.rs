@@ -1,23 +1,76 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
 
 // use dll_syringe::process::OwnedProcess;
 // use dll_syringe::Syringe;
-use egui::{ComboBox, PointerButton, Ui, Vec2};
+use egui::{
+    CollapsingHeader, Color32, ComboBox, DragValue, Grid, PointerButton, RichText, SelectableLabel,
+    TextEdit, Ui, Vec2,
+};
 use egui_extras::{Column, TableBuilder};
-use libmem::Process;
+use libmem::process::find_process;
+use libmem::{Module, Process};
 use obfstr::obfstr;
+use rfd::FileDialog;
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::filter::LevelFilter;
 
-// use tracing::{error, info};
-use crate::dll_info::{DllInfo, dll_list_buttons_column};
+use crate::dll_info::{
+    DllAddSummary, DllInfo, add_dll_paths, clr_details_pane, dll_list_buttons_column,
+    elf_details_pane,
+};
 use crate::emoji_button_widget::EmojiButtonWidget;
 use crate::emoji_label_widget::EmojiLabelWidget;
+use crate::export_browser::ExportBrowser;
+use crate::hook_inspector::HookInspector;
+use crate::log_console::{LogBuffer, LogConsole};
+use crate::log_files::{LogHandle, open_log_folder};
+use crate::memory_viewer::MemoryViewer;
 use crate::process_selection_method::ProcessSelectionMethod;
 use crate::process_selection_method::ProcessSelectionMethod::{ByPID, ByPIDInput, ByProcessName};
-use crate::utils::processlist::{get_process_list, inject_dll_test_fix};
+use crate::region_map::RegionMap;
+use crate::shellcode_workbench::ShellcodeWorkbench;
+use crate::utils::access_check::{AccessCheck, AccessVerdict, check_injection_access};
+use crate::utils::authenticode::{SignaturePolicy, SignatureStatus, SignatureVerifier, TrustStore};
+use crate::utils::debug_symbols::{
+    SymbolPath, request_symbols, saved_symbol_path, set_symbol_path, symbols_loading,
+};
+use crate::utils::injection_history::{
+    InjectionHistory, InjectionRecord, PinPolicy, PinnedHash, export_csv, export_json, file_sha256,
+};
+use crate::utils::injection_worker::{FailurePolicy, InjectionJob, InjectionWorker};
+use crate::utils::process_details::{ProcessDetails, format_bytes, format_duration};
+use crate::utils::process_watcher::{
+    ProcessChangeKind, ProcessChangeTracker, ProcessKey, ProcessSnapshot, ProcessWatcher,
+    process_key,
+};
+use crate::utils::processlist::{CleanupPolicy, InjectOptions, get_process_list};
+use crate::utils::remote_allocations::forget_process;
+use crate::utils::target_process::{TargetProcess, TargetState};
+
+const DEFAULT_AUTO_REFRESH_INTERVAL_SECS: u64 = 3;
+const DEFAULT_INJECTION_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_MODULE_WAIT_TIMEOUT_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MainTab {
+    Injector,
+    History,
+}
 
 impl Default for InjectorApp {
     fn default() -> Self {
+        // The first enumeration happens before the window is shown, later ones
+        // run on the watcher thread.
+        let process_list = get_process_list();
+        let process_watcher = ProcessWatcher::spawn(
+            process_list.clone(),
+            Some(Duration::from_secs(DEFAULT_AUTO_REFRESH_INTERVAL_SECS)),
+        );
         Self {
             combo_box_process_name: "".to_owned(),
             combo_box_pid: "".to_owned(),
@@ -25,12 +78,40 @@ impl Default for InjectorApp {
             // checkbox_value: false,
             text_edit_value: "".to_owned(),
             // process_architecture: "x64".to_owned(),
-            process_list: get_process_list(),
-            current_process_selected_index: 0,
+            process_list,
+            selected_process: None,
+            process_details: None,
+            access_check: None,
+            details_loading: None,
+            process_watcher,
+            process_changes: ProcessChangeTracker::default(),
+            auto_refresh_enabled: true,
+            auto_refresh_interval_secs: DEFAULT_AUTO_REFRESH_INTERVAL_SECS,
+            last_enumeration_time: Duration::ZERO,
             // focused_item_index: Some(0),
             selected_row: None,
             dll_list_vector: Vec::new(),
-            show_popup_error_dll_already_added: false,
+            dll_add_summary: None,
+            log_console: LogConsole::new(LogBuffer::default()),
+            log_handle: None,
+            export_browser: None,
+            hook_inspector: None,
+            symbol_path_text: saved_symbol_path().to_string(),
+            memory_viewer: None,
+            region_map: None,
+            shellcode_workbench: ShellcodeWorkbench::default(),
+            main_tab: MainTab::Injector,
+            history: InjectionHistory::default(),
+            history_filter: String::new(),
+            history_result_filter: None,
+            injection_worker: None,
+            injection_timeout_secs: DEFAULT_INJECTION_TIMEOUT_SECS,
+            cleanup_on_timeout: CleanupPolicy::Leak,
+            module_wait_timeout_secs: DEFAULT_MODULE_WAIT_TIMEOUT_SECS,
+            failure_policy: FailurePolicy::Continue,
+            trust_store: TrustStore::load_default(),
+            signature_verifier: SignatureVerifier::default(),
+            signature_policy: SignaturePolicy::Allow,
         }
     }
 }
@@ -43,14 +124,412 @@ pub struct InjectorApp {
     text_edit_value: String,
     // process_architecture: String,
     process_list: Vec<Process>,
-    current_process_selected_index: usize,
+    selected_process: Option<TargetProcess>,
+    process_details: Option<ProcessDetails>,
+    access_check: Option<AccessCheck>,
+    // Details of the selected process being collected on a worker thread.
+    details_loading: Option<(ProcessKey, Receiver<(ProcessDetails, AccessCheck)>)>,
+    process_watcher: ProcessWatcher,
+    process_changes: ProcessChangeTracker,
+    auto_refresh_enabled: bool,
+    auto_refresh_interval_secs: u64,
+    last_enumeration_time: Duration,
     // focused_item_index: Option<usize>,
     selected_row: Option<usize>,
     dll_list_vector: Vec<DllInfo>,
-    show_popup_error_dll_already_added: bool,
+    dll_add_summary: Option<DllAddSummary>,
+    log_console: LogConsole,
+    log_handle: Option<LogHandle>,
+    export_browser: Option<ExportBrowser>,
+    hook_inspector: Option<HookInspector>,
+    symbol_path_text: String,
+    memory_viewer: Option<MemoryViewer>,
+    region_map: Option<RegionMap>,
+    shellcode_workbench: ShellcodeWorkbench,
+    main_tab: MainTab,
+    history: InjectionHistory,
+    history_filter: String,
+    // None shows every attempt, otherwise only successes or failures.
+    history_result_filter: Option<bool>,
+    injection_worker: Option<InjectionWorker>,
+    injection_timeout_secs: u64,
+    cleanup_on_timeout: CleanupPolicy,
+    module_wait_timeout_secs: u64,
+    failure_policy: FailurePolicy,
+    trust_store: TrustStore,
+    signature_verifier: SignatureVerifier,
+    signature_policy: SignaturePolicy,
 }
 
 impl InjectorApp {
+    pub fn new(log_buffer: LogBuffer, log_handle: LogHandle) -> Self {
+        Self {
+            log_console: LogConsole::new(log_buffer),
+            log_handle: Some(log_handle),
+            ..Default::default()
+        }
+    }
+
+    // Run the jobs in order on a worker thread. Each attempt gets the next
+    // history id, which doubles as the injection id the log console groups by.
+    fn start_injection(&mut self, mut jobs: Vec<InjectionJob>) {
+        if self.injection_worker.is_some() {
+            warn!("An injection is already running");
+            return;
+        }
+        let first_id = self.history.next_id();
+        for (index, job) in jobs.iter_mut().enumerate() {
+            job.id = first_id + index as u64;
+        }
+        let options = InjectOptions {
+            timeout: Duration::from_secs(self.injection_timeout_secs),
+            cleanup_on_timeout: self.cleanup_on_timeout,
+            module_wait_timeout: Duration::from_secs(self.module_wait_timeout_secs),
+            signature_policy: self.signature_policy,
+            trust_store: Arc::new(self.trust_store.clone()),
+            ..Default::default()
+        };
+        self.injection_worker = Some(InjectionWorker::spawn(jobs, options, self.failure_policy));
+    }
+
+    // Apply finished signature checks and start them for PE files that have
+    // not been checked yet.
+    fn check_signatures(&mut self, ctx: &egui::Context) {
+        for (path, check) in self.signature_verifier.poll() {
+            for dll in self.dll_list_vector.iter_mut().filter(|dll| dll.dll_path == path) {
+                dll.signature = Some(check.clone());
+            }
+        }
+        let unchecked: Vec<String> = self
+            .dll_list_vector
+            .iter()
+            .filter(|dll| dll.elf.is_none() && dll.signature.is_none())
+            .filter(|dll| !self.signature_verifier.is_pending(&dll.dll_path))
+            .map(|dll| dll.dll_path.clone())
+            .collect();
+        if !unchecked.is_empty() {
+            self.signature_verifier.verify(unchecked, &self.trust_store);
+        }
+        if self.signature_verifier.is_busy() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+    }
+
+    // Both open the process and query it, which can take a while on protected
+    // targets, so they run on a worker thread.
+    fn load_process_details(&mut self, target: &TargetProcess) {
+        let (result_tx, result_rx) = mpsc::channel();
+        let process = target.process().clone();
+        let spawned = thread::Builder::new().name("process-details".to_owned()).spawn(move || {
+            let _ = result_tx
+                .send((ProcessDetails::collect(&process), check_injection_access(&process)));
+        });
+        match spawned {
+            Ok(_) => self.details_loading = Some((target.key(), result_rx)),
+            Err(err) => error!("Failed to spawn the process details thread: {}", err),
+        }
+    }
+
+    fn poll_process_details(&mut self, ctx: &egui::Context) {
+        let Some((_, loading)) = &self.details_loading else {
+            return;
+        };
+        match loading.try_recv() {
+            Ok((details, check)) => {
+                self.process_details = Some(details);
+                self.access_check = Some(check);
+            },
+            Err(TryRecvError::Empty) => {
+                ctx.request_repaint_after(Duration::from_millis(100));
+                return;
+            },
+            Err(TryRecvError::Disconnected) => {},
+        }
+        self.details_loading = None;
+    }
+
+    // Run a recorded injection again. The recorded PID is usually gone by
+    // now, so the target is looked up by name, newest instance first.
+    fn repeat_injection(&mut self, record: &InjectionRecord) {
+        let target = self
+            .process_list
+            .iter()
+            .filter(|process| process.name.eq_ignore_ascii_case(&record.target_name))
+            .max_by_key(|process| process.start_time)
+            .and_then(|process| TargetProcess::new(process).resolve_live().ok())
+            .or_else(|| find_process(&record.target_name));
+        let Some(target) = target else {
+            warn!(
+                "Cannot repeat injection #{}: no process named {} is running",
+                record.id, record.target_name
+            );
+            return;
+        };
+
+        if let (Some(recorded), Ok(current)) =
+            (record.dll_sha256.as_ref(), file_sha256(Path::new(&record.dll_path)))
+        {
+            if *recorded != current {
+                warn!("{} has changed since injection #{}", record.dll_path, record.id);
+            }
+        }
+        info!("Repeating injection #{} into {} (PID {})", record.id, target.name, target.pid);
+        self.selected_process = Some(TargetProcess::new(&target));
+        self.start_injection(vec![InjectionJob {
+            managed: record.managed.clone(),
+            ..InjectionJob::new(target, record.dll_path.clone())
+        }]);
+    }
+
+    // Files and folders dropped anywhere on the window go to the inject list.
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let (hovering, dropped) = ctx.input(|i| {
+            let dropped: Vec<PathBuf> =
+                i.raw.dropped_files.iter().filter_map(|file| file.path.clone()).collect();
+            (!i.raw.hovered_files.is_empty(), dropped)
+        });
+
+        if hovering {
+            let painter = ctx
+                .layer_painter(egui::LayerId::new(egui::Order::Foreground, "DropOverlay".into()));
+            let rect = ctx.screen_rect();
+            painter.rect_filled(rect, 0.0, Color32::from_black_alpha(160));
+            painter.text(
+                rect.center(),
+                egui::Align2::CENTER_CENTER,
+                obfstr!("Drop DLLs or folders to add them to the inject list"),
+                egui::FontId::proportional(20.0),
+                Color32::WHITE,
+            );
+        }
+
+        if !dropped.is_empty() {
+            self.main_tab = MainTab::Injector;
+            self.dll_add_summary = Some(add_dll_paths(&mut self.dll_list_vector, dropped));
+        }
+    }
+
+    fn history_tab(&mut self, ui: &mut Ui) {
+        let mut export: Option<&str> = None;
+        let mut clear = false;
+        ui.horizontal(|ui| {
+            ui.label("🔍");
+            ui.add(
+                TextEdit::singleline(&mut self.history_filter)
+                    .hint_text(obfstr!("Target, DLL, hash or error"))
+                    .desired_width(250.0),
+            );
+            ComboBox::from_id_source("HistoryResultFilter")
+                .selected_text(match self.history_result_filter {
+                    None => "All results",
+                    Some(true) => "Succeeded",
+                    Some(false) => "Failed",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.history_result_filter, None, "All results");
+                    ui.selectable_value(&mut self.history_result_filter, Some(true), "Succeeded");
+                    ui.selectable_value(&mut self.history_result_filter, Some(false), "Failed");
+                });
+            if ui.button(obfstr!("💾 Export CSV")).clicked() {
+                export = Some("csv");
+            }
+            if ui.button(obfstr!("💾 Export JSON")).clicked() {
+                export = Some("json");
+            }
+            if ui.button(obfstr!("🗑 Clear history")).clicked() {
+                clear = true;
+            }
+        });
+
+        // Newest first.
+        let shown: Vec<&InjectionRecord> = self
+            .history
+            .records()
+            .iter()
+            .rev()
+            .filter(|record| self.history_result_filter.is_none_or(|s| record.success == s))
+            .filter(|record| record.matches(&self.history_filter))
+            .collect();
+        ui.label(format!("{} of {} attempts", shown.len(), self.history.records().len()));
+
+        if let Some(extension) = export {
+            if let Some(path) = FileDialog::new()
+                .add_filter(extension.to_uppercase(), &[extension])
+                .set_file_name(format!("injection_history.{}", extension))
+                .save_file()
+            {
+                let result = match extension {
+                    "csv" => export_csv(&path, &shown),
+                    _ => export_json(&path, &shown),
+                };
+                match result {
+                    Ok(()) => info!("Exported {} records to {}", shown.len(), path.display()),
+                    Err(err) => error!("Failed to export history to {}: {}", path.display(), err),
+                }
+            }
+        }
+
+        let mut repeat = None;
+        ui.push_id("HistoryTable", |ui| {
+            TableBuilder::new(ui)
+                .striped(true)
+                .resizable(true)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .column(Column::auto()) // Time
+                .column(Column::auto()) // Target
+                .column(Column::auto()) // PID
+                .column(Column::auto()) // Arch
+                .column(Column::initial(150.0)) // DLL
+                .column(Column::auto()) // SHA-256
+                .column(Column::auto()) // Technique
+                .column(Column::auto()) // Duration
+                .column(Column::auto()) // Base
+                .column(Column::initial(200.0)) // Result
+                .column(Column::remainder()) // Repeat
+                .header(20.0, |mut header| {
+                    for title in [
+                        "Time",
+                        "Target",
+                        "PID",
+                        "Arch",
+                        "DLL",
+                        "SHA-256",
+                        "Technique",
+                        "Duration",
+                        "Base",
+                        "Result",
+                        "",
+                    ] {
+                        header.col(|ui| {
+                            ui.strong(title);
+                        });
+                    }
+                })
+                .body(|body| {
+                    body.rows(18.0, shown.len(), |mut row| {
+                        let record = shown[row.index()];
+                        row.col(|ui| {
+                            ui.label(record.timestamp.format("%Y-%m-%d %H:%M:%S").to_string());
+                        });
+                        row.col(|ui| {
+                            ui.label(&record.target_name).on_hover_text(&record.target_path);
+                        });
+                        row.col(|ui| {
+                            ui.label(record.target_pid.to_string());
+                        });
+                        row.col(|ui| {
+                            ui.label(&record.target_arch);
+                        });
+                        row.col(|ui| {
+                            ui.label(record.dll_name()).on_hover_text(&record.dll_path);
+                        });
+                        row.col(|ui| match record.dll_sha256.as_ref() {
+                            Some(hash) => {
+                                ui.monospace(&hash[..12.min(hash.len())]).on_hover_text(hash);
+                            },
+                            None => {
+                                ui.label("—");
+                            },
+                        });
+                        row.col(|ui| {
+                            ui.label(record.technique.map_or("—".to_owned(), |t| t.to_string()));
+                        });
+                        row.col(|ui| {
+                            ui.label(format!("{} ms", record.duration_ms));
+                        });
+                        row.col(|ui| {
+                            ui.monospace(
+                                record
+                                    .module_base
+                                    .map_or("—".to_owned(), |b| format!("{:#x}", b)),
+                            );
+                        });
+                        row.col(|ui| {
+                            if let Some(leftovers) = record.leftovers.as_ref() {
+                                ui.colored_label(Color32::YELLOW, "⚠")
+                                    .on_hover_text(format!("Left in the target: {}", leftovers));
+                            }
+                            match (record.error.as_ref(), record.return_value) {
+                                (None, Some(value)) => {
+                                    ui.colored_label(
+                                        Color32::LIGHT_GREEN,
+                                        format!("✔ Success, returned {}", value),
+                                    );
+                                },
+                                (None, None) => {
+                                    ui.colored_label(Color32::LIGHT_GREEN, "✔ Success");
+                                },
+                                (Some(err), _) => {
+                                    ui.colored_label(Color32::LIGHT_RED, format!("✖ {}", err))
+                                        .on_hover_text(err);
+                                },
+                            }
+                        });
+                        row.col(|ui| {
+                            if ui
+                                .button(obfstr!("🔁 Repeat"))
+                                .on_hover_text(obfstr!(
+                                    "Inject this DLL again into the newest process with this name"
+                                ))
+                                .clicked()
+                            {
+                                repeat = Some(record.clone());
+                            }
+                        });
+                    });
+                });
+        });
+
+        if clear {
+            self.history.clear();
+        }
+        if let Some(record) = repeat {
+            self.repeat_injection(&record);
+        }
+    }
+
+    fn log_file_settings(&self, ui: &mut Ui) {
+        let Some(log_handle) = self.log_handle.as_ref() else {
+            return;
+        };
+        ui.separator();
+        let mut level = log_handle.level();
+        ComboBox::from_id_source("LogLevelCombo")
+            .selected_text(format!("Log level: {}", level))
+            .show_ui(ui, |ui| {
+                for option in [
+                    LevelFilter::OFF,
+                    LevelFilter::ERROR,
+                    LevelFilter::WARN,
+                    LevelFilter::INFO,
+                    LevelFilter::DEBUG,
+                    LevelFilter::TRACE,
+                ] {
+                    ui.selectable_value(&mut level, option, option.to_string());
+                }
+            });
+        if level != log_handle.level() {
+            log_handle.set_level(level);
+        }
+
+        match log_handle.directory() {
+            Some(directory) => {
+                if ui
+                    .button(obfstr!("📂 Open log folder"))
+                    .on_hover_text(directory.display().to_string())
+                    .clicked()
+                {
+                    if let Err(err) = open_log_folder(directory) {
+                        error!("Failed to open {}: {}", directory.display(), err);
+                    }
+                }
+            },
+            None => {
+                ui.label(RichText::new(obfstr!("Log files disabled")).color(Color32::YELLOW));
+            },
+        }
+    }
+
     fn filter_system_services_and_daemon_processes(&self) -> Vec<&Process> {
         let mut sys32dir = PathBuf::from(std::env::var("SystemRoot").ok().unwrap());
         sys32dir.push("System32");
@@ -79,28 +558,557 @@ impl InjectorApp {
         // Collect the values (processes) from the HashMap into a Vec.
         unique_processes.values().copied().collect()
     }
+
+    fn apply_process_snapshot(&mut self, snapshot: ProcessSnapshot) {
+        for process in &snapshot.diff.exited {
+            forget_process(process_key(process));
+        }
+        self.process_changes.record(&snapshot.diff);
+        self.last_enumeration_time = snapshot.enumeration_time;
+        self.process_list = snapshot.processes;
+    }
+
+    fn selected_process_state(&self) -> Option<TargetState> {
+        self.selected_process.as_ref().map(|target| target.state(&self.process_list))
+    }
+
+    fn selected_process_tooltip(&self) -> Option<String> {
+        let target = self.selected_process.as_ref()?;
+        let process = target.process();
+        Some(format!(
+            "PID: {:#?}\nPPID: {:#?}\nArchitecture: {:#?}\nBits: {:#?}\nStart Time: \
+             {:#?}\nPath:\n{:#?}\nName: {:#?}{}",
+            process.pid,
+            process.ppid,
+            process.arch,
+            process.bits,
+            process.start_time,
+            process.path,
+            process.name,
+            if self.selected_process_state() == Some(TargetState::Exited) {
+                "\n(process exited)"
+            } else {
+                ""
+            }
+        ))
+    }
+
+    fn selected_process_label(&self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Selected process :\t");
+            match (&self.selected_process, self.selected_process_state()) {
+                (Some(target), Some(TargetState::Running)) => {
+                    ui.label(format!("{:#?}", target.process()));
+                    if let Some(check) =
+                        self.access_check.as_ref().filter(|check| check.key == target.key())
+                    {
+                        access_check_badge(ui, check);
+                    }
+                },
+                (Some(target), _) => {
+                    ui.colored_label(
+                        Color32::LIGHT_RED,
+                        format!(
+                            "Process exited: {} (PID {})",
+                            target.process().name,
+                            target.process().pid
+                        ),
+                    );
+                },
+                (None, _) => {
+                    ui.weak(obfstr!("No process selected"));
+                },
+            }
+        });
+    }
+
+    fn process_details_pane(&mut self, ui: &mut Ui) {
+        let Some(target) = self.selected_process.clone() else {
+            self.process_details = None;
+            self.access_check = None;
+            self.details_loading = None;
+            return;
+        };
+        let loading = self.details_loading.as_ref().is_some_and(|(key, _)| *key == target.key());
+        if !loading
+            && self.process_details.as_ref().is_none_or(|details| details.key != target.key())
+        {
+            self.load_process_details(&target);
+        }
+        let target = &target;
+
+        let mut open_exports = None;
+        let mut open_memory = None;
+        let mut open_regions = false;
+        let mut open_hooks = false;
+        CollapsingHeader::new(obfstr!("Process details")).default_open(true).show(ui, |ui| {
+            ui.horizontal(|ui| {
+                if loading {
+                    ui.spinner();
+                    ui.weak("Collecting details…");
+                } else if ui.button(obfstr!("🔄 Refresh details")).clicked() {
+                    self.load_process_details(target);
+                }
+                if ui
+                    .button(obfstr!("🔬 Memory"))
+                    .on_hover_text(obfstr!("View and patch the memory of the process"))
+                    .clicked()
+                {
+                    open_memory = Some(target.process().name.clone());
+                }
+                if ui
+                    .button(obfstr!("🗺 Regions"))
+                    .on_hover_text(obfstr!(
+                        "Memory regions of the process and the buffers our injections left there"
+                    ))
+                    .clicked()
+                {
+                    open_regions = true;
+                }
+                if ui
+                    .button(obfstr!("🪝 Hooks"))
+                    .on_hover_text(obfstr!(
+                        "Compare the loaded modules with their files to find inline and IAT hooks"
+                    ))
+                    .clicked()
+                {
+                    open_hooks = true;
+                }
+            });
+            symbol_path_settings(ui, &mut self.symbol_path_text);
+            let Some(details) = &self.process_details else {
+                return;
+            };
+            let unknown = || "unknown".to_owned();
+
+            Grid::new("ProcessDetailsGrid").num_columns(2).striped(true).show(ui, |ui| {
+                ui.label("Command line");
+                ui.label(details.command_line.clone().unwrap_or_else(unknown));
+                ui.end_row();
+                ui.label("Working directory");
+                ui.label(details.working_directory.clone().unwrap_or_else(unknown));
+                ui.end_row();
+                ui.label("User");
+                ui.label(details.user.clone().unwrap_or_else(unknown));
+                ui.end_row();
+                for (name, value) in details.security.describe() {
+                    ui.label(name);
+                    ui.label(value);
+                    ui.end_row();
+                }
+                ui.label("Resident memory");
+                ui.label(details.resident_memory.map_or_else(unknown, format_bytes));
+                ui.end_row();
+                ui.label("Virtual memory");
+                ui.label(details.virtual_memory.map_or_else(unknown, format_bytes));
+                ui.end_row();
+                ui.label("Threads");
+                ui.label(details.thread_count.map_or_else(unknown, |count| count.to_string()));
+                ui.end_row();
+                ui.label("Uptime");
+                ui.label(details.uptime().map_or_else(unknown, format_duration));
+                ui.end_row();
+                ui.label("Environment");
+                ui.label(details.environment_summary());
+                ui.end_row();
+            });
+
+            if !details.modules.is_empty() {
+                let modules = &details.modules;
+                CollapsingHeader::new(format!("Loaded modules ({})", modules.len())).show(
+                    ui,
+                    |ui| {
+                        egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                            for module in modules {
+                                ui.horizontal(|ui| {
+                                    if ui.small_button("🔎").on_hover_text("Exports").clicked() {
+                                        open_exports = Some(module.path.clone());
+                                    }
+                                    if ui.small_button("🔬").on_hover_text("Memory").clicked() {
+                                        open_memory = Some(format!("{:#x}", module.base));
+                                    }
+                                    if symbols_loading(Path::new(&module.path)) {
+                                        ui.spinner().on_hover_text("Loading debug symbols");
+                                    } else if ui
+                                        .small_button("🏷")
+                                        .on_hover_text("Load debug symbols")
+                                        .clicked()
+                                    {
+                                        load_module_symbols(module);
+                                    }
+                                    ui.monospace(format!("{:#x}", module.base));
+                                    ui.label(&module.name).on_hover_text(&module.path);
+                                });
+                            }
+                        });
+                    },
+                );
+            }
+
+            if !details.environment.is_empty() {
+                CollapsingHeader::new(obfstr!("Environment variables")).show(ui, |ui| {
+                    egui::ScrollArea::vertical().max_height(150.0).show(ui, |ui| {
+                        for var in &details.environment {
+                            ui.monospace(var);
+                        }
+                    });
+                });
+            }
+        });
+        if let Some(expression) = open_memory {
+            self.memory_viewer = Some(MemoryViewer::open(target, &expression));
+        }
+        if open_regions {
+            self.region_map = Some(RegionMap::open(target));
+        }
+        if open_hooks {
+            self.hook_inspector = Some(HookInspector::open(target));
+        }
+        if let Some(path) = open_exports {
+            self.open_module_exports(&path);
+        }
+    }
+
+    // Forwarders of a loaded module resolve to the modules the target has
+    // loaded, and only then to the system directory.
+    fn open_module_exports(&mut self, path: &str) {
+        let modules: Vec<(String, String)> = self
+            .process_details
+            .as_ref()
+            .map(|details| {
+                details
+                    .modules
+                    .iter()
+                    .map(|module| (module.name.clone(), module.path.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.export_browser = Some(ExportBrowser::open(Path::new(path), modules));
+    }
+
+    fn dll_exports_button(&mut self, ui: &mut Ui) {
+        let selected = self
+            .selected_row
+            .and_then(|index| self.dll_list_vector.iter().find(|dll| dll.index == index))
+            .filter(|dll| dll.pe_arch.is_some())
+            .map(|dll| PathBuf::from(&dll.dll_path));
+        if ui
+            .add_enabled(selected.is_some(), egui::Button::new(obfstr!("🔎 Exports")))
+            .on_hover_text(obfstr!("Browse the export table of the selected DLL"))
+            .clicked()
+        {
+            if let Some(path) = selected {
+                self.export_browser = Some(ExportBrowser::open(&path, Vec::new()));
+            }
+        }
+    }
+
+    fn auto_refresh_interval(&self) -> Option<Duration> {
+        self.auto_refresh_enabled.then(|| Duration::from_secs(self.auto_refresh_interval_secs))
+    }
+
+    fn process_entry_text(&self, process: &Process, text: String) -> RichText {
+        match self.process_changes.kind_of(process) {
+            Some(ProcessChangeKind::Started) => {
+                RichText::new(format!("🆕 {}", text)).color(Color32::LIGHT_GREEN)
+            },
+            Some(ProcessChangeKind::Exited) => RichText::new(text).strikethrough().weak(),
+            None => RichText::new(text),
+        }
+    }
+
+    fn show_exited_processes(&self, ui: &mut Ui) {
+        for process in self.process_changes.exited() {
+            let text = format!("{}\t{}\t{} (exited)", process.name, process.pid, process.ppid);
+            ui.add_enabled(
+                false,
+                SelectableLabel::new(false, self.process_entry_text(process, text)),
+            );
+        }
+    }
+
+    fn process_refresh_settings(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let toggled =
+                ui.checkbox(&mut self.auto_refresh_enabled, "Auto-refresh every").changed();
+            let interval_changed = ui
+                .add(
+                    DragValue::new(&mut self.auto_refresh_interval_secs)
+                        .range(1..=300)
+                        .suffix(" s"),
+                )
+                .changed();
+            if toggled || interval_changed {
+                self.process_watcher.set_interval(self.auto_refresh_interval());
+            }
+
+            if self.process_watcher.is_refreshing() {
+                ui.spinner();
+            }
+            ui.label(format!(
+                "{} processes, enumerated in {} ms",
+                self.process_list.len(),
+                self.last_enumeration_time.as_millis()
+            ));
+        });
+    }
+
+    fn signature_settings(&mut self, ui: &mut Ui) {
+        let mut trust_changed = false;
+        ui.horizontal(|ui| {
+            ComboBox::from_id_source("SignaturePolicyCombo")
+                .selected_text(match self.signature_policy {
+                    SignaturePolicy::Allow => "Signatures: not enforced",
+                    SignaturePolicy::BlockUnsigned => "Signatures: block unsigned",
+                    SignaturePolicy::BlockUntrusted => "Signatures: block untrusted",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(
+                        &mut self.signature_policy,
+                        SignaturePolicy::Allow,
+                        "Not enforced",
+                    );
+                    ui.selectable_value(
+                        &mut self.signature_policy,
+                        SignaturePolicy::BlockUnsigned,
+                        "Block unsigned",
+                    )
+                    .on_hover_text("Refuse DLLs without a valid signature");
+                    ui.selectable_value(
+                        &mut self.signature_policy,
+                        SignaturePolicy::BlockUntrusted,
+                        "Block untrusted",
+                    )
+                    .on_hover_text("Refuse DLLs not signed through a trusted certificate");
+                });
+            if ui.button(obfstr!("🔄 Re-check signatures")).clicked() {
+                trust_changed = true;
+            }
+        });
+
+        CollapsingHeader::new(format!(
+            "Trusted certificates ({})",
+            self.trust_store.certificates().len()
+        ))
+        .id_source("TrustedCertificates")
+        .show(ui, |ui| {
+            let mut removed = None;
+            for trusted in self.trust_store.certificates() {
+                ui.horizontal(|ui| {
+                    if ui.small_button("🗑").on_hover_text("Remove from the trusted set").clicked()
+                    {
+                        removed = Some(trusted.path.clone());
+                    }
+                    ui.label(&trusted.subject).on_hover_text(format!(
+                        "SHA-256: {}\nFile: {}",
+                        trusted.thumbprint,
+                        trusted.path.display()
+                    ));
+                });
+            }
+            if let Some(path) = removed {
+                match self.trust_store.remove(&path) {
+                    Ok(()) => info!("Removed trusted certificate {}", path.display()),
+                    Err(err) => error!("Failed to remove {}: {}", path.display(), err),
+                }
+                trust_changed = true;
+            }
+            if ui.button(obfstr!("➕ Add certificate...")).clicked() {
+                if let Some(path) = FileDialog::new()
+                    .add_filter(obfstr!("Certificates"), &["cer", "crt", "pem", "der"])
+                    .pick_file()
+                {
+                    match self.trust_store.add(&path) {
+                        Ok(()) => info!("Trusted certificate added from {}", path.display()),
+                        Err(err) => error!("Failed to add {}: {}", path.display(), err),
+                    }
+                    trust_changed = true;
+                }
+            }
+        });
+
+        if trust_changed {
+            self.signature_verifier.reset();
+            for dll in &mut self.dll_list_vector {
+                dll.signature = None;
+            }
+        }
+    }
+
+    fn injection_controls(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Remote thread timeout");
+            ui.add(DragValue::new(&mut self.injection_timeout_secs).range(1..=600).suffix(" s"))
+                .on_hover_text(obfstr!(
+                    "Only applies to x86 targets; x64 loads go through libmem and cannot be \
+                     interrupted"
+                ));
+            ComboBox::from_id_source("CleanupPolicyCombo")
+                .selected_text(match self.cleanup_on_timeout {
+                    CleanupPolicy::Leak => "On timeout: leak remote memory",
+                    CleanupPolicy::Free => "On timeout: free remote memory",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(
+                        &mut self.cleanup_on_timeout,
+                        CleanupPolicy::Leak,
+                        "Leak remote memory (safe)",
+                    )
+                    .on_hover_text("The remote thread may still be running and using it");
+                    ui.selectable_value(
+                        &mut self.cleanup_on_timeout,
+                        CleanupPolicy::Free,
+                        "Free remote memory",
+                    )
+                    .on_hover_text("Crashes the target if the remote thread resumes later");
+                });
+        });
+        ui.horizontal(|ui| {
+            ui.label("Wait-for-module timeout");
+            ui.add(DragValue::new(&mut self.module_wait_timeout_secs).range(1..=3600).suffix(" s"));
+            ComboBox::from_id_source("FailurePolicyCombo")
+                .selected_text(match self.failure_policy {
+                    FailurePolicy::Continue => "On failure: continue",
+                    FailurePolicy::Stop => "On failure: stop",
+                    FailurePolicy::RollBack => "On failure: stop and eject",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(
+                        &mut self.failure_policy,
+                        FailurePolicy::Continue,
+                        "Continue",
+                    )
+                    .on_hover_text("Inject the remaining DLLs anyway");
+                    ui.selectable_value(&mut self.failure_policy, FailurePolicy::Stop, "Stop")
+                        .on_hover_text("Skip the remaining DLLs");
+                    ui.selectable_value(
+                        &mut self.failure_policy,
+                        FailurePolicy::RollBack,
+                        "Stop and eject",
+                    )
+                    .on_hover_text("Skip the remaining DLLs and eject the ones already injected");
+                });
+        });
+
+        self.signature_settings(ui);
+
+        let Some(worker) = self.injection_worker.as_ref() else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.add(
+                egui::ProgressBar::new(worker.progress())
+                    .text(worker.status())
+                    .desired_width(400.0),
+            );
+            if worker.is_cancelled() {
+                ui.label("Cancelling...");
+            } else if ui.button(obfstr!("⏹ Cancel")).clicked() {
+                worker.cancel();
+            }
+        });
+    }
+}
+
+// Applied to every symbol lookup from the next one on.
+fn symbol_path_settings(ui: &mut Ui, text: &mut String) {
+    ui.horizontal(|ui| {
+        ui.label("Symbol path");
+        ui.add(
+            egui::TextEdit::singleline(text)
+                .desired_width(320.0)
+                .hint_text(r"C:\symbols;srv*C:\symcache*https://msdl.microsoft.com/..."),
+        )
+        .on_hover_text(obfstr!(
+            "Local directories with PDBs or separate DWARF files, separated by ;. Flat \
+             directories and symbol store layouts both work; nothing is downloaded. \
+             _NT_SYMBOL_PATH and the app's symbols directory are searched after these"
+        ));
+        let path = SymbolPath::parse(text);
+        if ui.add_enabled(path != saved_symbol_path(), egui::Button::new("Apply")).clicked() {
+            info!("Symbol path set to {}", path);
+            set_symbol_path(path);
+        }
+    });
+}
+
+// Starts loading the symbols right away, so a missing or mismatched PDB shows
+// up in the log before a memory view or hook report needs it. The background
+// load logs its own outcome.
+fn load_module_symbols(module: &Module) {
+    match request_symbols(Path::new(&module.path)) {
+        Some(Ok(symbols)) => info!(
+            "Loaded {} symbols for {} from {}",
+            symbols.len(),
+            module.name,
+            symbols.source.display()
+        ),
+        Some(Err(err)) => warn!("No debug symbols for {}: {}", module.name, err),
+        None => {},
+    }
+}
+
+fn access_check_badge(ui: &mut Ui, check: &AccessCheck) {
+    let (icon, color) = match check.verdict {
+        AccessVerdict::Allowed => ("✅", Color32::LIGHT_GREEN),
+        AccessVerdict::Denied => ("⛔", Color32::LIGHT_RED),
+        AccessVerdict::Unknown => ("❔", Color32::YELLOW),
+    };
+    let response = ui.colored_label(color, format!("{} {}", icon, check.summary()));
+    let details = check.details();
+    if !details.is_empty() {
+        response.on_hover_text(details);
+    }
 }
 
 fn dll_list_table(ui: &mut Ui, selected_row: &mut Option<usize>, dll_list: &mut Vec<DllInfo>) {
     let c = dll_list.to_owned();
+    // (from, to) positions of a row dropped onto another one this frame.
+    let mut moved: Option<(usize, usize)> = None;
 
     TableBuilder::new(ui)
         .striped(true)
         .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+        .column(Column::exact(20.0)) // Drag handle
         .column(Column::initial(100.0).at_least(40.0)) // First column
+        .column(Column::initial(70.0).at_least(70.0)) // Pin
         .column(Column::remainder().resizable(true)) // Second column
         .column(Column::remainder().resizable(true)) // Third column
+        .column(Column::initial(80.0).at_least(80.0)) // Delay
+        .column(Column::initial(120.0).at_least(80.0)) // Wait for module
+        .column(Column::initial(90.0).at_least(70.0)) // Signature
         .column(Column::remainder().resizable(true)) // Fourth column
         .header(20.0, |mut header| {
+            header.col(|ui| {
+                ui.label("#");
+            });
             header.col(|ui| {
                 ui.label("Switch");
             });
+            header.col(|ui| {
+                ui.label("Pin").on_hover_text(
+                    "Check the DLL against the SHA-256 recorded when it was added before \
+                     injecting it",
+                );
+            });
             header.col(|ui| {
                 ui.label("DLL Name");
             });
             header.col(|ui| {
                 ui.label("DLL Arch");
             });
+            header.col(|ui| {
+                ui.label("Delay").on_hover_text("Wait this long before injecting the DLL");
+            });
+            header.col(|ui| {
+                ui.label("Wait for module").on_hover_text(
+                    "Only inject once a module with this name is loaded in the target",
+                );
+            });
+            header.col(|ui| {
+                ui.label("Signature");
+            });
             header.col(|ui| {
                 ui.label("DLL Path");
             });
@@ -113,9 +1121,20 @@ fn dll_list_table(ui: &mut Ui, selected_row: &mut Option<usize>, dll_list: &mut
                     });
                 });
             } else {
-                for dll in dll_list {
+                for (position, dll) in dll_list.iter_mut().enumerate() {
                     let is_selected = *selected_row == Some(dll.index);
                     body.row(18.0, |mut row| {
+                        row.col(|ui| {
+                            ui.dnd_drag_source(
+                                egui::Id::new(("DllRow", dll.index)),
+                                position,
+                                |ui| {
+                                    ui.label("☰");
+                                },
+                            )
+                            .response
+                            .on_hover_text("Drag to change the injection order");
+                        });
                         row.col(|ui| {
                             let response = ui.checkbox(&mut dll.switch, "ON/OFF");
                             if response.clicked() {
@@ -123,28 +1142,137 @@ fn dll_list_table(ui: &mut Ui, selected_row: &mut Option<usize>, dll_list: &mut
                             }
                         });
                         row.col(|ui| {
-                            let response = ui.selectable_label(is_selected, &dll.dll_name);
+                            ui.add_enabled_ui(dll.sha256.is_some(), |ui| {
+                                ComboBox::from_id_source(("DllPinCombo", dll.index))
+                                    .width(60.0)
+                                    .selected_text(match dll.pin {
+                                        None => "Off",
+                                        Some(PinPolicy::Warn) => "Warn",
+                                        Some(PinPolicy::Refuse) => "Block",
+                                    })
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(&mut dll.pin, None, "Off");
+                                        ui.selectable_value(
+                                            &mut dll.pin,
+                                            Some(PinPolicy::Warn),
+                                            "Warn",
+                                        )
+                                        .on_hover_text("Log a warning and inject anyway");
+                                        ui.selectable_value(
+                                            &mut dll.pin,
+                                            Some(PinPolicy::Refuse),
+                                            "Block",
+                                        )
+                                        .on_hover_text("Refuse to inject a modified DLL");
+                                    });
+                            });
+                        });
+                        row.col(|ui| {
+                            let response = ui
+                                .selectable_label(is_selected, &dll.dll_name)
+                                .on_hover_text(format!(
+                                    "SHA-256: {}\nSize: {}",
+                                    dll.sha256.as_deref().unwrap_or("unknown"),
+                                    format_bytes(dll.file_size)
+                                ));
                             if response.clicked() {
                                 *selected_row = Some(dll.index);
                             }
                         });
                         row.col(|ui| {
-                            let response = ui.selectable_label(is_selected, &dll.dll_arch);
+                            let mut response = ui.selectable_label(is_selected, &dll.dll_arch);
+                            if let Some(arch) = dll.pe_arch.as_ref() {
+                                let mut details = format!("Machine: {:#06x}", arch.raw_machine);
+                                if let Some(clr) = arch.clr.as_ref() {
+                                    details += &format!(
+                                        "\nCLR header v{}.{}, flags {:#x}",
+                                        clr.runtime_version.0, clr.runtime_version.1, clr.flags
+                                    );
+                                }
+                                response = response.on_hover_text(details);
+                            }
                             if response.clicked() {
                                 *selected_row = Some(dll.index);
                             }
                         });
+                        row.col(|ui| {
+                            ui.add(
+                                DragValue::new(&mut dll.delay_ms).range(0..=600_000).suffix(" ms"),
+                            );
+                        });
+                        row.col(|ui| {
+                            ui.add(
+                                TextEdit::singleline(&mut dll.wait_for_module)
+                                    .hint_text("e.g. d3d11.dll"),
+                            );
+                        });
+                        row.col(|ui| {
+                            if let Some(check) = dll.signature.as_ref() {
+                                let (text, color) = match check.status {
+                                    SignatureStatus::Trusted(_) => ("✔ Trusted", Color32::GREEN),
+                                    SignatureStatus::Untrusted(_) => ("Untrusted", Color32::YELLOW),
+                                    SignatureStatus::Unsigned => ("Unsigned", Color32::GRAY),
+                                    SignatureStatus::Invalid(_) => ("✖ Invalid", Color32::RED),
+                                };
+                                ui.colored_label(color, text).on_hover_text(check.details());
+                            } else if dll.elf.is_none() {
+                                ui.weak("Checking…");
+                            }
+                        });
                         row.col(|ui| {
                             let response = ui.selectable_label(is_selected, &dll.dll_path);
                             if response.clicked() {
                                 *selected_row = Some(dll.index);
                             }
                         });
+
+                        let response = row.response();
+                        if let Some(from) = response.dnd_release_payload::<usize>() {
+                            moved = Some((*from, position));
+                        } else if response.dnd_hover_payload::<usize>().is_some() {
+                            // Show where the dragged row will land.
+                            let rect = response.rect;
+                            let color = response.ctx.style().visuals.selection.stroke.color;
+                            response.ctx.layer_painter(response.layer_id).hline(
+                                rect.x_range(),
+                                rect.top(),
+                                (2.0, color),
+                            );
+                        }
                     });
                 }
             }
         });
 
+    if let Some((from, to)) = moved.filter(|(from, to)| from != to && *from < dll_list.len()) {
+        let selected_path = selected_row
+            .and_then(|index| dll_list.iter().find(|dll| dll.index == index))
+            .map(|dll| dll.dll_path.clone());
+        let dll = dll_list.remove(from);
+        dll_list.insert(to.min(dll_list.len()), dll);
+        // Indexes follow the list order, as in remove_selected_dll.
+        for (i, dll) in dll_list.iter_mut().enumerate() {
+            dll.index = i + 1;
+        }
+        *selected_row = selected_path
+            .and_then(|path| dll_list.iter().find(|dll| dll.dll_path == path))
+            .map(|dll| dll.index);
+    }
+
+    if let Some(details) = selected_row
+        .and_then(|index| dll_list.iter().find(|dll| dll.index == index))
+        .and_then(|dll| dll.elf.as_ref())
+    {
+        elf_details_pane(ui, details);
+    }
+    if let Some(dll) =
+        selected_row.and_then(|index| dll_list.iter_mut().find(|dll| dll.index == index))
+    {
+        if let Some(clr) = dll.clr.as_ref() {
+            clr_details_pane(ui, clr, &mut dll.managed);
+        }
+    }
+
     ui.label(format!("Selected Row: {:?}", selected_row));
     ui.label(if selected_row.is_some() {
         format!("{:#?}", &c[selected_row.unwrap() - 1usize])
@@ -155,7 +1283,86 @@ fn dll_list_table(ui: &mut Ui, selected_row: &mut Option<usize>, dll_list: &mut
 
 impl eframe::App for InjectorApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if let Some(snapshot) = self.process_watcher.poll() {
+            self.apply_process_snapshot(snapshot);
+        }
+        self.process_changes.prune();
+        // Snapshots arrive from the watcher thread, so keep polling for them.
+        if self.process_watcher.is_refreshing()
+            || self.auto_refresh_enabled
+            || !self.process_changes.is_empty()
+        {
+            ctx.request_repaint_after(Duration::from_millis(250));
+        }
+
+        if let Some(worker) = self.injection_worker.as_mut() {
+            for record in worker.poll() {
+                self.history.push(record);
+            }
+            if worker.is_done() {
+                self.injection_worker = None;
+            } else {
+                ctx.request_repaint_after(Duration::from_millis(100));
+            }
+        }
+
+        self.check_signatures(ctx);
+        self.poll_process_details(ctx);
+        self.handle_dropped_files(ctx);
+
+        egui::TopBottomPanel::top("AppMenuPanel").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.main_tab, MainTab::Injector, obfstr!("💉 Injector"));
+                ui.selectable_value(&mut self.main_tab, MainTab::History, obfstr!("🕘 History"));
+                ui.separator();
+                ui.toggle_value(&mut self.log_console.visible, obfstr!("📜 Log console"));
+                ui.toggle_value(&mut self.shellcode_workbench.visible, obfstr!("🧪 Assembler"));
+                self.log_file_settings(ui);
+            });
+        });
+        self.log_console.show(ctx);
+        if let Some(browser) = self.export_browser.as_mut() {
+            browser.show(ctx);
+            if !browser.visible {
+                self.export_browser = None;
+            }
+        }
+        if let Some(region_map) = self.region_map.as_mut() {
+            if let Some(address) = region_map.show(ctx) {
+                self.memory_viewer =
+                    Some(MemoryViewer::open(region_map.target(), &format!("{:#x}", address)));
+            }
+            if !region_map.visible {
+                self.region_map = None;
+            }
+        }
+        if let Some(hook_inspector) = self.hook_inspector.as_mut() {
+            if let Some(address) = hook_inspector.show(ctx) {
+                self.memory_viewer =
+                    Some(MemoryViewer::open(hook_inspector.target(), &format!("{:#x}", address)));
+            }
+            if !hook_inspector.visible {
+                self.hook_inspector = None;
+            }
+        }
+        self.shellcode_workbench.show(
+            ctx,
+            self.selected_process.as_ref(),
+            Duration::from_secs(self.injection_timeout_secs),
+            self.cleanup_on_timeout,
+        );
+        if let Some(viewer) = self.memory_viewer.as_mut() {
+            viewer.show(ctx);
+            if !viewer.visible {
+                self.memory_viewer = None;
+            }
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
+            if self.main_tab == MainTab::History {
+                self.history_tab(ui);
+                return;
+            }
             ui.horizontal(|ui| {
                 ui.vertical(|ui| {
                     TableBuilder::new(ui)
@@ -170,17 +1377,9 @@ impl eframe::App for InjectorApp {
                                 row.col(|ui| {
                                     let resp1 = ui.add(EmojiLabelWidget::new(obfstr!("⚙ Process:\t\t")));
                                     if resp1.hovered() && self.radio_button_proc_sel_meth == ByProcessName {
-                                        let process = &self.process_list[self.current_process_selected_index];
-                                        resp1.show_tooltip_text(format!(
-                                            "PID: {:#?}\nPPID: {:#?}\nArchitecture: {:#?}\nBits: {:#?}\nStart Time: {:#?}\nPath:\n{:#?}\nName: {:#?}",
-                                            process.pid,
-                                            process.ppid,
-                                            process.arch,
-                                            process.bits,
-                                            process.start_time,
-                                            process.path,
-                                            process.name
-                                        ));
+                                        if let Some(tooltip) = self.selected_process_tooltip() {
+                                            resp1.show_tooltip_text(tooltip);
+                                        }
                                     }
                                 });
 
@@ -201,7 +1400,7 @@ impl eframe::App for InjectorApp {
                                             filtered_processes.sort_by_key(|process| &process.name);
 
                                             let mut new_selected_process_name = None;
-                                            let mut new_selected_process_index = None;
+                                            let mut new_selected_process = None;
 
                                             for process in &filtered_processes {
                                                 let selectable_text = format!("{}\t{}\t{}", process.name, process.pid, process.ppid);
@@ -209,24 +1408,25 @@ impl eframe::App for InjectorApp {
                                                 if ui.selectable_value(
                                                     &mut self.combo_box_process_name.as_str(),
                                                     process.name.as_str(),
-                                                    selectable_text.as_str(),
+                                                    self.process_entry_text(process, selectable_text),
                                                 ).clicked() && self.radio_button_proc_sel_meth == ByProcessName {
                                                     new_selected_process_name = Some(process.name.to_owned());
-                                                    new_selected_process_index = Some(self.process_list.iter().position(|x| x.pid == process.pid).unwrap());
+                                                    new_selected_process = Some(TargetProcess::new(process));
                                                 }
                                             }
+                                            self.show_exited_processes(ui);
 
                                             if let Some(name) = new_selected_process_name {
                                                 self.combo_box_process_name = name;
                                             }
 
-                                            if let Some(index) = new_selected_process_index {
-                                                self.current_process_selected_index = index;
+                                            if let Some(target) = new_selected_process {
+                                                self.selected_process = Some(target);
                                             }
                                         }).response;
 
                                     if cb1_resp.clicked_by(PointerButton::Primary) && self.radio_button_proc_sel_meth == ByProcessName {
-                                        self.process_list = get_process_list();
+                                        self.process_watcher.request_refresh();
                                     }
                                 });
                             });
@@ -240,25 +1440,14 @@ impl eframe::App for InjectorApp {
                             .column(Column::initial(100.0).at_least(100.0)) // Second column: Radio Button
                             .column(Column::initial(100.0).at_least(100.0)) // Third column: ComboBox
                             .body(|mut body| {
-
-
-                                
                                 body.row(18.0, |mut row| {
                                     // First column: Label
                                     row.col(|ui| {
                                         let resp2 = ui.add(EmojiLabelWidget::new(obfstr!("⚙🆔 PID:\t\t\t\t\t\t")));
                                         if resp2.hovered() && self.radio_button_proc_sel_meth == ByPID {
-                                            let process = &self.process_list[self.current_process_selected_index];
-                                            resp2.show_tooltip_text(format!(
-                                                "PID: {:#?}\nPPID: {:#?}\nArchitecture: {:#?}\nBits: {:#?}\nStart Time: {:#?}\nPath:\n{:#?}\nName: {:#?}",
-                                                process.pid,
-                                                process.ppid,
-                                                process.arch,
-                                                process.bits,
-                                                process.start_time,
-                                                process.path,
-                                                process.name
-                                            ));
+                                            if let Some(tooltip) = self.selected_process_tooltip() {
+                                                resp2.show_tooltip_text(tooltip);
+                                            }
                                         }
                                     });
 
@@ -279,7 +1468,7 @@ impl eframe::App for InjectorApp {
                                                 filtered_processes.sort_by_key(|process| process.pid);
 
                                                 let mut new_selected_process_pid = None;
-                                                let mut new_selected_process_index = None;
+                                                let mut new_selected_process = None;
 
                                                 for process in &filtered_processes {
                                                     let process_pid = process.pid.to_string();
@@ -288,24 +1477,25 @@ impl eframe::App for InjectorApp {
                                                     if ui.selectable_value(
                                                         &mut self.combo_box_pid.as_str(),
                                                         process_pid.as_str(),
-                                                        selectable_text.as_str(),
+                                                        self.process_entry_text(process, selectable_text),
                                                     ).clicked() && self.radio_button_proc_sel_meth == ByPID {
                                                         new_selected_process_pid = Some(process_pid);
-                                                        new_selected_process_index = Some(self.process_list.iter().position(|x| x.pid == process.pid).unwrap());
+                                                        new_selected_process = Some(TargetProcess::new(process));
                                                     }
                                                 }
+                                                self.show_exited_processes(ui);
 
                                                 if let Some(pid) = new_selected_process_pid {
                                                     self.combo_box_pid = pid;
                                                 }
 
-                                                if let Some(index) = new_selected_process_index {
-                                                    self.current_process_selected_index = index;
+                                                if let Some(target) = new_selected_process {
+                                                    self.selected_process = Some(target);
                                                 }
                                             }).response;
 
                                         if cb2_resp.clicked_by(PointerButton::Primary) && self.radio_button_proc_sel_meth == ByPID {
-                                            self.process_list = get_process_list();
+                                            self.process_watcher.request_refresh();
                                         }
                                     });
                                 });
@@ -328,17 +1518,9 @@ impl eframe::App for InjectorApp {
                                 row.col(|ui| {
                                     let resp3 = ui.add(EmojiLabelWidget::new(obfstr!("⚙🆔📝 PID input:\t")));
                                     if resp3.hovered() && self.radio_button_proc_sel_meth == ByPIDInput {
-                                        let process = &self.process_list[self.current_process_selected_index];
-                                        resp3.show_tooltip_text(format!(
-                                            "PID: {:#?}\nPPID: {:#?}\nArchitecture: {:#?}\nBits: {:#?}\nStart Time: {:#?}\nPath:\n{:#?}\nName: {:#?}",
-                                            process.pid,
-                                            process.ppid,
-                                            process.arch,
-                                            process.bits,
-                                            process.start_time,
-                                            process.path,
-                                            process.name
-                                        ));
+                                        if let Some(tooltip) = self.selected_process_tooltip() {
+                                            resp3.show_tooltip_text(tooltip);
+                                        }
                                     }
                                 });
 
@@ -368,11 +1550,13 @@ impl eframe::App for InjectorApp {
 
                                     if self.radio_button_proc_sel_meth == ProcessSelectionMethod::ByPIDInput {
                                         if let Ok(input_pid) = self.text_edit_value.parse::<u32>() {
-                                            if let Some(index) = self.process_list.iter().position(|x| x.pid == input_pid) {
-                                                self.current_process_selected_index = index;
-                                            } else {
+                                            if let Some(process) = self.process_list.iter().find(|x| x.pid == input_pid) {
+                                                if !self.selected_process.as_ref().is_some_and(|target| target.is(process)) {
+                                                    self.selected_process = Some(TargetProcess::new(process));
+                                                }
+                                            } else if resp.changed() {
                                                 // If the PID is not found, refresh the process list
-                                                self.process_list = get_process_list();
+                                                self.process_watcher.request_refresh();
                                             }
                                         }
                                     }
@@ -388,17 +1572,16 @@ impl eframe::App for InjectorApp {
                                     let response = ui.add(emoji_button_select_process);
 
                                     if response.clicked() {
-                                        println!("Emoji button with custom label was clicked!");
-                                        // Perform the action for the button click
+                                        debug!("Select process button clicked");
                                     }
                                 });
                             });
                         });
                     });
-                    ui.horizontal(|ui| {
-                        ui.label("Selected process :\t");
-                        ui.label(format!("{:#?}", self.process_list[self.current_process_selected_index]));
-                    });
+                    self.selected_process_label(ui);
+                    self.process_details_pane(ui);
+                    self.process_refresh_settings(ui);
+                    self.injection_controls(ui);
 
                     ui.push_id("MainInjectionMenuTable", |ui| {
                         ui.horizontal(|ui| {
@@ -420,8 +1603,8 @@ impl eframe::App for InjectorApp {
                                             let response = ui.add(emoji_button_update_process_list);
 
                                             if response.clicked() {
-                                                self.process_list = get_process_list();
-                                                println!("Emoji button with custom label was clicked! To update process list");
+                                                self.process_watcher.request_refresh();
+                                                debug!("Process list refresh requested");
                                             }
 
                                         //});
@@ -438,19 +1621,33 @@ impl eframe::App for InjectorApp {
                                         let response2 = ui.add(emoji_button_inject_dll_into_proc);
 
                                         if response2.clicked() {
-                                            println!("Injecting DLL into selected process");
-                                            println!("Process name: {}", self.process_list[self.current_process_selected_index].name);
-                                            println!("PID: {}", self.process_list[self.current_process_selected_index].pid);
-
-                                            for dll in &self.dll_list_vector {
-                                                if dll.switch {
-                                                    println!("Injecting DLL: {}", dll.dll_name);
-                                                    match inject_dll_test_fix(&self.process_list[self.current_process_selected_index], &dll.dll_path) {
-                                                        Ok(_) => println!("Successfully injected: {}", dll.dll_name),
-                                                        Err(e) => println!("Failed to inject {}: {}", dll.dll_name, e),
-                                                    }
-                                                }
+                                            // Re-check the target right before injecting so a
+                                            // stale or reused PID is never written into.
+                                            let target = match self.selected_process.as_ref().map(TargetProcess::resolve_live) {
+                                                None => {
+                                                    warn!("No process selected");
+                                                    return;
+                                                },
+                                                Some(Err(err)) => {
+                                                    error!(outcome = "failure", error = %err, "Refusing to inject");
+                                                    self.process_watcher.request_refresh();
+                                                    return;
+                                                },
+                                                Some(Ok(process)) => process,
+                                            };
+                                            if let Some(check) = self.access_check.as_ref().filter(|c| c.verdict == AccessVerdict::Denied) {
+                                                warn!(details = %check.details(), "Pre-flight check failed, injection will likely fail: {}", check.summary());
                                             }
+                                            info!(process = %target.name, pid = target.pid, "Injecting DLLs into selected process");
+
+                                            let jobs = self.dll_list_vector.iter().filter(|dll| dll.switch).map(|dll| InjectionJob {
+                                                delay: Duration::from_millis(dll.delay_ms),
+                                                wait_for_module: Some(dll.wait_for_module.trim().to_owned()).filter(|module| !module.is_empty()),
+                                                pinned: dll.pin.zip(dll.sha256.clone()).map(|(policy, sha256)| PinnedHash { sha256, policy }),
+                                                managed: dll.managed.clone(),
+                                                ..InjectionJob::new(target.clone(), dll.dll_path.clone())
+                                            }).collect();
+                                            self.start_injection(jobs);
                                         }
                                     });
                                 });
@@ -462,8 +1659,9 @@ impl eframe::App for InjectorApp {
                 ui.vertical(|ui| {
                     ui.label(obfstr!("Inject list"));
                     ui.horizontal(|ui| {
-                        dll_list_buttons_column(ui, &mut self.dll_list_vector, &mut self.selected_row, &mut self.show_popup_error_dll_already_added);
+                        dll_list_buttons_column(ui, &mut self.dll_list_vector, &mut self.selected_row, &mut self.dll_add_summary);
                         ui.vertical(|ui| {
+                            self.dll_exports_button(ui);
                             dll_list_table(ui, &mut self.selected_row, &mut self.dll_list_vector);
                         });
                     });
@@ -0,0 +1,347 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Write as _};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Local};
+use egui::{Color32, ComboBox, RichText, ScrollArea, Ui};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+// Oldest entries are dropped once the console holds this many of our own
+// events, or this many from dependencies (egui, wgpu, winit), so their noise
+// never pushes out an injection's events.
+const MAX_LOG_ENTRIES: usize = 5000;
+const MAX_DEPENDENCY_LOG_ENTRIES: usize = 1000;
+
+// Span field that ties events to one injection attempt.
+pub const INJECTION_ID_FIELD: &str = "injection_id";
+// Event field set to "success" or "failure" on the final event of an attempt.
+pub const OUTCOME_FIELD: &str = "outcome";
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub timestamp: DateTime<Local>,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+    pub fields: Vec<(String, String)>,
+    pub injection_id: Option<u64>,
+}
+
+impl LogEntry {
+    pub fn is_injector_event(&self) -> bool {
+        self.target.starts_with(env!("CARGO_CRATE_NAME"))
+    }
+
+    pub fn outcome(&self) -> Option<&str> {
+        self.fields.iter().find(|(name, _)| name == OUTCOME_FIELD).map(|(_, v)| v.as_str())
+    }
+
+    pub fn to_line(&self) -> String {
+        let mut line = format!(
+            "{} {:>5} {}: {}",
+            self.timestamp.format("%H:%M:%S%.3f"),
+            self.level,
+            self.target,
+            self.message
+        );
+        for (name, value) in &self.fields {
+            let _ = write!(line, " {}={}", name, value);
+        }
+        line
+    }
+}
+
+#[derive(Default)]
+struct LogEntries {
+    injector: VecDeque<LogEntry>,
+    dependencies: VecDeque<LogEntry>,
+}
+
+#[derive(Clone, Default)]
+pub struct LogBuffer(Arc<Mutex<LogEntries>>);
+
+impl LogBuffer {
+    fn push(&self, entry: LogEntry) {
+        if let Ok(mut entries) = self.0.lock() {
+            let (entries, max) = if entry.is_injector_event() {
+                (&mut entries.injector, MAX_LOG_ENTRIES)
+            } else {
+                (&mut entries.dependencies, MAX_DEPENDENCY_LOG_ENTRIES)
+            };
+            if entries.len() == max {
+                entries.pop_front();
+            }
+            entries.push_back(entry);
+        }
+    }
+
+    // Both kinds of entries, in the order they were logged.
+    fn snapshot(&self) -> Vec<LogEntry> {
+        let mut snapshot: Vec<LogEntry> = self
+            .0
+            .lock()
+            .map(|entries| entries.injector.iter().chain(&entries.dependencies).cloned().collect())
+            .unwrap_or_default();
+        snapshot.sort_by_key(|entry| entry.timestamp);
+        snapshot
+    }
+
+    fn clear(&self) {
+        if let Ok(mut entries) = self.0.lock() {
+            entries.injector.clear();
+            entries.dependencies.clear();
+        }
+    }
+}
+
+#[derive(Default)]
+struct EventVisitor {
+    message: String,
+    fields: Vec<(String, String)>,
+}
+
+impl Visit for EventVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_owned();
+        } else {
+            self.fields.push((field.name().to_owned(), value.to_owned()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields.push((field.name().to_owned(), format!("{:?}", value)));
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct InjectionId(u64);
+
+struct InjectionIdVisitor(Option<u64>);
+
+impl Visit for InjectionIdVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == INJECTION_ID_FIELD {
+            self.0 = Some(value);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+}
+
+// Tracing layer that copies every event into a buffer shown by the log
+// console, tagged with the injection attempt it belongs to (if any).
+pub struct LogConsoleLayer {
+    buffer: LogBuffer,
+}
+
+impl LogConsoleLayer {
+    pub fn new(buffer: LogBuffer) -> Self {
+        LogConsoleLayer { buffer }
+    }
+}
+
+impl<S> Layer<S> for LogConsoleLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = InjectionIdVisitor(None);
+        attrs.record(&mut visitor);
+        if let (Some(injection_id), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(InjectionId(injection_id));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut visitor = EventVisitor::default();
+        event.record(&mut visitor);
+
+        let injection_id = ctx.event_scope(event).and_then(|scope| {
+            scope.into_iter().find_map(|span| span.extensions().get::<InjectionId>().map(|id| id.0))
+        });
+
+        self.buffer.push(LogEntry {
+            timestamp: Local::now(),
+            level: *event.metadata().level(),
+            target: event.metadata().target().to_owned(),
+            message: visitor.message,
+            fields: visitor.fields,
+            injection_id,
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogDock {
+    Bottom,
+    Right,
+    Window,
+}
+
+pub struct LogConsole {
+    buffer: LogBuffer,
+    pub visible: bool,
+    dock: LogDock,
+    show_levels: [bool; 5],
+    search: String,
+    only_injector_events: bool,
+    group_by_injection: bool,
+}
+
+const LEVELS: [Level; 5] = [Level::ERROR, Level::WARN, Level::INFO, Level::DEBUG, Level::TRACE];
+
+impl LogConsole {
+    pub fn new(buffer: LogBuffer) -> Self {
+        LogConsole {
+            buffer,
+            visible: true,
+            dock: LogDock::Bottom,
+            show_levels: [true, true, true, false, false],
+            search: String::new(),
+            only_injector_events: true,
+            group_by_injection: true,
+        }
+    }
+
+    fn is_shown(&self, entry: &LogEntry) -> bool {
+        let level_shown = LEVELS
+            .iter()
+            .position(|level| *level == entry.level)
+            .is_some_and(|index| self.show_levels[index]);
+        let target_shown = !self.only_injector_events || entry.is_injector_event();
+        let search = self.search.to_lowercase();
+        let matches_search = search.is_empty() || entry.to_line().to_lowercase().contains(&search);
+        level_shown && target_shown && matches_search
+    }
+
+    // Show the console in its current dock. Panels have to be added before
+    // the central panel, so this is called first thing in `update`.
+    pub fn show(&mut self, ctx: &egui::Context) {
+        if !self.visible {
+            return;
+        }
+        match self.dock {
+            LogDock::Bottom => {
+                egui::TopBottomPanel::bottom("LogConsolePanel")
+                    .resizable(true)
+                    .default_height(200.0)
+                    .show(ctx, |ui| self.contents(ui));
+            },
+            LogDock::Right => {
+                egui::SidePanel::right("LogConsolePanel")
+                    .resizable(true)
+                    .default_width(400.0)
+                    .show(ctx, |ui| self.contents(ui));
+            },
+            LogDock::Window => {
+                let mut open = true;
+                egui::Window::new("Log console")
+                    .open(&mut open)
+                    .default_size([600.0, 300.0])
+                    .show(ctx, |ui| self.contents(ui));
+                self.visible = open;
+            },
+        }
+    }
+
+    fn contents(&mut self, ui: &mut Ui) {
+        let entries: Vec<LogEntry> =
+            self.buffer.snapshot().into_iter().filter(|entry| self.is_shown(entry)).collect();
+
+        ui.horizontal_wrapped(|ui| {
+            for (level, shown) in LEVELS.iter().zip(self.show_levels.iter_mut()) {
+                ui.toggle_value(shown, level.as_str());
+            }
+            ui.separator();
+            ui.label("🔍");
+            ui.add(egui::TextEdit::singleline(&mut self.search).desired_width(150.0));
+            ui.checkbox(&mut self.only_injector_events, "Injector only");
+            ui.checkbox(&mut self.group_by_injection, "Group by injection");
+            if ui.button("📋 Copy").clicked() {
+                let text = entries.iter().map(LogEntry::to_line).collect::<Vec<_>>().join("\n");
+                ui.ctx().copy_text(text);
+            }
+            if ui.button("🗑 Clear").clicked() {
+                self.buffer.clear();
+            }
+            ComboBox::from_id_source("LogConsoleDock")
+                .selected_text(format!("{:?}", self.dock))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.dock, LogDock::Bottom, "Bottom");
+                    ui.selectable_value(&mut self.dock, LogDock::Right, "Right");
+                    ui.selectable_value(&mut self.dock, LogDock::Window, "Window");
+                });
+        });
+        ui.separator();
+
+        ScrollArea::vertical().auto_shrink([false, false]).stick_to_bottom(true).show(ui, |ui| {
+            if !self.group_by_injection {
+                for entry in &entries {
+                    log_entry_row(ui, entry);
+                }
+                return;
+            }
+
+            // Keep the original order, but fold the events of each injection
+            // attempt under a single header at the position of its first event.
+            let mut shown_groups: Vec<u64> = Vec::new();
+            for entry in &entries {
+                let Some(injection_id) = entry.injection_id else {
+                    log_entry_row(ui, entry);
+                    continue;
+                };
+                if shown_groups.contains(&injection_id) {
+                    continue;
+                }
+                shown_groups.push(injection_id);
+
+                let group: Vec<&LogEntry> =
+                    entries.iter().filter(|e| e.injection_id == Some(injection_id)).collect();
+                let outcome = group.iter().rev().find_map(|e| e.outcome());
+                let header = format!(
+                    "Injection #{} — {} ({} events)",
+                    injection_id,
+                    group.first().map_or("", |e| e.message.as_str()),
+                    group.len()
+                );
+                egui::CollapsingHeader::new(RichText::new(header).color(outcome_color(outcome)))
+                    .id_source(("LogInjectionGroup", injection_id))
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        for entry in group {
+                            log_entry_row(ui, entry);
+                        }
+                    });
+            }
+        });
+    }
+}
+
+fn outcome_color(outcome: Option<&str>) -> Color32 {
+    match outcome {
+        Some("success") => Color32::LIGHT_GREEN,
+        Some("failure") => Color32::LIGHT_RED,
+        _ => Color32::GRAY,
+    }
+}
+
+fn log_entry_row(ui: &mut Ui, entry: &LogEntry) {
+    let color = match (entry.outcome(), entry.level) {
+        (Some(outcome), _) => outcome_color(Some(outcome)),
+        (None, Level::ERROR) => Color32::LIGHT_RED,
+        (None, Level::WARN) => Color32::YELLOW,
+        (None, Level::INFO) => ui.visuals().text_color(),
+        (None, _) => Color32::GRAY,
+    };
+    ui.label(RichText::new(entry.to_line()).monospace().color(color));
+}
@@ -0,0 +1,332 @@
+use std::cmp::Reverse;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{Layer, Registry, fmt, reload};
+
+use crate::log_console::{LogBuffer, LogConsoleLayer};
+use crate::utils::data_dir::{APP_DIRECTORY_NAME, app_data_directory};
+
+const LOG_FILE_PREFIX: &str = APP_DIRECTORY_NAME;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Json,
+    Text,
+}
+
+impl LogFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            LogFormat::Json => "json",
+            LogFormat::Text => "log",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogSettings {
+    pub level: LevelFilter,
+    pub format: LogFormat,
+    // Overrides the per-user data directory.
+    pub directory: Option<PathBuf>,
+    // A new file is started once the current one grows past this size, and
+    // when the date changes.
+    pub max_file_size: u64,
+    // Older log files are deleted once there are more than this many, or
+    // once they are older than `retention_days`.
+    pub max_files: usize,
+    pub retention_days: Option<u64>,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        LogSettings {
+            level: LevelFilter::DEBUG,
+            format: LogFormat::Json,
+            directory: None,
+            max_file_size: 10 * 1024 * 1024,
+            max_files: 10,
+            retention_days: Some(14),
+        }
+    }
+}
+
+impl LogSettings {
+    pub fn log_directory(&self) -> PathBuf {
+        self.directory.clone().unwrap_or_else(default_log_directory)
+    }
+}
+
+pub fn default_log_directory() -> PathBuf {
+    app_data_directory().join("logs")
+}
+
+pub struct CommandLine {
+    pub log_settings: LogSettings,
+    pub open_log_folder: bool,
+}
+
+pub const USAGE: &str = "Options:
+  --log-level <off|error|warn|info|debug|trace>
+  --log-format <json|text>
+  --log-dir <path>
+  --log-max-size <MiB>
+  --log-max-files <count>
+  --log-retention-days <days, 0 keeps files forever>
+  --open-log-folder";
+
+pub fn parse_command_line(args: impl IntoIterator<Item = String>) -> Result<CommandLine, String> {
+    let mut command_line =
+        CommandLine { log_settings: LogSettings::default(), open_log_folder: false };
+    let settings = &mut command_line.log_settings;
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) => (name.to_owned(), Some(value.to_owned())),
+            None => (arg, None),
+        };
+        if name == "--open-log-folder" {
+            command_line.open_log_folder = true;
+            continue;
+        }
+
+        let value = inline_value
+            .or_else(|| args.next())
+            .ok_or_else(|| format!("Missing value for {}", name))?;
+        let number = |value: &str| {
+            value.parse::<u64>().map_err(|_| format!("Invalid number for {}: {}", name, value))
+        };
+        let too_large = || format!("Value for {} is too large: {}", name, value);
+        match name.as_str() {
+            "--log-level" => {
+                settings.level = value
+                    .parse::<LevelFilter>()
+                    .map_err(|_| format!("Invalid log level: {}", value))?;
+            },
+            "--log-format" => {
+                settings.format = match value.to_lowercase().as_str() {
+                    "json" => LogFormat::Json,
+                    "text" => LogFormat::Text,
+                    _ => return Err(format!("Invalid log format: {}", value)),
+                };
+            },
+            "--log-dir" => settings.directory = Some(PathBuf::from(value)),
+            "--log-max-size" => {
+                settings.max_file_size =
+                    number(&value)?.max(1).checked_mul(1024 * 1024).ok_or_else(too_large)?;
+            },
+            "--log-max-files" => settings.max_files = number(&value)?.max(1) as usize,
+            "--log-retention-days" => {
+                let days = number(&value)?;
+                retention_period(days).ok_or_else(too_large)?;
+                settings.retention_days = Some(days).filter(|days| *days > 0);
+            },
+            _ => return Err(format!("Unknown option: {}", name)),
+        }
+    }
+
+    Ok(command_line)
+}
+
+fn retention_period(days: u64) -> Option<Duration> {
+    days.checked_mul(24 * 60 * 60).map(Duration::from_secs)
+}
+
+// Only files named like the ones `RotatingFileWriter::rotate` creates, so
+// nothing else that happens to share the log directory is ever deleted.
+fn is_rotated_log_file(name: &str) -> bool {
+    let Some(stem) = name
+        .strip_suffix(".json")
+        .or_else(|| name.strip_suffix(".log"))
+        .and_then(|stem| stem.strip_prefix(LOG_FILE_PREFIX))
+        .and_then(|stem| stem.strip_prefix('_'))
+    else {
+        return false;
+    };
+    let (timestamp, counter) = match stem.get(17..) {
+        Some("") => (stem, None),
+        Some(rest) => (&stem[..17], rest.strip_prefix('_')),
+        None => return false,
+    };
+    NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d_%H%M%S").is_ok()
+        && counter.is_none_or(|counter| {
+            !counter.is_empty() && counter.bytes().all(|byte| byte.is_ascii_digit())
+        })
+}
+
+// Writes log lines to `<prefix>_<date>_<time>.<ext>` in the log directory,
+// starting a new file on every launch, when the date changes and when the
+// current file exceeds the size limit. Old files are pruned on rotation.
+pub struct RotatingFileWriter {
+    directory: PathBuf,
+    extension: &'static str,
+    max_file_size: u64,
+    max_files: usize,
+    retention_days: Option<u64>,
+    file: Option<File>,
+    file_size: u64,
+    file_date: NaiveDate,
+}
+
+impl RotatingFileWriter {
+    pub fn new(directory: &Path, settings: &LogSettings) -> io::Result<Self> {
+        fs::create_dir_all(directory)?;
+        let mut writer = RotatingFileWriter {
+            directory: directory.to_owned(),
+            extension: settings.format.extension(),
+            max_file_size: settings.max_file_size,
+            max_files: settings.max_files,
+            retention_days: settings.retention_days,
+            file: None,
+            file_size: 0,
+            file_date: Local::now().date_naive(),
+        };
+        writer.rotate()?;
+        Ok(writer)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        let now = Local::now();
+        let stem = format!("{}_{}", LOG_FILE_PREFIX, now.format("%Y-%m-%d_%H%M%S"));
+        let mut path = self.directory.join(format!("{}.{}", stem, self.extension));
+        let mut counter = 1;
+        while path.exists() {
+            path = self.directory.join(format!("{}_{}.{}", stem, counter, self.extension));
+            counter += 1;
+        }
+
+        self.file = Some(OpenOptions::new().create_new(true).write(true).open(&path)?);
+        self.file_size = 0;
+        self.file_date = now.date_naive();
+        self.prune_old_files();
+        Ok(())
+    }
+
+    fn prune_old_files(&self) {
+        let Ok(entries) = fs::read_dir(&self.directory) else {
+            return;
+        };
+        let mut files: Vec<(SystemTime, PathBuf)> = entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_str().is_some_and(is_rotated_log_file))
+            .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+            .collect();
+        // Newest first; the file just opened is always kept.
+        files.sort_by_key(|(modified, _)| Reverse(*modified));
+
+        let max_age = self.retention_days.and_then(retention_period);
+        for (index, (modified, path)) in files.iter().enumerate().skip(1) {
+            let too_old =
+                max_age.is_some_and(|max_age| modified.elapsed().is_ok_and(|age| age > max_age));
+            if index >= self.max_files || too_old {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+impl Write for RotatingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let date_changed = Local::now().date_naive() != self.file_date;
+        let too_large =
+            self.file_size > 0 && self.file_size + buf.len() as u64 > self.max_file_size;
+        if self.file.is_none() || date_changed || too_large {
+            self.rotate()?;
+        }
+
+        let file = self.file.as_mut().ok_or_else(|| io::Error::other("log file is not open"))?;
+        let written = file.write(buf)?;
+        self.file_size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+// Lets the UI change the level at runtime and find the log files.
+#[derive(Clone)]
+pub struct LogHandle {
+    directory: Option<PathBuf>,
+    level: reload::Handle<LevelFilter, Registry>,
+}
+
+impl LogHandle {
+    // None if no log directory could be created and logs only go to the
+    // console.
+    pub fn directory(&self) -> Option<&Path> {
+        self.directory.as_deref()
+    }
+
+    pub fn level(&self) -> LevelFilter {
+        self.level.clone_current().unwrap_or(LevelFilter::OFF)
+    }
+
+    pub fn set_level(&self, level: LevelFilter) {
+        if let Err(err) = self.level.modify(|current| *current = level) {
+            eprintln!("Failed to change log level: {}", err);
+        }
+    }
+}
+
+// Install the global subscriber: the log files, if the directory is usable, and
+// the in-app console.
+pub fn init_logging(settings: &LogSettings, buffer: LogBuffer) -> LogHandle {
+    let mut directory = settings.log_directory();
+    let mut writer = RotatingFileWriter::new(&directory, settings);
+    if writer.is_err() && settings.directory.is_none() {
+        directory = std::env::temp_dir().join(APP_DIRECTORY_NAME).join("logs");
+        writer = RotatingFileWriter::new(&directory, settings);
+    }
+    let open_error = writer.as_ref().err().map(|err| err.to_string());
+
+    let (level_filter, level) = reload::Layer::new(settings.level);
+    let file_layer = writer.ok().map(|writer| {
+        let layer = fmt::layer().with_ansi(false).with_writer(Mutex::new(writer));
+        match settings.format {
+            LogFormat::Json => layer.json().boxed(),
+            LogFormat::Text => layer.boxed(),
+        }
+    });
+    let has_file_layer = file_layer.is_some();
+
+    tracing_subscriber::registry()
+        .with(level_filter)
+        .with(file_layer)
+        .with(LogConsoleLayer::new(buffer))
+        .init();
+
+    match open_error {
+        Some(err) => {
+            tracing::error!("Cannot write log files to {}: {}", directory.display(), err);
+        },
+        None => tracing::info!("Writing logs to {}", directory.display()),
+    }
+
+    LogHandle { directory: has_file_layer.then_some(directory), level }
+}
+
+pub fn open_log_folder(directory: &Path) -> io::Result<()> {
+    fs::create_dir_all(directory)?;
+    #[cfg(windows)]
+    let program = "explorer";
+    #[cfg(target_os = "macos")]
+    let program = "open";
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let program = "xdg-open";
+    std::process::Command::new(program).arg(directory).spawn().map(|_| ())
+}
@@ -2,7 +2,6 @@
 //#![cfg_attr(not(debug_assertions), console window on Windows in release
 
 use std::fs;
-use std::fs::File;
 
 use eframe::egui;
 use egui::{Context, FontData, FontDefinitions, FontFamily};
@@ -13,12 +12,21 @@ use font_kit::source::SystemSource;
 use tracing::info;
 
 use crate::injector_app::InjectorApp as InjectorAppWindow;
+use crate::log_console::LogBuffer;
+use crate::log_files::{USAGE, init_logging, open_log_folder, parse_command_line};
 
 mod dll_info;
 mod emoji_button_widget;
 mod emoji_label_widget;
+mod export_browser;
+mod hook_inspector;
 mod injector_app;
+mod log_console;
+mod log_files;
+mod memory_viewer;
 mod process_selection_method;
+mod region_map;
+mod shellcode_workbench;
 mod utils;
 
 fn load_system_fonts(ctx: &Context) {
@@ -28,10 +36,7 @@ fn load_system_fonts(ctx: &Context) {
     fn load_font_data(postscript_name: &str, fallback_family: FamilyName) -> Vec<u8> {
         let font_handle = SystemSource::new()
             //.select_by_postscript_name(postscript_name)
-            .select_best_match(
-                &[FamilyName::Title(postscript_name.to_owned())],
-                &Properties::new(),
-            )
+            .select_best_match(&[FamilyName::Title(postscript_name.to_owned())], &Properties::new())
             .or_else(|_| {
                 SystemSource::new().select_best_match(&[fallback_family], &Properties::new())
             })
@@ -83,15 +88,26 @@ fn load_system_fonts(ctx: &Context) {
 }
 
 fn main() -> Result<(), eframe::Error> {
-    // Open a log file in write mode
-    let file = File::create("applog.json").unwrap();
+    let command_line = match parse_command_line(std::env::args().skip(1)) {
+        Ok(command_line) => command_line,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        },
+    };
+    if command_line.open_log_folder {
+        let directory = command_line.log_settings.log_directory();
+        if let Err(err) = open_log_folder(&directory) {
+            eprintln!("Failed to open {}: {}", directory.display(), err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-    // Initialize tracing subscriber to log to a file
-    tracing_subscriber::fmt()
-        .json()
-        .with_max_level(tracing::Level::DEBUG)
-        .with_writer(file) // Use the file as the writer
-        .init();
+    // Initialize tracing subscriber to log to rotating files and to the in-app
+    // console
+    let log_buffer = LogBuffer::default();
+    let log_handle = init_logging(&command_line.log_settings, log_buffer.clone());
 
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default(), //.with_inner_size([320.0, 240.0]),
@@ -105,7 +121,7 @@ fn main() -> Result<(), eframe::Error> {
             // This gives us image support:
             load_system_fonts(&cc.egui_ctx);
             egui_extras::install_image_loaders(&cc.egui_ctx);
-            Ok(Box::<InjectorAppWindow>::default())
+            Ok(Box::new(InjectorAppWindow::new(log_buffer, log_handle)))
         }),
     )
 }
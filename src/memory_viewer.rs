@@ -0,0 +1,469 @@
+use std::path::Path;
+use std::time::Duration;
+
+use egui::{Color32, RichText, Ui};
+use libmem::memory::write_memory_ex;
+use libmem::module::enum_modules_ex;
+use libmem::process::Process;
+use libmem::segment::find_segment_ex;
+use libmem::{Bits, Module, Segment};
+use tracing::{info, warn};
+
+use crate::utils::address_expression::AddressExpression;
+use crate::utils::debug_symbols::{any_symbols_loading, request_symbols, symbol_name};
+use crate::utils::memory_map::protection_label;
+use crate::utils::remote_exports::{RemoteExportResolver, read_remote_bytes};
+use crate::utils::target_process::TargetProcess;
+use crate::utils::text_assembler::{disassemble, hex_bytes, parse_hex_bytes};
+
+// Bytes read per page; reads stop early at the end of the region.
+const PAGE_SIZE: usize = 512;
+const BYTES_PER_ROW: usize = 16;
+// How often to check whether debug symbols finished loading.
+const SYMBOL_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ViewMode {
+    Hex,
+    Disassembly,
+}
+
+struct MemoryPage {
+    address: usize,
+    bytes: Vec<u8>,
+    segment: Segment,
+    module: Option<Module>,
+    // Set while the module's debug symbols load in the background.
+    symbols_pending: bool,
+    // module!function+offset of the page address, from debug symbols.
+    symbol: Option<String>,
+    // Functions starting in the page, labelled in the disassembly.
+    labels: Vec<(usize, String)>,
+}
+
+impl MemoryPage {
+    // Fills in `symbol` and `labels` if the module's symbols are loaded.
+    fn label(&mut self) {
+        let Some(module) = &self.module else {
+            return;
+        };
+        let Some(symbols) = request_symbols(Path::new(&module.path)) else {
+            self.symbols_pending = true;
+            return;
+        };
+        self.symbols_pending = false;
+        let Ok(symbols) = symbols else {
+            return;
+        };
+        let start = self.address - module.base;
+        self.symbol = symbol_name(module, &symbols, self.address);
+        self.labels = symbols
+            .starting_in(start..start + self.bytes.len())
+            .iter()
+            .map(|symbol| (module.base + symbol.rva, symbol.name.clone()))
+            .collect();
+    }
+}
+
+struct PendingWrite {
+    address: usize,
+    old: Vec<u8>,
+    new: Vec<u8>,
+}
+
+fn read_page(process: &Process, address: usize) -> Result<MemoryPage, String> {
+    let segment = find_segment_ex(process, address)
+        .ok_or_else(|| format!("{:#x} is not mapped in the target", address))?;
+    let len = PAGE_SIZE.min(segment.end.saturating_sub(address));
+    let bytes = read_remote_bytes(process, address, len)
+        .ok_or_else(|| format!("failed to read {} bytes at {:#x}", len, address))?;
+    let module = enum_modules_ex(process)
+        .unwrap_or_default()
+        .into_iter()
+        .find(|module| (module.base..module.end).contains(&address));
+    let mut page = MemoryPage {
+        address,
+        bytes,
+        segment,
+        module,
+        symbols_pending: false,
+        symbol: None,
+        labels: Vec::new(),
+    };
+    page.label();
+    Ok(page)
+}
+
+// Hex view and disassembly of the target's memory, to check what a hook or
+// an injected module changed. Writes go through a confirmation.
+pub struct MemoryViewer {
+    pub visible: bool,
+    target: TargetProcess,
+    address_text: String,
+    page: Result<MemoryPage, String>,
+    // The bytes of the last read of the same page, to highlight changes.
+    previous: Option<(usize, Vec<u8>)>,
+    mode: ViewMode,
+    selected: Option<usize>,
+    patch: String,
+    pending_write: Option<PendingWrite>,
+    status: Option<Result<String, String>>,
+    // The address failed to resolve while debug symbols were loading.
+    resolve_pending: bool,
+}
+
+impl MemoryViewer {
+    // `expression` is anything AddressExpression parses.
+    pub fn open(target: &TargetProcess, expression: &str) -> Self {
+        let mut viewer = MemoryViewer {
+            visible: true,
+            target: target.clone(),
+            address_text: expression.to_owned(),
+            page: Err("nothing read yet".to_owned()),
+            previous: None,
+            mode: ViewMode::Hex,
+            selected: None,
+            patch: String::new(),
+            pending_write: None,
+            status: None,
+            resolve_pending: false,
+        };
+        viewer.go();
+        viewer
+    }
+
+    fn bitness(&self) -> u32 {
+        match self.target.process().bits {
+            Bits::Bits32 => 32,
+            Bits::Bits64 => 64,
+        }
+    }
+
+    fn go(&mut self) {
+        let address = AddressExpression::parse(&self.address_text).and_then(|expression| {
+            let process = self.target.resolve_live().map_err(|err| err.to_string())?;
+            expression.resolve(&mut RemoteExportResolver::new(&process))
+        });
+        self.resolve_pending = address.is_err() && any_symbols_loading();
+        match address {
+            Ok(address) => {
+                self.selected = Some(address);
+                self.read(address);
+            },
+            Err(err) => self.page = Err(err),
+        }
+    }
+
+    fn read(&mut self, address: usize) {
+        self.pending_write = None;
+        self.resolve_pending = false;
+        self.previous = match &self.page {
+            Ok(page) if page.address == address => Some((page.address, page.bytes.clone())),
+            _ => None,
+        };
+        self.page = self
+            .target
+            .resolve_live()
+            .map_err(|err| err.to_string())
+            .and_then(|process| read_page(&process, address));
+    }
+
+    fn move_to(&mut self, address: usize) {
+        self.address_text = format!("{:#x}", address);
+        self.read(address);
+    }
+
+    fn changed(&self, address: usize) -> bool {
+        self.previous.as_ref().is_some_and(|(base, bytes)| {
+            let current = self
+                .page
+                .as_ref()
+                .ok()
+                .and_then(|page| page.bytes.get(address.checked_sub(page.address)?).copied());
+            let previous = address.checked_sub(*base).and_then(|offset| bytes.get(offset)).copied();
+            current.is_some() && previous.is_some() && current != previous
+        })
+    }
+
+    // An address that names a debug symbol, or the page's labels, may wait
+    // for symbols loading in the background.
+    fn poll_symbols(&mut self, ctx: &egui::Context) {
+        let waiting = match &self.page {
+            Ok(page) => page.symbols_pending,
+            Err(_) => self.resolve_pending,
+        };
+        if !waiting {
+            return;
+        }
+        if any_symbols_loading() {
+            ctx.request_repaint_after(SYMBOL_POLL_INTERVAL);
+            return;
+        }
+        match self.page.as_mut() {
+            Ok(page) => page.label(),
+            Err(_) => self.go(),
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        self.poll_symbols(ctx);
+        let mut open = self.visible;
+        egui::Window::new(format!(
+            "Memory of {} (PID {})",
+            self.target.process().name,
+            self.target.process().pid
+        ))
+        .id(egui::Id::new("MemoryViewer"))
+        .open(&mut open)
+        .default_size([720.0, 480.0])
+        .show(ctx, |ui| self.contents(ui));
+        self.visible = open;
+    }
+
+    fn contents(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.address_text)
+                    .desired_width(260.0)
+                    .hint_text("0x7ff6a1b20000, game.exe+1f30, kernel32!LoadLibraryW, game!Update"),
+            );
+            let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button("➡ Go").clicked() || entered {
+                self.go();
+            }
+            let current = self.page.as_ref().ok().map(|page| page.address);
+            if let Some(address) = current {
+                if ui.button("◀").on_hover_text("Previous page").clicked() {
+                    self.move_to(address.saturating_sub(PAGE_SIZE));
+                }
+                if ui.button("▶").on_hover_text("Next page").clicked() {
+                    self.move_to(address.saturating_add(PAGE_SIZE));
+                }
+                if ui
+                    .button("🔄 Refresh")
+                    .on_hover_text("Read again, highlighting changes")
+                    .clicked()
+                {
+                    self.read(address);
+                }
+            }
+            ui.separator();
+            ui.selectable_value(&mut self.mode, ViewMode::Hex, "Hex");
+            ui.selectable_value(&mut self.mode, ViewMode::Disassembly, "Disassembly");
+        });
+
+        let page = match &self.page {
+            Ok(page) => page,
+            Err(err) => {
+                ui.horizontal(|ui| {
+                    if self.resolve_pending {
+                        ui.spinner();
+                    }
+                    ui.colored_label(Color32::LIGHT_RED, err);
+                });
+                return;
+            },
+        };
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "Region {:#x}-{:#x}, {}",
+                page.segment.base,
+                page.segment.end,
+                protection_label(page.segment.prot)
+            ));
+            if let Some(module) = &page.module {
+                ui.separator();
+                ui.monospace(format!("{}+{:#x}", module.name, page.address - module.base));
+            }
+            if page.symbols_pending {
+                ui.separator();
+                ui.spinner();
+                ui.weak("Loading debug symbols…");
+            } else if let Some(symbol) = &page.symbol {
+                ui.separator();
+                ui.monospace(symbol);
+            }
+            if ui.button("📋 Copy").on_hover_text("Copy the shown page").clicked() {
+                let text = page
+                    .bytes
+                    .chunks(BYTES_PER_ROW)
+                    .enumerate()
+                    .map(|(row, bytes)| {
+                        format!("{:016x}  {}", page.address + row * BYTES_PER_ROW, hex_bytes(bytes))
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                ui.ctx().copy_text(text);
+            }
+        });
+        ui.separator();
+
+        let mut selected = self.selected;
+        let height = (ui.available_height() - 70.0).max(100.0);
+        egui::ScrollArea::vertical().max_height(height).auto_shrink([false, true]).show(ui, |ui| {
+            match self.mode {
+                ViewMode::Hex => self.hex_view(ui, page, &mut selected),
+                ViewMode::Disassembly => self.disassembly_view(ui, page, &mut selected),
+            }
+        });
+        if selected != self.selected {
+            self.selected = selected;
+            self.pending_write = None;
+        }
+        ui.separator();
+        self.patch_pane(ui);
+    }
+
+    fn hex_view(&self, ui: &mut Ui, page: &MemoryPage, selected: &mut Option<usize>) {
+        ui.spacing_mut().item_spacing.x = 4.0;
+        for (row, bytes) in page.bytes.chunks(BYTES_PER_ROW).enumerate() {
+            let row_address = page.address + row * BYTES_PER_ROW;
+            ui.horizontal(|ui| {
+                ui.monospace(format!("{:016x}", row_address));
+                ui.add_space(8.0);
+                for (column, byte) in bytes.iter().enumerate() {
+                    let address = row_address + column;
+                    let mut text = RichText::new(format!("{:02x}", byte)).monospace();
+                    if self.changed(address) {
+                        text = text.color(Color32::YELLOW);
+                    }
+                    if ui.selectable_label(*selected == Some(address), text).clicked() {
+                        *selected = Some(address);
+                    }
+                }
+                ui.add_space(8.0);
+                let ascii: String = bytes
+                    .iter()
+                    .map(|byte| if byte.is_ascii_graphic() { *byte as char } else { '.' })
+                    .collect();
+                ui.monospace(ascii);
+            });
+        }
+    }
+
+    fn disassembly_view(&self, ui: &mut Ui, page: &MemoryPage, selected: &mut Option<usize>) {
+        for line in disassemble(&page.bytes, self.bitness(), page.address as u64) {
+            let address = line.address as usize;
+            let bytes = &line.bytes;
+            let text = &line.text;
+            for (_, label) in page.labels.iter().filter(|(start, _)| *start == address) {
+                ui.label(
+                    RichText::new(format!("{}:", label)).monospace().color(Color32::LIGHT_BLUE),
+                );
+            }
+            let changed = (address..address + bytes.len()).any(|address| self.changed(address));
+            let mut line =
+                RichText::new(format!("{:016x}  {:<24} {}", address, hex_bytes(bytes), text))
+                    .monospace();
+            if changed {
+                line = line.color(Color32::YELLOW);
+            }
+            if ui.selectable_label(*selected == Some(address), line).clicked() {
+                *selected = Some(address);
+            }
+        }
+    }
+
+    fn patch_pane(&mut self, ui: &mut Ui) {
+        let Some(address) = self.selected else {
+            ui.weak("Select a byte or instruction to patch it.");
+            return;
+        };
+        let new = parse_hex_bytes(&self.patch);
+        ui.horizontal(|ui| {
+            ui.label(format!("Write at {:#x}", address));
+            ui.add(
+                egui::TextEdit::singleline(&mut self.patch)
+                    .desired_width(220.0)
+                    .hint_text("90 90 c3"),
+            );
+            if ui.add_enabled(new.is_some(), egui::Button::new("✏ Write…")).clicked() {
+                if let Some(new) = new {
+                    let old = self
+                        .target
+                        .resolve_live()
+                        .ok()
+                        .and_then(|process| read_remote_bytes(&process, address, new.len()))
+                        .unwrap_or_default();
+                    self.pending_write = Some(PendingWrite { address, old, new });
+                    self.status = None;
+                }
+            }
+        });
+
+        let mut confirmed = None;
+        if let Some(pending) = &self.pending_write {
+            ui.horizontal(|ui| {
+                ui.colored_label(
+                    Color32::YELLOW,
+                    format!(
+                        "⚠ Overwrite {} bytes at {:#x}: {} → {}?",
+                        pending.new.len(),
+                        pending.address,
+                        if pending.old.is_empty() {
+                            "unreadable".to_owned()
+                        } else {
+                            hex_bytes(&pending.old)
+                        },
+                        hex_bytes(&pending.new)
+                    ),
+                );
+                if ui.button("Confirm").clicked() {
+                    confirmed = Some(true);
+                }
+                if ui.button("Cancel").clicked() {
+                    confirmed = Some(false);
+                }
+            });
+        }
+        match confirmed {
+            Some(true) => self.write_pending(),
+            Some(false) => self.pending_write = None,
+            None => {},
+        }
+
+        match &self.status {
+            Some(Ok(message)) => {
+                ui.colored_label(Color32::LIGHT_GREEN, message);
+            },
+            Some(Err(err)) => {
+                ui.colored_label(Color32::LIGHT_RED, err);
+            },
+            None => {},
+        }
+    }
+
+    fn write_pending(&mut self) {
+        let Some(pending) = self.pending_write.take() else {
+            return;
+        };
+        let process = match self.target.resolve_live() {
+            Ok(process) => process,
+            Err(err) => {
+                self.status = Some(Err(err.to_string()));
+                return;
+            },
+        };
+        let PendingWrite { address, old, new } = pending;
+        self.status = Some(match write_memory_ex(&process, address, new.as_slice()) {
+            Some(()) => {
+                info!(
+                    pid = process.pid,
+                    "Wrote {} at {:#x} (was {})",
+                    hex_bytes(&new),
+                    address,
+                    hex_bytes(&old)
+                );
+                Ok(format!("Wrote {} bytes at {:#x}", new.len(), address))
+            },
+            None => {
+                warn!(pid = process.pid, "Failed to write {} bytes at {:#x}", new.len(), address);
+                Err(format!("Failed to write {} bytes at {:#x}", new.len(), address))
+            },
+        });
+        if let Ok(page) = &self.page {
+            let page_address = page.address;
+            self.read(page_address);
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::time::SystemTime;
+
+use egui::{Color32, RichText, Ui};
+use egui_extras::{Column, TableBuilder};
+
+use crate::utils::memory_map::{MemoryMap, MemoryRegion, protection_label};
+use crate::utils::process_details::{format_bytes, format_duration};
+use crate::utils::remote_allocations::RemoteAllocation;
+use crate::utils::target_process::TargetProcess;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RegionFilter {
+    All,
+    ExecutablePrivate,
+    Ours,
+}
+
+fn allocation_line(allocation: &RemoteAllocation) -> String {
+    let age = SystemTime::now().duration_since(allocation.allocated_at).unwrap_or_default();
+    format!(
+        "{} at {:#x}, {} {}, allocated {} ago",
+        allocation.what,
+        allocation.address,
+        format_bytes(allocation.size as u64),
+        protection_label(allocation.prot),
+        format_duration(age)
+    )
+}
+
+// The virtual memory regions of the selected process, with the buffers our
+// injections left behind. Read once when opened and on Refresh.
+pub struct RegionMap {
+    pub visible: bool,
+    target: TargetProcess,
+    map: Result<MemoryMap, String>,
+    filter: RegionFilter,
+    search: String,
+}
+
+impl RegionMap {
+    pub fn open(target: &TargetProcess) -> Self {
+        let mut region_map = RegionMap {
+            visible: true,
+            target: target.clone(),
+            map: Err("nothing read yet".to_owned()),
+            filter: RegionFilter::All,
+            search: String::new(),
+        };
+        region_map.refresh();
+        region_map
+    }
+
+    pub fn target(&self) -> &TargetProcess {
+        &self.target
+    }
+
+    fn refresh(&mut self) {
+        self.map = self
+            .target
+            .resolve_live()
+            .map_err(|err| err.to_string())
+            .and_then(|process| MemoryMap::collect(&process));
+    }
+
+    fn shows(&self, region: &MemoryRegion) -> bool {
+        let search = self.search.to_lowercase();
+        let filtered = match self.filter {
+            RegionFilter::All => true,
+            RegionFilter::ExecutablePrivate => region.is_executable_private(),
+            RegionFilter::Ours => !region.allocations.is_empty(),
+        };
+        filtered
+            && (search.is_empty()
+                || region.file.as_ref().is_some_and(|file| file.to_lowercase().contains(&search))
+                || format!("{:x}", region.base).contains(search.trim_start_matches("0x")))
+    }
+
+    // Returns an address to open in the memory viewer.
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<usize> {
+        let mut open = self.visible;
+        let mut view = None;
+        egui::Window::new(format!(
+            "Memory regions of {} (PID {})",
+            self.target.process().name,
+            self.target.process().pid
+        ))
+        .id(egui::Id::new("RegionMap"))
+        .open(&mut open)
+        .default_size([760.0, 480.0])
+        .show(ctx, |ui| view = self.contents(ui));
+        self.visible = open;
+        view
+    }
+
+    fn contents(&mut self, ui: &mut Ui) -> Option<usize> {
+        ui.horizontal(|ui| {
+            if ui.button("🔄 Refresh").clicked() {
+                self.refresh();
+            }
+            ui.label("🔍");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.search)
+                    .desired_width(160.0)
+                    .hint_text("file or address"),
+            );
+            ui.selectable_value(&mut self.filter, RegionFilter::All, "All");
+            ui.selectable_value(
+                &mut self.filter,
+                RegionFilter::ExecutablePrivate,
+                "Executable private",
+            );
+            ui.selectable_value(&mut self.filter, RegionFilter::Ours, "Our allocations");
+        });
+        let map = match &self.map {
+            Ok(map) => map,
+            Err(err) => {
+                ui.colored_label(Color32::LIGHT_RED, err);
+                return None;
+            },
+        };
+
+        let outstanding: Vec<&RemoteAllocation> = map.outstanding().collect();
+        ui.label(format!(
+            "{} regions, {} executable private, {} of our allocations outstanding",
+            map.regions.len(),
+            map.regions.iter().filter(|region| region.is_executable_private()).count(),
+            outstanding.len() + map.unmatched.len()
+        ));
+        if !outstanding.is_empty() || !map.unmatched.is_empty() {
+            egui::CollapsingHeader::new(
+                RichText::new("Outstanding allocations").color(Color32::YELLOW),
+            )
+            .id_source("OutstandingAllocations")
+            .show(ui, |ui| {
+                for allocation in &outstanding {
+                    ui.label(allocation_line(allocation));
+                }
+                for allocation in &map.unmatched {
+                    ui.weak(format!("{} (no longer mapped)", allocation_line(allocation)));
+                }
+            });
+        }
+        ui.separator();
+
+        let shown: Vec<&MemoryRegion> =
+            map.regions.iter().filter(|region| self.shows(region)).collect();
+        let mut view = None;
+        TableBuilder::new(ui)
+            .striped(true)
+            .resizable(true)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .column(Column::initial(140.0)) // Base
+            .column(Column::initial(80.0)) // Size
+            .column(Column::initial(70.0)) // Protection
+            .column(Column::initial(60.0)) // Type
+            .column(Column::remainder().clip(true)) // File or owner
+            .header(20.0, |mut header| {
+                for title in ["Base", "Size", "Protection", "Type", "File / owner"] {
+                    header.col(|ui| {
+                        ui.strong(title);
+                    });
+                }
+            })
+            .body(|body| {
+                body.rows(18.0, shown.len(), |mut row| {
+                    let region = shown[row.index()];
+                    let color = if !region.allocations.is_empty() {
+                        Some(Color32::LIGHT_GREEN)
+                    } else if region.is_executable_private() {
+                        Some(Color32::from_rgb(255, 165, 0))
+                    } else {
+                        None
+                    };
+                    let text = |text: String| {
+                        let text = RichText::new(text).monospace();
+                        match color {
+                            Some(color) => text.color(color),
+                            None => text,
+                        }
+                    };
+                    row.col(|ui| {
+                        ui.label(text(format!("{:#x}", region.base))).context_menu(|ui| {
+                            if ui.button("View memory").clicked() {
+                                view = Some(region.base);
+                                ui.close_menu();
+                            }
+                            if ui.button("Copy base").clicked() {
+                                ui.ctx().copy_text(format!("{:#x}", region.base));
+                                ui.close_menu();
+                            }
+                        });
+                    });
+                    row.col(|ui| {
+                        ui.label(text(format_bytes(region.size as u64)));
+                    });
+                    row.col(|ui| {
+                        ui.label(text(protection_label(region.prot).to_owned()));
+                    });
+                    row.col(|ui| {
+                        ui.label(text(region.kind.label().to_owned()));
+                    });
+                    row.col(|ui| {
+                        if !region.allocations.is_empty() {
+                            let owners = region
+                                .allocations
+                                .iter()
+                                .map(|allocation| allocation.what)
+                                .collect::<Vec<_>>()
+                                .join(", ");
+                            ui.label(text(format!("💉 {}", owners))).on_hover_text(
+                                region
+                                    .allocations
+                                    .iter()
+                                    .map(allocation_line)
+                                    .collect::<Vec<_>>()
+                                    .join("\n"),
+                            );
+                        } else if let Some(file) = &region.file {
+                            ui.label(file.as_str()).on_hover_text(file.as_str());
+                        } else if region.is_executable_private() {
+                            ui.label(text("executable, not backed by an image".to_owned()));
+                        }
+                    });
+                });
+            });
+        view
+    }
+}
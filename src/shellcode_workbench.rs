@@ -0,0 +1,469 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use egui::{Color32, RichText, Ui};
+use egui_extras::{Column, TableBuilder};
+use libmem::Bits;
+use tracing::{error, info, warn};
+
+use crate::utils::processlist::{CleanupPolicy, InjectOptions, execute_code};
+use crate::utils::target_process::TargetProcess;
+use crate::utils::text_assembler::{
+    Relocation, assemble, disassemble, disassemble_to_source, hex_bytes, parse_hex_bytes,
+};
+
+const DEFAULT_BASE: u64 = 0x1000_0000;
+const DEFAULT_SOURCE_X64: &str = "; The thread's exit code is the low 32 bits of eax.
+    mov eax, 0x1337
+    ret
+";
+// x86 thread procedures are stdcall and pop their one argument.
+const DEFAULT_SOURCE_X86: &str = "; The thread's exit code is eax.
+    mov eax, 0x1337
+    ret 4
+";
+
+fn default_source(bitness: u32) -> &'static str {
+    if bitness == 32 { DEFAULT_SOURCE_X86 } else { DEFAULT_SOURCE_X64 }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InputMode {
+    Assembly,
+    Hex,
+}
+
+struct ListingRow {
+    offset: usize,
+    bytes: Vec<u8>,
+    text: String,
+    // Source line the bytes came from, in assembly mode.
+    line: Option<usize>,
+}
+
+struct Listing {
+    bytes: Vec<u8>,
+    rows: Vec<ListingRow>,
+    relocations: Vec<Relocation>,
+    // None for raw bytes, which are not checked.
+    position_dependent_lines: Option<Vec<usize>>,
+}
+
+// The input and settings a listing was built from.
+#[derive(Clone, PartialEq, Eq)]
+struct ListingKey {
+    source: String,
+    mode: InputMode,
+    bitness: u32,
+    base: u64,
+}
+
+struct Execution {
+    target: String,
+    cancel: Arc<AtomicBool>,
+    result: Receiver<Result<u32, String>>,
+}
+
+fn parse_base(text: &str) -> Option<u64> {
+    let text = text.trim().replace('`', "");
+    u64::from_str_radix(text.strip_prefix("0x").unwrap_or(&text), 16).ok()
+}
+
+fn build(key: &ListingKey) -> Result<Listing, String> {
+    let (bytes, instructions, relocations, position_dependent_lines) = match key.mode {
+        InputMode::Assembly => {
+            let assembly =
+                assemble(&key.source, key.bitness, key.base).map_err(|err| err.to_string())?;
+            (
+                assembly.bytes,
+                assembly.instructions,
+                assembly.relocations,
+                Some(assembly.position_dependent_lines),
+            )
+        },
+        InputMode::Hex => {
+            let bytes = parse_hex_bytes(&key.source)
+                .ok_or("expected hex bytes, e.g. 31 c0 c3 or \\x31\\xc0\\xc3")?;
+            (bytes, Vec::new(), Vec::new(), None)
+        },
+    };
+    // The assembled bytes are disassembled again to show what the assembler
+    // picked for each line.
+    let rows = disassemble(&bytes, key.bitness, key.base)
+        .into_iter()
+        .map(|instruction| {
+            let offset = (instruction.address - key.base) as usize;
+            ListingRow {
+                offset,
+                line: instructions
+                    .iter()
+                    .find(|line| (line.offset..line.offset + line.len).contains(&offset))
+                    .map(|line| line.line),
+                bytes: instruction.bytes,
+                text: instruction.text,
+            }
+        })
+        .collect();
+    Ok(Listing { bytes, rows, relocations, position_dependent_lines })
+}
+
+// Write x86/x64 assembly or paste shellcode, check it and run it in the
+// selected target on a remote thread.
+pub struct ShellcodeWorkbench {
+    pub visible: bool,
+    source: String,
+    mode: InputMode,
+    bitness: u32,
+    base: String,
+    listing: Result<Listing, String>,
+    built_from: Option<ListingKey>,
+    running: Option<Execution>,
+    // Target and outcome of the last run.
+    last_run: Option<(String, Result<u32, String>)>,
+}
+
+impl Default for ShellcodeWorkbench {
+    fn default() -> Self {
+        ShellcodeWorkbench {
+            visible: false,
+            source: default_source(64).to_owned(),
+            mode: InputMode::Assembly,
+            bitness: 64,
+            base: format!("{:#x}", DEFAULT_BASE),
+            listing: Err("nothing assembled yet".to_owned()),
+            built_from: None,
+            running: None,
+            last_run: None,
+        }
+    }
+}
+
+impl ShellcodeWorkbench {
+    fn key(&self) -> Result<ListingKey, String> {
+        let base = parse_base(&self.base).ok_or_else(|| format!("invalid base {}", self.base))?;
+        if self.bitness == 32 && base > u32::MAX as u64 {
+            return Err(format!("base {:#x} is out of range for x86", base));
+        }
+        Ok(ListingKey { source: self.source.clone(), mode: self.mode, bitness: self.bitness, base })
+    }
+
+    // Rebuilt only when the input changes; the assembler walks every
+    // encoding of each mnemonic.
+    fn rebuild(&mut self) {
+        let key = match self.key() {
+            Ok(key) => key,
+            Err(err) => {
+                self.listing = Err(err);
+                self.built_from = None;
+                return;
+            },
+        };
+        if self.built_from.as_ref() != Some(&key) {
+            self.listing = build(&key);
+            self.built_from = Some(key);
+        }
+    }
+
+    fn poll(&mut self) {
+        let Some(execution) = &self.running else {
+            return;
+        };
+        let result = match execution.result.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err("the run ended without a result".to_owned()),
+        };
+        match &result {
+            Ok(exit_code) => info!(
+                exit_code = format_args!("{:#x}", exit_code),
+                "Workbench code finished in {}", execution.target
+            ),
+            Err(err) => warn!("Workbench code failed in {}: {}", execution.target, err),
+        }
+        self.last_run = Some((execution.target.clone(), result));
+        self.running = None;
+    }
+
+    fn run(&mut self, target: &TargetProcess, timeout: Duration, cleanup: CleanupPolicy) {
+        let Ok(key) = self.key() else {
+            return;
+        };
+        let size = self.listing.as_ref().map_or(0, |listing| listing.bytes.len());
+        let cancel = Arc::new(AtomicBool::new(false));
+        let options = InjectOptions {
+            timeout,
+            cleanup_on_timeout: cleanup,
+            cancel: cancel.clone(),
+            ..Default::default()
+        };
+        let (result_tx, result_rx) = mpsc::channel();
+        let target = target.clone();
+        let name = format!("{} (PID {})", target.process().name, target.process().pid);
+        info!("Running {} bytes of workbench code in {}", size, name);
+
+        let spawned = thread::Builder::new().name("workbench-run".to_owned()).spawn(move || {
+            let result = target.resolve_live().map_err(|err| err.to_string()).and_then(|process| {
+                // Assembled again for the address it ends up at, so absolute
+                // references to labels point into the buffer.
+                let assemble_at = |address: usize| {
+                    let key = ListingKey { base: address as u64, ..key };
+                    build(&key).map(|listing| listing.bytes)
+                };
+                execute_code(&process, size, assemble_at, &options, &|_| {})
+                    .map_err(|err| err.to_string())
+            });
+            let _ = result_tx.send(result);
+        });
+        match spawned {
+            Ok(_) => self.running = Some(Execution { target: name, cancel, result: result_rx }),
+            Err(err) => error!("Failed to spawn the workbench thread: {}", err),
+        }
+    }
+
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        target: Option<&TargetProcess>,
+        timeout: Duration,
+        cleanup: CleanupPolicy,
+    ) {
+        self.poll();
+        if self.running.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+        if !self.visible {
+            return;
+        }
+        let mut open = self.visible;
+        egui::Window::new("Assembler workbench")
+            .id(egui::Id::new("ShellcodeWorkbench"))
+            .open(&mut open)
+            .default_size([860.0, 520.0])
+            .show(ctx, |ui| self.contents(ui, target, timeout, cleanup));
+        self.visible = open;
+    }
+
+    fn contents(
+        &mut self,
+        ui: &mut Ui,
+        target: Option<&TargetProcess>,
+        timeout: Duration,
+        cleanup: CleanupPolicy,
+    ) {
+        let bitness = self.bitness;
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.mode, InputMode::Assembly, "Assembly");
+            ui.selectable_value(&mut self.mode, InputMode::Hex, "Hex bytes");
+            ui.separator();
+            ui.selectable_value(&mut self.bitness, 32, "x86");
+            ui.selectable_value(&mut self.bitness, 64, "x64");
+            ui.separator();
+            ui.label("Base");
+            ui.add(egui::TextEdit::singleline(&mut self.base).desired_width(140.0))
+                .on_hover_text("Address the listing is assembled for");
+        });
+        // Swap an untouched example for the one matching the new mode.
+        if self.bitness != bitness && self.source == default_source(bitness) {
+            self.source = default_source(self.bitness).to_owned();
+        }
+        self.rebuild();
+        ui.separator();
+
+        let mut convert = None;
+        ui.columns(2, |columns| {
+            egui::ScrollArea::vertical().id_source("WorkbenchSource").show(&mut columns[0], |ui| {
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.source)
+                        .code_editor()
+                        .desired_rows(20)
+                        .desired_width(f32::INFINITY),
+                );
+            });
+            let ui = &mut columns[1];
+            match &self.listing {
+                Ok(listing) => {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{} bytes", listing.bytes.len()));
+                        if ui.button("📋 Copy bytes").clicked() {
+                            ui.ctx().copy_text(hex_bytes(&listing.bytes));
+                        }
+                        if ui.button("📋 Copy as C").clicked() {
+                            let escaped: String = listing
+                                .bytes
+                                .iter()
+                                .map(|byte| format!("\\x{:02x}", byte))
+                                .collect();
+                            ui.ctx().copy_text(format!("\"{}\"", escaped));
+                        }
+                        if self.mode == InputMode::Hex && ui.button("Convert to assembly").clicked()
+                        {
+                            convert = self.built_from.as_ref().map(|key| {
+                                disassemble_to_source(&listing.bytes, key.bitness, key.base)
+                            });
+                        }
+                    });
+                    listing_table(ui, listing);
+                },
+                Err(err) => {
+                    ui.colored_label(Color32::LIGHT_RED, err);
+                },
+            }
+        });
+        if let Some(source) = convert {
+            self.source = source;
+            self.mode = InputMode::Assembly;
+        }
+        ui.separator();
+        self.checks(ui);
+        ui.separator();
+        self.run_pane(ui, target, timeout, cleanup);
+    }
+
+    fn checks(&self, ui: &mut Ui) {
+        let Ok(listing) = &self.listing else {
+            return;
+        };
+        match &listing.position_dependent_lines {
+            None => {
+                ui.label(
+                    "Raw bytes are not checked for position independence; convert them to \
+                     assembly to check them.",
+                );
+            },
+            Some(lines) if lines.is_empty() => {
+                ui.colored_label(Color32::LIGHT_GREEN, "✔ Position independent");
+            },
+            Some(lines) => {
+                let lines: Vec<String> = lines
+                    .iter()
+                    .map(|line| match line {
+                        0 => "the layout".to_owned(),
+                        line => line.to_string(),
+                    })
+                    .collect();
+                ui.colored_label(
+                    Color32::from_rgb(255, 165, 0),
+                    format!(
+                        "⚠ Depends on the address it runs at (lines {}); it is assembled again \
+                         for the buffer it runs from",
+                        lines.join(", ")
+                    ),
+                );
+            },
+        }
+        if !listing.relocations.is_empty() {
+            egui::CollapsingHeader::new(format!("Relocations ({})", listing.relocations.len()))
+                .id_source("WorkbenchRelocations")
+                .show(ui, |ui| {
+                    for relocation in &listing.relocations {
+                        ui.monospace(format!(
+                            "+{:04x}  {} byte absolute address, line {}",
+                            relocation.offset, relocation.size, relocation.line
+                        ));
+                    }
+                });
+        }
+    }
+
+    fn run_pane(
+        &mut self,
+        ui: &mut Ui,
+        target: Option<&TargetProcess>,
+        timeout: Duration,
+        cleanup: CleanupPolicy,
+    ) {
+        if self.bitness == 32 {
+            ui.label("An x86 thread is called with one stack argument: return with ret 4.");
+        }
+        ui.horizontal(|ui| {
+            if let Some(execution) = &self.running {
+                ui.spinner();
+                ui.label(format!("Running in {}", execution.target));
+                if ui.button("⏹ Cancel").clicked() {
+                    execution.cancel.store(true, Ordering::Relaxed);
+                }
+                return;
+            }
+            let target_bitness = target.map(|target| match target.process().bits {
+                Bits::Bits32 => 32,
+                Bits::Bits64 => 64,
+            });
+            let blocked = match (target, target_bitness) {
+                (None, _) => Some("Select a target process first".to_owned()),
+                (_, Some(bitness)) if bitness != self.bitness => {
+                    Some(format!("The target is {}-bit", bitness))
+                },
+                _ if self.listing.is_err() => Some("Fix the errors first".to_owned()),
+                _ => None,
+            };
+            let button = ui.add_enabled(blocked.is_none(), egui::Button::new("▶ Run in target"));
+            let button = match &blocked {
+                Some(reason) => button.on_disabled_hover_text(reason),
+                None => button.on_hover_text(
+                    "Allocates the code in the target, runs it on a new thread and waits for its \
+                     exit code",
+                ),
+            };
+            if let (true, Some(target)) = (button.clicked(), target) {
+                self.run(target, timeout, cleanup);
+            }
+        });
+        match &self.last_run {
+            Some((target, Ok(exit_code))) => {
+                ui.label(
+                    RichText::new(format!(
+                        "{} returned {:#x} ({})",
+                        target, exit_code, *exit_code as i32
+                    ))
+                    .monospace(),
+                );
+            },
+            Some((target, Err(err))) => {
+                ui.colored_label(Color32::LIGHT_RED, format!("{}: {}", target, err));
+            },
+            None => {},
+        }
+    }
+}
+
+fn listing_table(ui: &mut Ui, listing: &Listing) {
+    let dependent = listing.position_dependent_lines.as_deref().unwrap_or_default();
+    TableBuilder::new(ui)
+        .striped(true)
+        .resizable(true)
+        .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+        .column(Column::initial(50.0)) // Offset
+        .column(Column::initial(150.0).clip(true)) // Bytes
+        .column(Column::remainder()) // Instruction
+        .header(20.0, |mut header| {
+            for title in ["Offset", "Bytes", "Instruction"] {
+                header.col(|ui| {
+                    ui.strong(title);
+                });
+            }
+        })
+        .body(|body| {
+            body.rows(18.0, listing.rows.len(), |mut row| {
+                let line = &listing.rows[row.index()];
+                let mut text = RichText::new(&line.text).monospace();
+                if line.line.is_some_and(|line| dependent.contains(&line)) {
+                    text = text.color(Color32::from_rgb(255, 165, 0));
+                }
+                row.col(|ui| {
+                    ui.monospace(format!("{:04x}", line.offset));
+                });
+                row.col(|ui| {
+                    ui.monospace(hex_bytes(&line.bytes));
+                });
+                row.col(|ui| {
+                    let label = ui.label(text);
+                    if let Some(line) = line.line {
+                        label.on_hover_text(format!("line {}", line));
+                    }
+                });
+            });
+        });
+}
@@ -0,0 +1,218 @@
+use libmem::process::Process;
+
+use crate::utils::process_details::ProcessSecurity;
+use crate::utils::process_watcher::{ProcessKey, process_key};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessVerdict {
+    Allowed,
+    Denied,
+    Unknown,
+}
+
+// Result of the pre-flight check: whether the injector can get the rights it
+// needs on the target, what stands in the way and what to change about it.
+#[derive(Debug, Clone)]
+pub struct AccessCheck {
+    pub key: ProcessKey,
+    pub verdict: AccessVerdict,
+    pub findings: Vec<String>,
+    pub hints: Vec<String>,
+}
+
+impl AccessCheck {
+    fn new(process: &Process) -> Self {
+        AccessCheck {
+            key: process_key(process),
+            verdict: AccessVerdict::Unknown,
+            findings: Vec::new(),
+            hints: Vec::new(),
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        match self.verdict {
+            AccessVerdict::Allowed => "Injector has the required access".to_owned(),
+            AccessVerdict::Denied => format!(
+                "Access denied: {}",
+                self.findings.first().map_or("insufficient rights", String::as_str)
+            ),
+            AccessVerdict::Unknown => "Access could not be determined".to_owned(),
+        }
+    }
+
+    pub fn details(&self) -> String {
+        let mut text = self.findings.join("\n");
+        if !self.hints.is_empty() {
+            text.push_str("\n\nWhat to change:\n");
+            text.push_str(
+                &self.hints.iter().map(|hint| format!("• {}", hint)).collect::<Vec<_>>().join("\n"),
+            );
+        }
+        text
+    }
+}
+
+#[cfg(windows)]
+pub fn check_injection_access(process: &Process) -> AccessCheck {
+    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::System::Threading::{
+        GetProcessInformation, PROCESS_PROTECTION_LEVEL_INFORMATION, PROTECTION_LEVEL_NONE,
+        ProcessProtectionLevelInfo,
+    };
+    use winsafe::prelude::*;
+    use winsafe::{HPROCESS, co};
+
+    use crate::utils::process_details::IntegrityLevel;
+
+    let mut check = AccessCheck::new(process);
+    let own = ProcessSecurity::query(std::process::id());
+    let target = ProcessSecurity::query(process.pid);
+
+    // Protected processes refuse VM access to everyone outside their signer
+    // level, elevated or not.
+    if let Ok(handle) =
+        HPROCESS::OpenProcess(co::PROCESS::QUERY_LIMITED_INFORMATION, false, process.pid)
+    {
+        let mut info = PROCESS_PROTECTION_LEVEL_INFORMATION::default();
+        let queried = unsafe {
+            GetProcessInformation(
+                HANDLE(handle.ptr() as isize),
+                ProcessProtectionLevelInfo,
+                &mut info as *mut _ as *mut _,
+                size_of::<PROCESS_PROTECTION_LEVEL_INFORMATION>() as u32,
+            )
+        };
+        if queried.is_ok() && info.ProtectionLevel != PROTECTION_LEVEL_NONE {
+            check.verdict = AccessVerdict::Denied;
+            check.findings.push(format!(
+                "{} is a protected process (protection level {:#x})",
+                process.name, info.ProtectionLevel.0
+            ));
+            check.hints.push(
+                "Protected processes cannot be injected from user mode; pick another target"
+                    .to_owned(),
+            );
+            return check;
+        }
+    }
+
+    // PROCESS_VM_OPERATION is spelled differently across winsafe versions.
+    let vm_operation = unsafe { co::PROCESS::from_raw(0x0008) };
+    let required = co::PROCESS::CREATE_THREAD
+        | co::PROCESS::QUERY_INFORMATION
+        | vm_operation
+        | co::PROCESS::VM_READ
+        | co::PROCESS::VM_WRITE;
+    match HPROCESS::OpenProcess(required, false, process.pid) {
+        Ok(_) => check.verdict = AccessVerdict::Allowed,
+        Err(co::ERROR::ACCESS_DENIED) => {
+            check.verdict = AccessVerdict::Denied;
+            check.findings.push(format!(
+                "OpenProcess on {} (PID {}) failed with ERROR_ACCESS_DENIED",
+                process.name, process.pid
+            ));
+            let rank = |level: Option<IntegrityLevel>| match level {
+                Some(IntegrityLevel::Untrusted) => 0,
+                Some(IntegrityLevel::Low) => 1,
+                Some(IntegrityLevel::Medium) | Some(IntegrityLevel::MediumPlus) => 2,
+                Some(IntegrityLevel::High) => 3,
+                Some(IntegrityLevel::System) | Some(IntegrityLevel::Protected) => 4,
+                Some(IntegrityLevel::Other(_)) | None => 2,
+            };
+            if rank(target.integrity) > rank(own.integrity) {
+                check.findings.push(format!(
+                    "Target runs at {:?} integrity, the injector at {:?}",
+                    target.integrity.unwrap_or(IntegrityLevel::Other(0)),
+                    own.integrity.unwrap_or(IntegrityLevel::Other(0))
+                ));
+            }
+            if own.elevated != Some(true) {
+                check.hints.push("Restart the injector as administrator".to_owned());
+            } else {
+                check.hints.push(
+                    "The target may be a service or belong to another session; SeDebugPrivilege \
+                     is required to open it"
+                        .to_owned(),
+                );
+            }
+        },
+        Err(co::ERROR::INVALID_PARAMETER) => {
+            check.verdict = AccessVerdict::Denied;
+            check.findings.push(format!(
+                "PID {} cannot be opened; it has exited or is a system process",
+                process.pid
+            ));
+        },
+        Err(err) => {
+            check.findings.push(format!("OpenProcess on PID {} failed: {}", process.pid, err));
+        },
+    }
+
+    check
+}
+
+#[cfg(unix)]
+pub fn check_injection_access(process: &Process) -> AccessCheck {
+    // Bit 19 of the capability sets.
+    const CAP_SYS_PTRACE: u64 = 1 << 19;
+
+    let mut check = AccessCheck::new(process);
+    let own = ProcessSecurity::query(std::process::id());
+    let target = ProcessSecurity::query(process.pid);
+    let has_cap_sys_ptrace =
+        own.effective_capabilities.is_some_and(|caps| caps & CAP_SYS_PTRACE != 0);
+    let ptrace_scope = std::fs::read_to_string("/proc/sys/kernel/yama/ptrace_scope")
+        .ok()
+        .and_then(|scope| scope.trim().parse::<u32>().ok());
+
+    match (own.uid, target.uid) {
+        (Some(own_uid), Some(target_uid)) if own_uid != target_uid && !has_cap_sys_ptrace => {
+            check.verdict = AccessVerdict::Denied;
+            check.findings.push(format!(
+                "Target runs as uid {}, the injector as uid {} without CAP_SYS_PTRACE",
+                target_uid, own_uid
+            ));
+            check.hints.push(
+                "Run the injector as the target's user, as root, or grant it CAP_SYS_PTRACE"
+                    .to_owned(),
+            );
+            return check;
+        },
+        (_, None) => {
+            check.findings.push(format!("Cannot read /proc/{}/status", process.pid));
+            return check;
+        },
+        _ => {},
+    }
+
+    match ptrace_scope {
+        Some(3) => {
+            check.verdict = AccessVerdict::Denied;
+            check.findings.push("kernel.yama.ptrace_scope is 3: ptrace attach is disabled".into());
+            check.hints.push("ptrace_scope 3 can only be lowered by rebooting".to_owned());
+        },
+        Some(2) if !has_cap_sys_ptrace => {
+            check.verdict = AccessVerdict::Denied;
+            check
+                .findings
+                .push("kernel.yama.ptrace_scope is 2: only CAP_SYS_PTRACE may attach".into());
+            check.hints.push("Run the injector as root or grant it CAP_SYS_PTRACE".to_owned());
+        },
+        Some(1) if !has_cap_sys_ptrace => {
+            check.verdict = AccessVerdict::Denied;
+            check.findings.push(
+                "kernel.yama.ptrace_scope is 1: only descendants of the injector may be traced"
+                    .into(),
+            );
+            check.hints.push(
+                "Set kernel.yama.ptrace_scope to 0 (sysctl -w kernel.yama.ptrace_scope=0) or \
+                 grant the injector CAP_SYS_PTRACE"
+                    .to_owned(),
+            );
+        },
+        _ => check.verdict = AccessVerdict::Allowed,
+    }
+
+    check
+}
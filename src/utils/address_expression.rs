@@ -0,0 +1,208 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::utils::debug_symbols::request_symbols;
+use crate::utils::remote_exports::RemoteExportResolver;
+
+// What an address expression is relative to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressBase {
+    Absolute(usize),
+    Module(String),
+    // Hex without 0x that may also name a module, like "d3d9" or "ace": the
+    // module if one is loaded, the address otherwise.
+    ModuleOrAbsolute { module: String, address: usize },
+    Export { module: String, export: String },
+}
+
+// An address in the target as typed in the UI, in the debugger syntax:
+// "7ff6a1b20000", "0x7ff6a1b20000+10", "00007ff6`a1b20000", "game.exe+1f30",
+// "kernel32!LoadLibraryW" or "kernel32.dll!LoadLibraryW+5". Numbers are hex,
+// with or without 0x. The name after ! may also be a function from the
+// module's debug symbols; while they load in the background resolving fails
+// and can be retried.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressExpression {
+    pub base: AddressBase,
+    pub offset: i64,
+}
+
+fn parse_hex(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text)
+        .replace('`', "");
+    u64::from_str_radix(&digits, 16).ok()
+}
+
+impl AddressExpression {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("enter an address".to_owned());
+        }
+        // The offset follows the last + or - that is followed by a number, so
+        // module names like "msvcp140-1.dll" stay whole.
+        let split = text
+            .char_indices()
+            .skip(1)
+            .filter(|(index, c)| {
+                (*c == '+' || *c == '-') && parse_hex(&text[index + 1..]).is_some()
+            })
+            .last();
+        let (base, offset) = match split {
+            Some((index, sign)) => {
+                let offset = parse_hex(&text[index + 1..])
+                    .and_then(|offset| i64::try_from(offset).ok())
+                    .ok_or_else(|| format!("invalid offset {}", &text[index + 1..]))?;
+                (text[..index].trim(), if sign == '-' { -offset } else { offset })
+            },
+            None => (text, 0),
+        };
+        let base = match base.split_once('!') {
+            Some((module, export)) if !module.is_empty() && !export.is_empty() => {
+                AddressBase::Export { module: module.to_owned(), export: export.to_owned() }
+            },
+            Some(_) => return Err(format!("invalid export {}", base)),
+            None => match parse_hex(base) {
+                Some(address)
+                    if base.starts_with("0x") || base.starts_with("0X") || base.contains('`') =>
+                {
+                    AddressBase::Absolute(
+                        usize::try_from(address)
+                            .map_err(|_| format!("{} is out of range", base))?,
+                    )
+                },
+                Some(address) => match usize::try_from(address) {
+                    Ok(address) => {
+                        AddressBase::ModuleOrAbsolute { module: base.to_owned(), address }
+                    },
+                    Err(_) => AddressBase::Module(base.to_owned()),
+                },
+                None => AddressBase::Module(base.to_owned()),
+            },
+        };
+        Ok(AddressExpression { base, offset })
+    }
+
+    // Module names may leave out ".dll", as in "kernel32!LoadLibraryW".
+    pub fn resolve(&self, resolver: &mut RemoteExportResolver) -> Result<usize, String> {
+        let module_name = |module: &str| {
+            if module.contains('.') { module.to_owned() } else { format!("{}.dll", module) }
+        };
+        let base = match &self.base {
+            AddressBase::Absolute(address) => *address,
+            AddressBase::Module(module) => resolver
+                .find_module(&module_name(module))
+                .map(|module| module.base)
+                .map_err(|err| err.to_string())?,
+            AddressBase::ModuleOrAbsolute { module, address } => {
+                resolver.find_module(&module_name(module)).map_or(*address, |module| module.base)
+            },
+            AddressBase::Export { module, export } => {
+                let module = module_name(module);
+                match resolver.resolve(&module, export) {
+                    Ok(address) => address,
+                    Err(err) => {
+                        let remote = resolver
+                            .find_module(&module)
+                            .ok()
+                            .map(|remote| (remote.base, request_symbols(Path::new(&remote.path))));
+                        match remote {
+                            Some((_, None)) => {
+                                return Err(format!("{} (its debug symbols are loading)", err));
+                            },
+                            Some((base, Some(Ok(symbols)))) => {
+                                symbols.find(export).map(|symbol| base + symbol.rva)
+                            },
+                            _ => None,
+                        }
+                        .ok_or_else(|| format!("{} (and not in its debug symbols)", err))?
+                    },
+                }
+            },
+        };
+        base.checked_add_signed(self.offset as isize)
+            .ok_or_else(|| format!("{} is out of range", self))
+    }
+}
+
+impl fmt::Display for AddressExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.base {
+            AddressBase::Absolute(address) => write!(f, "{:#x}", address)?,
+            AddressBase::Module(module) | AddressBase::ModuleOrAbsolute { module, .. } => {
+                write!(f, "{}", module)?
+            },
+            AddressBase::Export { module, export } => write!(f, "{}!{}", module, export)?,
+        }
+        match self.offset {
+            0 => Ok(()),
+            offset if offset < 0 => write!(f, "-{:#x}", offset.unsigned_abs()),
+            offset => write!(f, "+{:#x}", offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expression(base: AddressBase, offset: i64) -> AddressExpression {
+        AddressExpression { base, offset }
+    }
+
+    fn module(name: &str) -> AddressBase {
+        AddressBase::Module(name.to_owned())
+    }
+
+    fn export(module: &str, export: &str) -> AddressBase {
+        AddressBase::Export { module: module.to_owned(), export: export.to_owned() }
+    }
+
+    fn module_or_absolute(module: &str, address: usize) -> AddressBase {
+        AddressBase::ModuleOrAbsolute { module: module.to_owned(), address }
+    }
+
+    #[test]
+    fn parses_debugger_syntax() {
+        let table = [
+            ("0x7ff6a1b20000", expression(AddressBase::Absolute(0x7ff6a1b20000), 0)),
+            ("0X7ff6a1b20000+10", expression(AddressBase::Absolute(0x7ff6a1b20000), 0x10)),
+            ("00007ff6`a1b20000", expression(AddressBase::Absolute(0x7ff6a1b20000), 0)),
+            ("7ff6a1b20000", expression(module_or_absolute("7ff6a1b20000", 0x7ff6a1b20000), 0)),
+            ("d3d9", expression(module_or_absolute("d3d9", 0xd3d9), 0)),
+            ("ace+1f30", expression(module_or_absolute("ace", 0xace), 0x1f30)),
+            ("dbghelp2", expression(module("dbghelp2"), 0)),
+            ("game.exe+1f30", expression(module("game.exe"), 0x1f30)),
+            ("game.exe-0x20", expression(module("game.exe"), -0x20)),
+            ("msvcp140-1.dll+10", expression(module("msvcp140-1.dll"), 0x10)),
+            ("msvcp140-1.dll", expression(module("msvcp140-1.dll"), 0)),
+            ("kernel32!LoadLibraryW", expression(export("kernel32", "LoadLibraryW"), 0)),
+            ("kernel32!LoadLibraryW-5", expression(export("kernel32", "LoadLibraryW"), -5)),
+            (
+                " kernel32.dll!LoadLibraryW + 5 ",
+                expression(export("kernel32.dll", "LoadLibraryW"), 5),
+            ),
+        ];
+        for (text, expected) in table {
+            assert_eq!(AddressExpression::parse(text), Ok(expected), "{}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        for text in ["", "  ", "!", "kernel32!", "!LoadLibraryW", "0x1+8000000000000000"] {
+            assert!(AddressExpression::parse(text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn displays_what_it_parsed() {
+        for text in ["0x7ff6a1b20000+0x10", "d3d9", "game.exe-0x20", "kernel32!LoadLibraryW-0x5"] {
+            assert_eq!(AddressExpression::parse(text).unwrap().to_string(), text);
+        }
+    }
+}
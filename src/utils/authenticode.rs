@@ -0,0 +1,971 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{fs, io, thread};
+
+use chrono::{DateTime, Utc};
+use cms::cert::CertificateChoices;
+use cms::content_info::ContentInfo;
+use cms::signed_data::{SignedData, SignerIdentifier, SignerInfo};
+use der::asn1::{Any, GeneralizedTime, ObjectIdentifier, OctetString};
+use der::{Decode, Encode, Reader, Sequence, SliceReader};
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use tracing::warn;
+use x509_cert::Certificate;
+use x509_cert::ext::pkix::{BasicConstraints, ExtendedKeyUsage};
+use x509_cert::spki::AlgorithmIdentifierOwned;
+use x509_cert::time::Time;
+
+use crate::utils::data_dir::app_data_directory;
+
+// Authenticode signatures are checked offline: the PE image hash is recomputed
+// and compared with the signed one, the PKCS#7 signature is verified with the
+// signer certificate, and the certificate chain embedded in the file has to
+// lead to one of the user's trusted certificates. Nothing here calls into
+// Windows, so it works the same on every platform.
+
+const WIN_CERT_TYPE_PKCS_SIGNED_DATA: u16 = 0x0002;
+const IMAGE_DIRECTORY_ENTRY_SECURITY: usize = 4;
+// Longest certificate chain followed before giving up.
+const MAX_CHAIN_DEPTH: usize = 8;
+
+const OID_SIGNED_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.2");
+const OID_SPC_INDIRECT_DATA: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.311.2.1.4");
+const OID_MESSAGE_DIGEST: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.4");
+const OID_SIGNING_TIME: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.5");
+const OID_COUNTER_SIGNATURE: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.6");
+const OID_RFC3161_TIMESTAMP: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.311.3.3.1");
+const OID_KP_CODE_SIGNING: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.5.5.7.3.3");
+const OID_KP_TIME_STAMPING: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.5.5.7.3.8");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    // Digest algorithms and the RSA signature algorithms that imply one.
+    fn from_oid(oid: &ObjectIdentifier) -> Option<Self> {
+        match oid.to_string().as_str() {
+            "1.3.14.3.2.26" | "1.2.840.113549.1.1.5" => Some(HashAlgorithm::Sha1),
+            "2.16.840.1.101.3.4.2.1" | "1.2.840.113549.1.1.11" => Some(HashAlgorithm::Sha256),
+            "2.16.840.1.101.3.4.2.2" | "1.2.840.113549.1.1.12" => Some(HashAlgorithm::Sha384),
+            "2.16.840.1.101.3.4.2.3" | "1.2.840.113549.1.1.13" => Some(HashAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha1 => "SHA-1",
+            HashAlgorithm::Sha256 => "SHA-256",
+            HashAlgorithm::Sha384 => "SHA-384",
+            HashAlgorithm::Sha512 => "SHA-512",
+        }
+    }
+
+    fn digest(self, chunks: &[&[u8]]) -> Vec<u8> {
+        fn hash<D: Digest>(chunks: &[&[u8]]) -> Vec<u8> {
+            let mut hasher = D::new();
+            for chunk in chunks {
+                hasher.update(chunk);
+            }
+            hasher.finalize().to_vec()
+        }
+        match self {
+            HashAlgorithm::Sha1 => hash::<Sha1>(chunks),
+            HashAlgorithm::Sha256 => hash::<Sha256>(chunks),
+            HashAlgorithm::Sha384 => hash::<Sha384>(chunks),
+            HashAlgorithm::Sha512 => hash::<Sha512>(chunks),
+        }
+    }
+
+    fn pkcs1v15(self) -> Pkcs1v15Sign {
+        match self {
+            HashAlgorithm::Sha1 => Pkcs1v15Sign::new::<Sha1>(),
+            HashAlgorithm::Sha256 => Pkcs1v15Sign::new::<Sha256>(),
+            HashAlgorithm::Sha384 => Pkcs1v15Sign::new::<Sha384>(),
+            HashAlgorithm::Sha512 => Pkcs1v15Sign::new::<Sha512>(),
+        }
+    }
+}
+
+// SpcIndirectDataContent, the signed content of an Authenticode signature.
+#[derive(Sequence)]
+struct SpcIndirectDataContent {
+    data: Any,
+    message_digest: DigestInfo,
+}
+
+#[derive(Sequence)]
+struct DigestInfo {
+    digest_algorithm: AlgorithmIdentifierOwned,
+    digest: OctetString,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureStatus {
+    Unsigned,
+    // The signature could not be parsed, or the file or signature do not
+    // match it.
+    Invalid(String),
+    // Intact, but not issued by a trusted certificate.
+    Untrusted(String),
+    // The chain leads to this trusted certificate.
+    Trusted(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct SignerDetails {
+    pub subject: String,
+    pub issuer: String,
+    pub serial: String,
+    // From the countersignature or RFC 3161 timestamp, once its signature
+    // verifies and its chain leads to a trusted certificate.
+    pub timestamp: Option<DateTime<Utc>>,
+    // Why a timestamp in the file was ignored.
+    pub timestamp_error: Option<String>,
+    pub digest_algorithm: &'static str,
+    pub digest_matches: bool,
+    pub signature_valid: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SignatureCheck {
+    pub status: SignatureStatus,
+    pub signer: Option<SignerDetails>,
+}
+
+impl SignatureCheck {
+    fn invalid(reason: impl Into<String>, signer: Option<SignerDetails>) -> Self {
+        SignatureCheck { status: SignatureStatus::Invalid(reason.into()), signer }
+    }
+
+    pub fn is_trusted(&self) -> bool {
+        matches!(self.status, SignatureStatus::Trusted(_))
+    }
+
+    pub fn is_signed(&self) -> bool {
+        !matches!(self.status, SignatureStatus::Unsigned | SignatureStatus::Invalid(_))
+    }
+
+    // e.g. "Trusted: CN=Example" or "Unsigned"
+    pub fn summary(&self) -> String {
+        let signer = self.signer.as_ref().map_or("", |signer| signer.subject.as_str());
+        match &self.status {
+            SignatureStatus::Unsigned => "Unsigned".to_owned(),
+            SignatureStatus::Invalid(reason) => format!("Invalid: {}", reason),
+            SignatureStatus::Untrusted(_) => format!("Untrusted: {}", signer),
+            SignatureStatus::Trusted(_) => format!("Trusted: {}", signer),
+        }
+    }
+
+    pub fn details(&self) -> String {
+        let mut lines = vec![self.summary()];
+        match &self.status {
+            SignatureStatus::Untrusted(reason) => lines.push(reason.clone()),
+            SignatureStatus::Trusted(anchor) => lines.push(format!("Trusted through {}", anchor)),
+            _ => {},
+        }
+        if let Some(signer) = self.signer.as_ref() {
+            lines.push(format!("Subject: {}", signer.subject));
+            lines.push(format!("Issuer: {}", signer.issuer));
+            lines.push(format!("Serial: {}", signer.serial));
+            lines.push(match (&signer.timestamp, &signer.timestamp_error) {
+                (Some(time), _) => format!("Timestamp: {}", time.to_rfc3339()),
+                (None, Some(err)) => format!("Timestamp: ignored, {}", err),
+                (None, None) => "Timestamp: none".to_owned(),
+            });
+            lines.push(format!(
+                "Digest ({}): {}",
+                signer.digest_algorithm,
+                if signer.digest_matches { "matches" } else { "MISMATCH" }
+            ));
+            lines.push(format!(
+                "Signature: {}",
+                if signer.signature_valid { "valid" } else { "INVALID" }
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+// Which DLLs may be injected, based on their signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignaturePolicy {
+    Allow,
+    BlockUnsigned,
+    BlockUntrusted,
+}
+
+impl SignaturePolicy {
+    pub fn allows(self, check: &SignatureCheck) -> bool {
+        match self {
+            SignaturePolicy::Allow => true,
+            SignaturePolicy::BlockUnsigned => check.is_signed(),
+            SignaturePolicy::BlockUntrusted => check.is_trusted(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrustedCertificate {
+    pub path: PathBuf,
+    pub subject: String,
+    // SHA-256 of the DER encoding, as shown by most certificate viewers.
+    pub thumbprint: String,
+    certificate: Certificate,
+}
+
+// The certificates DLL signatures are checked against, kept as .cer/.crt/.pem
+// files in the per-user data directory.
+#[derive(Debug, Clone, Default)]
+pub struct TrustStore {
+    directory: Option<PathBuf>,
+    certificates: Vec<TrustedCertificate>,
+}
+
+impl TrustStore {
+    pub fn load(directory: PathBuf) -> Self {
+        let mut certificates = Vec::new();
+        match fs::read_dir(&directory) {
+            Ok(entries) => {
+                let mut paths: Vec<PathBuf> =
+                    entries.filter_map(Result::ok).map(|entry| entry.path()).collect();
+                paths.sort();
+                for path in paths.into_iter().filter(|path| path.is_file()) {
+                    match read_certificates(&path) {
+                        Ok(certs) => {
+                            certificates.extend(certs.into_iter().map(|certificate| {
+                                TrustedCertificate::new(path.clone(), certificate)
+                            }))
+                        },
+                        Err(err) => {
+                            warn!("Ignoring trusted certificate {}: {}", path.display(), err)
+                        },
+                    }
+                }
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {},
+            Err(err) => warn!("Failed to read {}: {}", directory.display(), err),
+        }
+        TrustStore { directory: Some(directory), certificates }
+    }
+
+    pub fn load_default() -> Self {
+        TrustStore::load(default_trust_directory())
+    }
+
+    pub fn certificates(&self) -> &[TrustedCertificate] {
+        &self.certificates
+    }
+
+    // Copy a certificate file into the store.
+    pub fn add(&mut self, path: &Path) -> io::Result<()> {
+        let directory = self.directory.clone().ok_or_else(|| io::Error::other("no directory"))?;
+        read_certificates(path).map_err(io::Error::other)?;
+        let file_name = path.file_name().ok_or_else(|| io::Error::other("not a file"))?;
+        fs::create_dir_all(&directory)?;
+        fs::copy(path, directory.join(file_name))?;
+        *self = TrustStore::load(directory);
+        Ok(())
+    }
+
+    // Delete the file the certificate was loaded from, and every other
+    // certificate in it.
+    pub fn remove(&mut self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)?;
+        self.certificates.retain(|trusted| trusted.path != path);
+        Ok(())
+    }
+
+    fn find(&self, certificate: &Certificate) -> Option<&TrustedCertificate> {
+        let thumbprint = thumbprint(certificate)?;
+        self.certificates.iter().find(|trusted| trusted.thumbprint == thumbprint)
+    }
+}
+
+impl TrustedCertificate {
+    fn new(path: PathBuf, certificate: Certificate) -> Self {
+        TrustedCertificate {
+            path,
+            subject: certificate.tbs_certificate.subject.to_string(),
+            thumbprint: thumbprint(&certificate).unwrap_or_default(),
+            certificate,
+        }
+    }
+}
+
+pub fn default_trust_directory() -> PathBuf {
+    app_data_directory().join("trusted_certs")
+}
+
+fn read_certificates(path: &Path) -> Result<Vec<Certificate>, String> {
+    let bytes = fs::read(path).map_err(|err| err.to_string())?;
+    // PEM files exported by openssl often start with subject/issuer lines.
+    if let Some(start) = bytes.windows(10).position(|window| window == b"-----BEGIN") {
+        Certificate::load_pem_chain(&bytes[start..]).map_err(|err| err.to_string())
+    } else {
+        Certificate::from_der(&bytes).map(|cert| vec![cert]).map_err(|err| err.to_string())
+    }
+}
+
+fn thumbprint(certificate: &Certificate) -> Option<String> {
+    let der = certificate.to_der().ok()?;
+    Some(HashAlgorithm::Sha256.digest(&[&der]).iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+// Offsets in a PE file that the Authenticode image hash skips.
+struct PeLayout {
+    checksum_offset: usize,
+    security_entry_offset: usize,
+    certificate_table: Option<(usize, usize)>,
+}
+
+fn pe_layout(image: &[u8]) -> Result<PeLayout, String> {
+    let read_u16 =
+        |offset: usize| image.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+    let read_u32 = |offset: usize| {
+        image.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let truncated = || "truncated PE headers".to_owned();
+
+    if image.get(0..2) != Some(b"MZ") {
+        return Err("not a PE file".to_owned());
+    }
+    let nt_headers = read_u32(0x3c).ok_or_else(truncated)? as usize;
+    if image.get(nt_headers..nt_headers + 4) != Some(b"PE\0\0") {
+        return Err("not a PE file".to_owned());
+    }
+    let optional_header = nt_headers + 24;
+    let data_directories = match read_u16(optional_header).ok_or_else(truncated)? {
+        0x10b => optional_header + 96,
+        0x20b => optional_header + 112,
+        magic => return Err(format!("unknown optional header magic {:#x}", magic)),
+    };
+    let directory_count = read_u32(data_directories - 4).ok_or_else(truncated)? as usize;
+    let security_entry_offset = data_directories + IMAGE_DIRECTORY_ENTRY_SECURITY * 8;
+
+    let certificate_table = if directory_count > IMAGE_DIRECTORY_ENTRY_SECURITY {
+        let offset = read_u32(security_entry_offset).ok_or_else(truncated)? as usize;
+        let size = read_u32(security_entry_offset + 4).ok_or_else(truncated)? as usize;
+        // The image hash covers everything up to the table, so it cannot
+        // start inside the headers it skips parts of.
+        let directories_end = data_directories + directory_count.min(16) * 8;
+        if offset == 0 || size == 0 {
+            None
+        } else if offset < directories_end
+            || offset.checked_add(size).is_none_or(|end| end > image.len())
+        {
+            return Err("certificate table lies outside the file".to_owned());
+        } else {
+            Some((offset, size))
+        }
+    } else {
+        None
+    };
+
+    Ok(PeLayout { checksum_offset: optional_header + 64, security_entry_offset, certificate_table })
+}
+
+// The Authenticode image hash: the whole file except the checksum, the
+// security directory entry and the certificate table itself.
+fn image_digest(image: &[u8], layout: &PeLayout, algorithm: HashAlgorithm) -> Vec<u8> {
+    let end = layout.certificate_table.map_or(image.len(), |(offset, _)| offset);
+    algorithm.digest(&[
+        &image[..layout.checksum_offset],
+        &image[layout.checksum_offset + 4..layout.security_entry_offset],
+        &image[layout.security_entry_offset + 8..end],
+    ])
+}
+
+// The PKCS#7 blob of the first Authenticode signature in the certificate
+// table.
+fn signature_blob(image: &[u8], (offset, size): (usize, usize)) -> Option<&[u8]> {
+    let mut table = &image[offset..offset + size];
+    while table.len() >= 8 {
+        let length = u32::from_le_bytes([table[0], table[1], table[2], table[3]]) as usize;
+        let certificate_type = u16::from_le_bytes([table[6], table[7]]);
+        if length < 8 || length > table.len() {
+            return None;
+        }
+        if certificate_type == WIN_CERT_TYPE_PKCS_SIGNED_DATA {
+            return Some(&table[8..length]);
+        }
+        // Entries are padded to 8 bytes.
+        table = &table[length.next_multiple_of(8).min(table.len())..];
+    }
+    None
+}
+
+pub fn verify_file(path: &Path, trust: &TrustStore) -> SignatureCheck {
+    match fs::read(path) {
+        Ok(image) => verify_image(&image, trust),
+        Err(err) => SignatureCheck::invalid(format!("cannot read file: {}", err), None),
+    }
+}
+
+type VerifierResult = (u64, String, SignatureCheck);
+
+// Checks files on a background thread, since reading and hashing a large DLL
+// would stall the UI. Results come back through `poll`.
+pub struct SignatureVerifier {
+    generation: u64,
+    pending: HashSet<String>,
+    results_tx: Sender<VerifierResult>,
+    results: Receiver<VerifierResult>,
+}
+
+impl Default for SignatureVerifier {
+    fn default() -> Self {
+        let (results_tx, results) = mpsc::channel();
+        SignatureVerifier { generation: 0, pending: HashSet::new(), results_tx, results }
+    }
+}
+
+impl SignatureVerifier {
+    pub fn verify(&mut self, paths: Vec<String>, trust: &TrustStore) {
+        let generation = self.generation;
+        self.pending.extend(paths.iter().cloned());
+        let trust = trust.clone();
+        let results_tx = self.results_tx.clone();
+        let worker_paths = paths.clone();
+        let spawned = thread::Builder::new().name("signature-check".to_owned()).spawn(move || {
+            for path in worker_paths {
+                let check = verify_file(Path::new(&path), &trust);
+                if results_tx.send((generation, path, check)).is_err() {
+                    return;
+                }
+            }
+        });
+        if let Err(err) = spawned {
+            warn!("Failed to spawn the signature check thread: {}", err);
+            for path in paths {
+                let check = SignatureCheck::invalid(format!("not checked: {}", err), None);
+                let _ = self.results_tx.send((generation, path, check));
+            }
+        }
+    }
+
+    pub fn is_pending(&self, path: &str) -> bool {
+        self.pending.contains(path)
+    }
+
+    pub fn is_busy(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    // Drop the checks still running, e.g. because the trust store changed
+    // and their results would be stale.
+    pub fn reset(&mut self) {
+        self.generation += 1;
+        self.pending.clear();
+    }
+
+    pub fn poll(&mut self) -> Vec<(String, SignatureCheck)> {
+        let mut finished = Vec::new();
+        while let Ok((generation, path, check)) = self.results.try_recv() {
+            if generation == self.generation && self.pending.remove(&path) {
+                finished.push((path, check));
+            }
+        }
+        finished
+    }
+}
+
+pub fn verify_image(image: &[u8], trust: &TrustStore) -> SignatureCheck {
+    let layout = match pe_layout(image) {
+        Ok(layout) => layout,
+        Err(err) => return SignatureCheck::invalid(err, None),
+    };
+    let Some(table) = layout.certificate_table else {
+        return SignatureCheck { status: SignatureStatus::Unsigned, signer: None };
+    };
+    let Some(blob) = signature_blob(image, table) else {
+        return SignatureCheck::invalid("no PKCS#7 signature in the certificate table", None);
+    };
+    match verify_signed_data(image, &layout, blob, trust) {
+        Ok(check) => check,
+        Err(err) => SignatureCheck::invalid(format!("malformed signature: {}", err), None),
+    }
+}
+
+fn verify_signed_data(
+    image: &[u8],
+    layout: &PeLayout,
+    blob: &[u8],
+    trust: &TrustStore,
+) -> der::Result<SignatureCheck> {
+    // The blob is padded, so decode the first element only.
+    let content_info = ContentInfo::decode(&mut SliceReader::new(blob)?)?;
+    if content_info.content_type != OID_SIGNED_DATA {
+        return Ok(SignatureCheck::invalid("not PKCS#7 signed data", None));
+    }
+    let signed_data: SignedData = content_info.content.decode_as()?;
+    let encap = &signed_data.encap_content_info;
+    let Some(content) =
+        encap.econtent.as_ref().filter(|_| encap.econtent_type == OID_SPC_INDIRECT_DATA)
+    else {
+        return Ok(SignatureCheck::invalid("not an Authenticode signature", None));
+    };
+    let indirect_data: SpcIndirectDataContent = content.decode_as()?;
+
+    let certificates = embedded_certificates(&signed_data);
+    let Some(signer_info) = signed_data.signer_infos.0.iter().next() else {
+        return Ok(SignatureCheck::invalid("no signer", None));
+    };
+    let signer_cert = match signer_certificate(signer_info, &certificates) {
+        Ok(signer_cert) => signer_cert,
+        Err(err) => return Ok(SignatureCheck::invalid(err, None)),
+    };
+
+    let Some(image_algorithm) =
+        HashAlgorithm::from_oid(&indirect_data.message_digest.digest_algorithm.oid)
+    else {
+        return Ok(SignatureCheck::invalid("unsupported image digest algorithm", None));
+    };
+    let digest_matches = image_digest(image, layout, image_algorithm)
+        == indirect_data.message_digest.digest.as_bytes();
+    let signature_valid = verify_signer(signer_info, signer_cert, content.value());
+    let (timestamp, timestamp_error) = match signing_time(signer_info, &certificates, trust) {
+        Ok(timestamp) => (timestamp, None),
+        Err(err) => (None, Some(err)),
+    };
+
+    let signer = SignerDetails {
+        subject: signer_cert.tbs_certificate.subject.to_string(),
+        issuer: signer_cert.tbs_certificate.issuer.to_string(),
+        serial: signer_cert.tbs_certificate.serial_number.to_string(),
+        timestamp,
+        timestamp_error,
+        digest_algorithm: image_algorithm.name(),
+        digest_matches,
+        signature_valid,
+    };
+    if !digest_matches {
+        return Ok(SignatureCheck::invalid("file was modified after signing", Some(signer)));
+    }
+    if !signature_valid {
+        return Ok(SignatureCheck::invalid("signature does not verify", Some(signer)));
+    }
+
+    // Certificates have to be valid when the file was signed, or now if it
+    // was not timestamped by someone trusted.
+    let at = timestamp.map_or_else(SystemTime::now, system_time);
+    let status = match build_chain(signer_cert, &certificates, trust, at, OID_KP_CODE_SIGNING) {
+        Ok(anchor) => SignatureStatus::Trusted(anchor),
+        Err(reason) => SignatureStatus::Untrusted(reason),
+    };
+    Ok(SignatureCheck { status, signer: Some(signer) })
+}
+
+fn embedded_certificates(signed_data: &SignedData) -> Vec<&Certificate> {
+    signed_data
+        .certificates
+        .iter()
+        .flat_map(|set| set.0.iter())
+        .filter_map(|choice| match choice {
+            CertificateChoices::Certificate(certificate) => Some(certificate),
+            _ => None,
+        })
+        .collect()
+}
+
+fn signer_certificate<'a>(
+    signer_info: &SignerInfo,
+    certificates: &[&'a Certificate],
+) -> Result<&'a Certificate, String> {
+    let SignerIdentifier::IssuerAndSerialNumber(signer_id) = &signer_info.sid else {
+        return Err("unsupported signer identifier".to_owned());
+    };
+    certificates
+        .iter()
+        .copied()
+        .find(|cert| {
+            cert.tbs_certificate.issuer == signer_id.issuer
+                && cert.tbs_certificate.serial_number == signer_id.serial_number
+        })
+        .ok_or_else(|| "signer certificate is missing".to_owned())
+}
+
+fn system_time(time: DateTime<Utc>) -> SystemTime {
+    u64::try_from(time.timestamp())
+        .map_or(UNIX_EPOCH, |secs| UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+// Check the signed attributes: their message digest has to match the signed
+// content, and the signer's signature has to cover them.
+fn verify_signer(signer_info: &SignerInfo, signer_cert: &Certificate, content: &[u8]) -> bool {
+    let Some(algorithm) = HashAlgorithm::from_oid(&signer_info.digest_alg.oid) else {
+        return false;
+    };
+    let Some(signed_attrs) = signer_info.signed_attrs.as_ref() else {
+        return false;
+    };
+    let content_digest = algorithm.digest(&[content]);
+    let digest_attr = signed_attrs
+        .iter()
+        .find(|attr| attr.oid == OID_MESSAGE_DIGEST)
+        .and_then(|attr| attr.values.iter().next())
+        .and_then(|value| value.decode_as::<OctetString>().ok());
+    if digest_attr.is_none_or(|digest| digest.as_bytes() != content_digest) {
+        return false;
+    }
+    // The signature covers the attributes encoded as a SET, not with the
+    // implicit [0] tag they carry in SignerInfo.
+    let Ok(signed_bytes) = signed_attrs.to_der() else {
+        return false;
+    };
+    verify_rsa(signer_cert, algorithm, &signed_bytes, signer_info.signature.as_bytes())
+}
+
+fn verify_rsa(
+    signer_cert: &Certificate,
+    algorithm: HashAlgorithm,
+    message: &[u8],
+    signature: &[u8],
+) -> bool {
+    // Only RSA keys are supported, which is what code signing certificates
+    // use in practice.
+    let Some(key) = signer_cert
+        .tbs_certificate
+        .subject_public_key_info
+        .to_der()
+        .ok()
+        .and_then(|der| RsaPublicKey::from_public_key_der(&der).ok())
+    else {
+        return false;
+    };
+    key.verify(algorithm.pkcs1v15(), &algorithm.digest(&[message]), signature).is_ok()
+}
+
+fn certificate_signed_by(certificate: &Certificate, issuer: &Certificate) -> bool {
+    let Some(algorithm) = HashAlgorithm::from_oid(&certificate.signature_algorithm.oid) else {
+        return false;
+    };
+    let Ok(tbs) = certificate.tbs_certificate.to_der() else {
+        return false;
+    };
+    certificate
+        .signature
+        .as_bytes()
+        .is_some_and(|signature| verify_rsa(issuer, algorithm, &tbs, signature))
+}
+
+fn valid_at(certificate: &Certificate, at: SystemTime) -> bool {
+    let validity = &certificate.tbs_certificate.validity;
+    validity.not_before.to_system_time() <= at && at <= validity.not_after.to_system_time()
+}
+
+fn is_certificate_authority(certificate: &Certificate) -> bool {
+    matches!(
+        certificate.tbs_certificate.get::<BasicConstraints>(),
+        Ok(Some((_, BasicConstraints { ca: true, .. })))
+    )
+}
+
+fn has_key_purpose(certificate: &Certificate, purpose: ObjectIdentifier) -> bool {
+    matches!(
+        certificate.tbs_certificate.get::<ExtendedKeyUsage>(),
+        Ok(Some((_, usage))) if usage.0.contains(&purpose)
+    )
+}
+
+// Follow issuers from the signer certificate, through the certificates in the
+// file and the trust store, until a trusted one. The signer needs the
+// extended key usage `purpose` and every issuer has to be a CA. Returns the
+// trusted certificate's subject.
+fn build_chain(
+    signer_cert: &Certificate,
+    embedded: &[&Certificate],
+    trust: &TrustStore,
+    at: SystemTime,
+    purpose: ObjectIdentifier,
+) -> Result<String, String> {
+    if !has_key_purpose(signer_cert, purpose) {
+        return Err(format!(
+            "{} is not allowed to sign {}",
+            signer_cert.tbs_certificate.subject,
+            if purpose == OID_KP_TIME_STAMPING { "timestamps" } else { "code" }
+        ));
+    }
+    let mut current = signer_cert;
+    for _ in 0..MAX_CHAIN_DEPTH {
+        if !valid_at(current, at) {
+            return Err(format!(
+                "{} was not valid on {}",
+                current.tbs_certificate.subject,
+                DateTime::<Utc>::from(at).format("%Y-%m-%d %H:%M:%S UTC")
+            ));
+        }
+        if let Some(trusted) = trust.find(current) {
+            return Ok(trusted.subject.clone());
+        }
+        let issuer = embedded
+            .iter()
+            .copied()
+            .chain(trust.certificates.iter().map(|trusted| &trusted.certificate))
+            .filter(|candidate| !std::ptr::eq(*candidate, current))
+            .find(|candidate| {
+                candidate.tbs_certificate.subject == current.tbs_certificate.issuer
+                    && certificate_signed_by(current, candidate)
+            });
+        match issuer {
+            Some(issuer) if !is_certificate_authority(issuer) => {
+                return Err(format!(
+                    "{} is not a certificate authority",
+                    issuer.tbs_certificate.subject
+                ));
+            },
+            Some(issuer) => current = issuer,
+            None => {
+                return Err(format!(
+                    "no trusted certificate issued {}",
+                    current.tbs_certificate.subject
+                ));
+            },
+        }
+    }
+    Err("certificate chain is too long".to_owned())
+}
+
+// Signing time from a PKCS#9 countersignature or an RFC 3161 timestamp token.
+// The time only counts if the timestamp signs this signature and its signer
+// chains to a trusted certificate; otherwise the reason is returned.
+fn signing_time(
+    signer_info: &SignerInfo,
+    certificates: &[&Certificate],
+    trust: &TrustStore,
+) -> Result<Option<DateTime<Utc>>, String> {
+    let Some(unsigned_attrs) = signer_info.unsigned_attrs.as_ref() else {
+        return Ok(None);
+    };
+    for attr in unsigned_attrs.iter() {
+        let Some(value) = attr.values.iter().next() else {
+            continue;
+        };
+        if attr.oid == OID_COUNTER_SIGNATURE {
+            return countersignature_time(value, signer_info, certificates, trust).map(Some);
+        } else if attr.oid == OID_RFC3161_TIMESTAMP {
+            return timestamp_token_time(value, signer_info, trust).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+// The countersigner signs the signature value of the signer it is attached
+// to, and its certificate comes with the signer's.
+fn countersignature_time(
+    value: &Any,
+    signer_info: &SignerInfo,
+    certificates: &[&Certificate],
+    trust: &TrustStore,
+) -> Result<DateTime<Utc>, String> {
+    let malformed = |err: der::Error| format!("malformed countersignature: {}", err);
+    let counter_signer: SignerInfo = value.decode_as().map_err(malformed)?;
+    let time = counter_signer
+        .signed_attrs
+        .as_ref()
+        .and_then(|attrs| attrs.iter().find(|attr| attr.oid == OID_SIGNING_TIME))
+        .and_then(|attr| attr.values.iter().next())
+        .ok_or_else(|| "countersignature has no signing time".to_owned())?;
+    // Time is a CHOICE, so it has to be decoded with its tag.
+    let time = time.to_der().and_then(|der| Time::from_der(&der)).map_err(malformed)?;
+    let time = DateTime::from_timestamp(time.to_unix_duration().as_secs() as i64, 0)
+        .ok_or_else(|| "countersignature time is out of range".to_owned())?;
+
+    let counter_cert = signer_certificate(&counter_signer, certificates)
+        .map_err(|err| format!("countersignature: {}", err))?;
+    if !verify_signer(&counter_signer, counter_cert, signer_info.signature.as_bytes()) {
+        return Err("countersignature does not verify".to_owned());
+    }
+    build_chain(counter_cert, certificates, trust, system_time(time), OID_KP_TIME_STAMPING)
+        .map_err(|err| format!("countersigner is not trusted: {}", err))?;
+    Ok(time)
+}
+
+// An RFC 3161 token is signed data of its own: the TSA signs a TSTInfo whose
+// message imprint is the hash of the signer's signature value.
+fn timestamp_token_time(
+    value: &Any,
+    signer_info: &SignerInfo,
+    trust: &TrustStore,
+) -> Result<DateTime<Utc>, String> {
+    let malformed = |err: der::Error| format!("malformed timestamp: {}", err);
+    let token: ContentInfo = value.decode_as().map_err(malformed)?;
+    let signed_data: SignedData = token.content.decode_as().map_err(malformed)?;
+    let tst_info: OctetString = signed_data
+        .encap_content_info
+        .econtent
+        .as_ref()
+        .ok_or_else(|| "timestamp has no content".to_owned())?
+        .decode_as()
+        .map_err(malformed)?;
+    let (imprint, gen_time) = tst_info_fields(tst_info.as_bytes()).map_err(malformed)?;
+    let time = DateTime::from_timestamp(gen_time.to_unix_duration().as_secs() as i64, 0)
+        .ok_or_else(|| "timestamp time is out of range".to_owned())?;
+
+    let imprint_matches =
+        HashAlgorithm::from_oid(&imprint.digest_algorithm.oid).is_some_and(|algorithm| {
+            algorithm.digest(&[signer_info.signature.as_bytes()]) == imprint.digest.as_bytes()
+        });
+    if !imprint_matches {
+        return Err("timestamp is for a different signature".to_owned());
+    }
+    let certificates = embedded_certificates(&signed_data);
+    let tsa_signer = signed_data
+        .signer_infos
+        .0
+        .iter()
+        .next()
+        .ok_or_else(|| "timestamp has no signer".to_owned())?;
+    let tsa_cert = signer_certificate(tsa_signer, &certificates)
+        .map_err(|err| format!("timestamp: {}", err))?;
+    if !verify_signer(tsa_signer, tsa_cert, tst_info.as_bytes()) {
+        return Err("timestamp signature does not verify".to_owned());
+    }
+    build_chain(tsa_cert, &certificates, trust, system_time(time), OID_KP_TIME_STAMPING)
+        .map_err(|err| format!("timestamp authority is not trusted: {}", err))?;
+    Ok(time)
+}
+
+// messageImprint and genTime from a TSTInfo; the fields after them are
+// skipped.
+fn tst_info_fields(der: &[u8]) -> der::Result<(DigestInfo, GeneralizedTime)> {
+    let mut reader = SliceReader::new(der)?;
+    reader.sequence(|reader| {
+        let _version = Any::decode(reader)?;
+        let _policy = ObjectIdentifier::decode(reader)?;
+        let message_imprint = DigestInfo::decode(reader)?;
+        let _serial_number = Any::decode(reader)?;
+        let gen_time = GeneralizedTime::decode(reader)?;
+        reader.read_slice(reader.remaining_len())?;
+        Ok((message_imprint, gen_time))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Made by tests/fixtures/authenticode/generate.py: a small DLL signed
+    // through an intermediate CA under root.cer.
+    fn fixture(name: &str) -> Vec<u8> {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/authenticode").join(name);
+        fs::read(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err))
+    }
+
+    fn trust_root() -> TrustStore {
+        let root = Certificate::from_der(&fixture("root.cer")).unwrap();
+        TrustStore {
+            directory: None,
+            certificates: vec![TrustedCertificate::new(PathBuf::from("root.cer"), root)],
+        }
+    }
+
+    fn check(name: &str) -> SignatureCheck {
+        verify_image(&fixture(name), &trust_root())
+    }
+
+    fn untrusted_reason(check: &SignatureCheck) -> &str {
+        match &check.status {
+            SignatureStatus::Untrusted(reason) => reason,
+            _ => panic!("expected an untrusted signature, got {}", check.details()),
+        }
+    }
+
+    #[test]
+    fn signed_image_is_trusted() {
+        let check = check("signed.dll");
+        assert_eq!(check.status, SignatureStatus::Trusted("CN=Fixture Root CA".to_owned()));
+        let signer = check.signer.unwrap();
+        assert_eq!(signer.subject, "CN=Fixture Publisher");
+        assert!(signer.digest_matches && signer.signature_valid);
+        assert!(signer.timestamp.is_none() && signer.timestamp_error.is_none());
+    }
+
+    #[test]
+    fn unsigned_image() {
+        assert_eq!(check("unsigned.dll").status, SignatureStatus::Unsigned);
+    }
+
+    #[test]
+    fn tampered_image_does_not_match_its_digest() {
+        let check = check("tampered.dll");
+        assert!(matches!(check.status, SignatureStatus::Invalid(_)), "{}", check.details());
+        let signer = check.signer.unwrap();
+        assert!(!signer.digest_matches);
+        assert!(signer.signature_valid);
+    }
+
+    #[test]
+    fn signature_without_trusted_root_is_untrusted() {
+        let check = verify_image(&fixture("signed.dll"), &TrustStore::default());
+        assert!(untrusted_reason(&check).starts_with("no trusted certificate issued"));
+    }
+
+    #[test]
+    fn issuers_have_to_be_certificate_authorities() {
+        let check = check("bad_chain.dll");
+        assert_eq!(untrusted_reason(&check), "CN=Fixture Not A CA is not a certificate authority");
+    }
+
+    #[test]
+    fn signer_needs_the_code_signing_usage() {
+        let check = check("no_code_signing.dll");
+        assert_eq!(untrusted_reason(&check), "CN=Fixture Web Server is not allowed to sign code");
+    }
+
+    #[test]
+    fn trusted_timestamp_covers_an_expired_signer() {
+        let check = check("timestamped.dll");
+        assert!(check.is_trusted(), "{}", check.details());
+        let timestamp = check.signer.unwrap().timestamp.unwrap();
+        assert_eq!(timestamp.to_rfc3339(), "2020-06-01T00:00:00+00:00");
+    }
+
+    #[test]
+    fn forged_timestamp_is_ignored() {
+        let check = check("forged_timestamp.dll");
+        assert!(untrusted_reason(&check).contains("was not valid on"));
+        let signer = check.signer.unwrap();
+        assert!(signer.timestamp.is_none());
+        assert_eq!(signer.timestamp_error.as_deref(), Some("timestamp signature does not verify"));
+    }
+
+    #[test]
+    fn timestamp_from_an_untrusted_authority_is_ignored() {
+        let signer =
+            verify_image(&fixture("timestamped.dll"), &TrustStore::default()).signer.unwrap();
+        assert!(signer.timestamp.is_none());
+        assert!(signer.timestamp_error.unwrap().starts_with("timestamp authority is not trusted"));
+    }
+
+    #[test]
+    fn certificate_table_outside_the_image_is_invalid() {
+        let signed = fixture("signed.dll");
+        let layout = pe_layout(&signed).unwrap();
+        let (_, size) = layout.certificate_table.unwrap();
+        for offset in [0x10, layout.checksum_offset, layout.security_entry_offset, signed.len() - 8]
+        {
+            let mut image = signed.clone();
+            let entry = layout.security_entry_offset;
+            image[entry..entry + 4].copy_from_slice(&(offset as u32).to_le_bytes());
+            image[entry + 4..entry + 8].copy_from_slice(&(size as u32).to_le_bytes());
+            assert!(pe_layout(&image).is_err(), "offset {:#x}", offset);
+            let check = verify_image(&image, &trust_root());
+            assert!(matches!(check.status, SignatureStatus::Invalid(_)), "offset {:#x}", offset);
+        }
+    }
+}
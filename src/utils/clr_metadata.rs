@@ -0,0 +1,321 @@
+use std::fmt;
+use std::path::Path;
+
+use pelite::image::IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR;
+use pelite::{FileMap, PeFile};
+
+use crate::utils::pe_machine::{ClrPlatform, PeArchitecture};
+
+// Reads just enough of the ECMA-335 metadata of a .NET assembly to offer the
+// methods the CLR hosting API can call: `static int Method(string)`.
+
+const METADATA_SIGNATURE: u32 = 0x424a_5342; // "BSJB"
+
+// Metadata table numbers.
+const TABLE_MODULE: usize = 0x00;
+const TABLE_TYPE_REF: usize = 0x01;
+const TABLE_TYPE_DEF: usize = 0x02;
+const TABLE_FIELD_PTR: usize = 0x03;
+const TABLE_FIELD: usize = 0x04;
+const TABLE_METHOD_PTR: usize = 0x05;
+const TABLE_METHOD_DEF: usize = 0x06;
+const TABLE_PARAM: usize = 0x08;
+const TABLE_MODULE_REF: usize = 0x1a;
+const TABLE_TYPE_SPEC: usize = 0x1b;
+const TABLE_ASSEMBLY_REF: usize = 0x23;
+
+const METHOD_ATTRIBUTE_STATIC: u16 = 0x0010;
+// DEFAULT calling convention, one parameter, returns I4, takes STRING.
+const STRING_TO_INT_SIGNATURE: [u8; 4] = [0x00, 0x01, 0x08, 0x0e];
+
+// A method ExecuteInDefaultAppDomain can invoke.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManagedEntryPoint {
+    // Namespace-qualified, as the hosting API expects it.
+    pub type_name: String,
+    pub method: String,
+}
+
+impl fmt::Display for ManagedEntryPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}::{}", self.type_name, self.method)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClrInfo {
+    // The metadata version string, e.g. "v4.0.30319".
+    pub runtime_version: String,
+    pub platform: ClrPlatform,
+    pub il_only: bool,
+    pub entry_points: Vec<ManagedEntryPoint>,
+}
+
+impl ClrInfo {
+    // The runtime to ask the CLR meta host for. Assemblies built for the
+    // 4.x framework all report v4.0.30319.
+    pub fn hosting_runtime(&self) -> &str {
+        if self.runtime_version.starts_with("v4.") { "v4.0.30319" } else { &self.runtime_version }
+    }
+}
+
+// None if the file is not a .NET assembly.
+pub fn inspect_clr(path: &Path) -> Result<Option<ClrInfo>, String> {
+    let file_map = FileMap::open(path).map_err(|err| err.to_string())?;
+    let pe = PeFile::from_bytes(&file_map).map_err(|err| err.to_string())?;
+    let architecture = PeArchitecture::from_pe(pe);
+    let (Some(clr), Some(platform)) = (architecture.clr, architecture.clr_platform()) else {
+        return Ok(None);
+    };
+
+    let directory = pe
+        .data_directory()
+        .get(IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR)
+        .ok_or("missing CLR header")?;
+    let cor_header = pe.slice(directory.VirtualAddress, 16, 1).map_err(|err| err.to_string())?;
+    let metadata_rva = read_u32(cor_header, 8).ok_or("truncated CLR header")?;
+    let metadata_size = read_u32(cor_header, 12).ok_or("truncated CLR header")?;
+    let metadata =
+        pe.slice(metadata_rva, metadata_size as usize, 1).map_err(|err| err.to_string())?;
+    let metadata = metadata.get(..metadata_size as usize).unwrap_or(metadata);
+
+    let (runtime_version, entry_points) = parse_metadata(metadata).ok_or("malformed metadata")?;
+    Ok(Some(ClrInfo { runtime_version, platform, il_only: clr.flags & 1 != 0, entry_points }))
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_index(bytes: &[u8], offset: usize, size: usize) -> Option<u32> {
+    match size {
+        2 => read_u16(bytes, offset).map(u32::from),
+        _ => read_u32(bytes, offset),
+    }
+}
+
+fn c_string(bytes: &[u8], offset: usize) -> Option<&str> {
+    let bytes = bytes.get(offset..)?;
+    let end = bytes.iter().position(|byte| *byte == 0)?;
+    std::str::from_utf8(&bytes[..end]).ok()
+}
+
+// A blob heap entry: an ECMA-335 compressed length followed by the data.
+fn blob(heap: &[u8], offset: usize) -> Option<&[u8]> {
+    let first = *heap.get(offset)?;
+    let (length, header) = if first & 0x80 == 0 {
+        (first as usize, 1)
+    } else if first & 0xc0 == 0x80 {
+        ((((first & 0x3f) as usize) << 8) | *heap.get(offset + 1)? as usize, 2)
+    } else {
+        let rest = heap.get(offset + 1..offset + 4)?;
+        (
+            (((first & 0x1f) as usize) << 24)
+                | ((rest[0] as usize) << 16)
+                | ((rest[1] as usize) << 8)
+                | rest[2] as usize,
+            4,
+        )
+    };
+    heap.get(offset + header..offset + header + length)
+}
+
+struct TableStream<'a> {
+    rows: [u32; 64],
+    string_index: usize,
+    guid_index: usize,
+    blob_index: usize,
+    data: &'a [u8],
+}
+
+impl TableStream<'_> {
+    fn index_size(&self, table: usize) -> usize {
+        if self.rows[table] < 0x1_0000 { 2 } else { 4 }
+    }
+
+    fn coded_index_size(&self, tables: &[usize], tag_bits: u32) -> usize {
+        let max_rows = tables.iter().map(|table| self.rows[*table]).max().unwrap_or(0);
+        if max_rows < 1 << (16 - tag_bits) { 2 } else { 4 }
+    }
+
+    fn row_size(&self, table: usize) -> usize {
+        let (string, guid, blob) = (self.string_index, self.guid_index, self.blob_index);
+        match table {
+            TABLE_MODULE => 2 + string + guid * 3,
+            TABLE_TYPE_REF => {
+                self.coded_index_size(
+                    &[TABLE_MODULE, TABLE_MODULE_REF, TABLE_ASSEMBLY_REF, TABLE_TYPE_REF],
+                    2,
+                ) + string * 2
+            },
+            TABLE_TYPE_DEF => {
+                4 + string * 2
+                    + self.coded_index_size(&[TABLE_TYPE_DEF, TABLE_TYPE_REF, TABLE_TYPE_SPEC], 2)
+                    + self.index_size(TABLE_FIELD)
+                    + self.index_size(TABLE_METHOD_DEF)
+            },
+            TABLE_FIELD_PTR => self.index_size(TABLE_FIELD),
+            TABLE_FIELD => 2 + string + blob,
+            TABLE_METHOD_PTR => self.index_size(TABLE_METHOD_DEF),
+            TABLE_METHOD_DEF => 4 + 2 + 2 + string + blob + self.index_size(TABLE_PARAM),
+            _ => 0,
+        }
+    }
+
+    // Offset of the first row of `table`; only the tables up to MethodDef
+    // are needed, and their sizes do not depend on later tables' layouts.
+    fn table_offset(&self, table: usize) -> usize {
+        (0..table).map(|previous| self.rows[previous] as usize * self.row_size(previous)).sum()
+    }
+}
+
+fn parse_metadata(metadata: &[u8]) -> Option<(String, Vec<ManagedEntryPoint>)> {
+    if read_u32(metadata, 0)? != METADATA_SIGNATURE {
+        return None;
+    }
+    let version_length = read_u32(metadata, 12)? as usize;
+    let runtime_version = c_string(metadata, 16)
+        .or_else(|| std::str::from_utf8(metadata.get(16..16 + version_length)?).ok())?
+        .to_owned();
+
+    let mut offset = 16 + version_length.next_multiple_of(4);
+    let stream_count = read_u16(metadata, offset + 2)?;
+    offset += 4;
+    let (mut tables, mut strings, mut blobs) = (None, None, None);
+    for _ in 0..stream_count {
+        let stream_offset = read_u32(metadata, offset)? as usize;
+        let stream_size = read_u32(metadata, offset + 4)? as usize;
+        let name = c_string(metadata, offset + 8)?;
+        let stream = metadata.get(stream_offset..stream_offset + stream_size)?;
+        match name {
+            "#~" | "#-" => tables = Some(stream),
+            "#Strings" => strings = Some(stream),
+            "#Blob" => blobs = Some(stream),
+            _ => {},
+        }
+        offset += 8 + (name.len() + 1).next_multiple_of(4);
+    }
+    let (tables, strings, blobs) = (tables?, strings?, blobs?);
+
+    let heap_sizes = *tables.get(6)?;
+    let valid = u64::from_le_bytes(tables.get(8..16)?.try_into().ok()?);
+    let mut rows = [0u32; 64];
+    let mut offset = 24;
+    for (table, count) in rows.iter_mut().enumerate() {
+        if valid & (1 << table) != 0 {
+            *count = read_u32(tables, offset)?;
+            offset += 4;
+        }
+    }
+    let stream = TableStream {
+        rows,
+        string_index: if heap_sizes & 0x01 != 0 { 4 } else { 2 },
+        guid_index: if heap_sizes & 0x02 != 0 { 4 } else { 2 },
+        blob_index: if heap_sizes & 0x04 != 0 { 4 } else { 2 },
+        data: tables.get(offset..)?,
+    };
+
+    let type_def_size = stream.row_size(TABLE_TYPE_DEF);
+    let method_def_size = stream.row_size(TABLE_METHOD_DEF);
+    let type_defs = stream.table_offset(TABLE_TYPE_DEF);
+    let method_defs = stream.table_offset(TABLE_METHOD_DEF);
+    let type_count = stream.rows[TABLE_TYPE_DEF] as usize;
+    let method_count = stream.rows[TABLE_METHOD_DEF] as usize;
+    // Offsets of the fields within a TypeDef row.
+    let type_namespace_at = 4 + stream.string_index;
+    let method_list_at = type_def_size - stream.index_size(TABLE_METHOD_DEF);
+
+    let method_list = |type_index: usize| -> Option<usize> {
+        if type_index == type_count {
+            return Some(method_count + 1);
+        }
+        let row = type_defs + type_index * type_def_size;
+        read_index(stream.data, row + method_list_at, stream.index_size(TABLE_METHOD_DEF))
+            .map(|index| index as usize)
+    };
+
+    let mut entry_points = Vec::new();
+    for type_index in 0..type_count {
+        let row = type_defs + type_index * type_def_size;
+        let name = read_index(stream.data, row + 4, stream.string_index)? as usize;
+        let namespace = read_index(stream.data, row + type_namespace_at, stream.string_index)?;
+        let name = c_string(strings, name)?;
+        let namespace = c_string(strings, namespace as usize)?;
+        let type_name = match namespace {
+            "" => name.to_owned(),
+            namespace => format!("{}.{}", namespace, name),
+        };
+
+        let first = method_list(type_index)?.max(1);
+        let last = method_list(type_index + 1)?.min(method_count + 1);
+        for method_index in first..last {
+            let row = method_defs + (method_index - 1) * method_def_size;
+            let flags = read_u16(stream.data, row + 6)?;
+            let name = read_index(stream.data, row + 8, stream.string_index)? as usize;
+            let signature =
+                read_index(stream.data, row + 8 + stream.string_index, stream.blob_index)? as usize;
+            if flags & METHOD_ATTRIBUTE_STATIC != 0
+                && blob(blobs, signature) == Some(&STRING_TO_INT_SIGNATURE[..])
+            {
+                entry_points.push(ManagedEntryPoint {
+                    type_name: type_name.clone(),
+                    method: c_string(strings, name)?.to_owned(),
+                });
+            }
+        }
+    }
+    Some((runtime_version, entry_points))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    // Written by tests/fixtures/clr/generate.py.
+    fn fixture(path: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(path)
+    }
+
+    fn entry_point(type_name: &str, method: &str) -> ManagedEntryPoint {
+        ManagedEntryPoint { type_name: type_name.to_owned(), method: method.to_owned() }
+    }
+
+    #[test]
+    fn lists_static_string_to_int_methods() {
+        let info = inspect_clr(&fixture("clr/managed.dll")).unwrap().unwrap();
+        assert_eq!(info.runtime_version, "v4.0.30319");
+        assert_eq!(info.hosting_runtime(), "v4.0.30319");
+        assert_eq!(info.platform, ClrPlatform::AnyCpu);
+        assert!(info.il_only);
+        // Not the instance method, the void one or anything of <Module> and
+        // the empty type between them.
+        assert_eq!(info.entry_points, [
+            entry_point("Fixture.Entry", "Run"),
+            entry_point("Loader", "Start")
+        ]);
+        assert_eq!(info.entry_points[0].to_string(), "Fixture.Entry::Run");
+    }
+
+    #[test]
+    fn native_images_have_no_metadata() {
+        assert!(inspect_clr(&fixture("authenticode/unsigned.dll")).unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_metadata_is_rejected() {
+        let image = std::fs::read(fixture("clr/managed.dll")).unwrap();
+        let start = image.windows(4).position(|bytes| bytes == b"BSJB").unwrap();
+        let metadata = &image[start..];
+        assert!(parse_metadata(metadata).is_some());
+        // Into the root, the stream headers, the tables and the blob heap.
+        for len in [0, 4, 16, 40, 80, 200, 400] {
+            assert!(parse_metadata(&metadata[..len]).is_none(), "{} bytes", len);
+        }
+    }
+}
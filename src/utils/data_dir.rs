@@ -0,0 +1,10 @@
+use std::path::PathBuf;
+
+pub const APP_DIRECTORY_NAME: &str = "NullInjector";
+
+// Per-user directory for logs, history and other state:
+// %LOCALAPPDATA%\NullInjector on Windows, ~/.local/share/NullInjector
+// elsewhere. Falls back to the temp directory if neither is known.
+pub fn app_data_directory() -> PathBuf {
+    dirs::data_local_dir().unwrap_or_else(std::env::temp_dir).join(APP_DIRECTORY_NAME)
+}
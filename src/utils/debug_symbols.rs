@@ -0,0 +1,618 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::{fmt, fs, thread};
+
+use gimli::{AttributeValue, EndianSlice, RunTimeEndian};
+use goblin::elf::Elf;
+use goblin::elf::program_header::PT_LOAD;
+use goblin::elf::section_header::{SHF_COMPRESSED, SHT_NOBITS};
+use goblin::elf::sym::STT_FUNC;
+use libmem::Module;
+use pdb::{FallibleIterator, PDB, SymbolData};
+use pelite::PeFile;
+use pelite::pe64::debug::{CodeView, Entry};
+use tracing::{error, info, warn};
+
+use crate::utils::data_dir::app_data_directory;
+use crate::utils::pe_exports::demangle;
+
+// Function symbols of the modules in a target from their debug info: the PDB
+// a PE's CodeView record names, or DWARF in an ELF file or its separate debug
+// file. Only local directories are searched; there is no symbol server.
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+// Symbols without a size (PDB publics, some ELF symbols) cover this much.
+const MAX_UNSIZED_OFFSET: usize = 0x1000;
+// Where distributions install separate debug files.
+const SYSTEM_DEBUG_DIRECTORY: &str = "/usr/lib/debug";
+
+#[derive(Debug, Clone)]
+pub struct DebugSymbol {
+    pub rva: usize,
+    // 0 when the debug info does not say.
+    pub size: usize,
+    pub name: String,
+}
+
+#[derive(Debug)]
+pub struct ModuleSymbols {
+    // The PDB or ELF file the symbols were read from.
+    pub source: PathBuf,
+    // Sorted by RVA, one per address.
+    symbols: Vec<DebugSymbol>,
+    // Every name a symbol was found under (decorated, mangled, plain) to its
+    // index in `symbols`.
+    by_name: HashMap<String, usize>,
+}
+
+impl ModuleSymbols {
+    fn new(source: PathBuf, mut entries: Vec<DebugSymbol>) -> Self {
+        entries.sort_by_key(|symbol| symbol.rva);
+        let mut symbols: Vec<DebugSymbol> = Vec::new();
+        let mut by_name = HashMap::new();
+        for entry in entries {
+            match symbols.last_mut() {
+                Some(last) if last.rva == entry.rva => {
+                    last.size = last.size.max(entry.size);
+                    by_name.entry(entry.name).or_insert(symbols.len() - 1);
+                },
+                _ => {
+                    by_name.entry(entry.name.clone()).or_insert(symbols.len());
+                    symbols.push(entry);
+                },
+            }
+        }
+        ModuleSymbols { source, symbols, by_name }
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn find(&self, name: &str) -> Option<&DebugSymbol> {
+        self.by_name.get(name).map(|index| &self.symbols[*index])
+    }
+
+    // Symbols starting inside `rvas`, in order.
+    pub fn starting_in(&self, rvas: Range<usize>) -> &[DebugSymbol] {
+        let start = self.symbols.partition_point(|symbol| symbol.rva < rvas.start);
+        let end = self.symbols.partition_point(|symbol| symbol.rva < rvas.end);
+        &self.symbols[start..end]
+    }
+
+    // The function `rva` is in and the offset into it.
+    pub fn containing(&self, rva: usize) -> Option<(&DebugSymbol, usize)> {
+        let index = self.symbols.partition_point(|symbol| symbol.rva <= rva).checked_sub(1)?;
+        let symbol = &self.symbols[index];
+        let offset = rva - symbol.rva;
+        let limit = if symbol.size == 0 { MAX_UNSIZED_OFFSET } else { symbol.size };
+        (offset < limit).then_some((symbol, offset))
+    }
+}
+
+// Directories searched for debug files, in order. Written like
+// _NT_SYMBOL_PATH: separated by semicolons, and for srv*cache*server entries
+// the local cache is used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolPath {
+    pub directories: Vec<PathBuf>,
+}
+
+impl SymbolPath {
+    pub fn parse(text: &str) -> Self {
+        let directories = text
+            .split(';')
+            .filter_map(|entry| {
+                let entry = entry.trim();
+                let parts: Vec<&str> = entry.split('*').collect();
+                match parts.as_slice() {
+                    [""] => None,
+                    [directory] => Some(PathBuf::from(directory)),
+                    [kind, cache, ..]
+                        if kind.eq_ignore_ascii_case("srv")
+                            || kind.eq_ignore_ascii_case("cache") =>
+                    {
+                        (!cache.is_empty() && !cache.contains("://")).then(|| PathBuf::from(cache))
+                    },
+                    _ => None,
+                }
+            })
+            .collect();
+        SymbolPath { directories }
+    }
+
+    // Followed by _NT_SYMBOL_PATH and our own symbols directory, leaving out
+    // directories that are already in it.
+    pub fn with_defaults(mut self) -> Self {
+        let environment = SymbolPath::parse(&std::env::var("_NT_SYMBOL_PATH").unwrap_or_default());
+        for directory in
+            environment.directories.into_iter().chain([app_data_directory().join("symbols")])
+        {
+            if !self.directories.contains(&directory) {
+                self.directories.push(directory);
+            }
+        }
+        self
+    }
+}
+
+impl fmt::Display for SymbolPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let directories: Vec<String> =
+            self.directories.iter().map(|directory| directory.display().to_string()).collect();
+        write!(f, "{}", directories.join(";"))
+    }
+}
+
+type CachedSymbols = Result<Arc<ModuleSymbols>, String>;
+
+struct SymbolCache {
+    // The directories set in the UI. None until first used; then the saved
+    // ones, or none.
+    saved: Option<SymbolPath>,
+    // Bumped when the path changes, so loads started with the old path are
+    // dropped when they finish.
+    generation: u64,
+    // None while a background load is running.
+    modules: Vec<(PathBuf, Option<CachedSymbols>)>,
+}
+
+// Loaded once per module file, including failures, so symbolizing addresses
+// in a module without debug info does not search the disk again.
+static SYMBOLS: Mutex<SymbolCache> =
+    Mutex::new(SymbolCache { saved: None, generation: 0, modules: Vec::new() });
+
+// The path set in the UI, kept across runs.
+fn symbol_path_file() -> PathBuf {
+    app_data_directory().join("symbol_path.txt")
+}
+
+pub fn saved_symbol_path() -> SymbolPath {
+    let load = || {
+        let text = fs::read_to_string(symbol_path_file()).unwrap_or_default();
+        SymbolPath::parse(text.trim())
+    };
+    match SYMBOLS.lock() {
+        Ok(mut cache) => cache.saved.get_or_insert_with(load).clone(),
+        Err(_) => load(),
+    }
+}
+
+// What is searched: the saved directories, then the defaults.
+pub fn symbol_path() -> SymbolPath {
+    saved_symbol_path().with_defaults()
+}
+
+// Replaces and saves the directories set in the UI, and forgets what was
+// loaded with the old ones.
+pub fn set_symbol_path(path: SymbolPath) {
+    let file = symbol_path_file();
+    let result = file
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&file, path.to_string()));
+    if let Err(err) = result {
+        error!("Failed to save the symbol path to {}: {}", file.display(), err);
+    }
+    if let Ok(mut cache) = SYMBOLS.lock() {
+        cache.saved = Some(path);
+        cache.generation += 1;
+        cache.modules.clear();
+    }
+}
+
+fn store_symbols(module_path: &Path, symbols: CachedSymbols) {
+    if let Ok(mut cache) = SYMBOLS.lock() {
+        match cache.modules.iter_mut().find(|(path, _)| path == module_path) {
+            Some((_, entry)) => *entry = Some(symbols),
+            None => cache.modules.push((module_path.to_owned(), Some(symbols))),
+        }
+    }
+}
+
+// Waits for the symbols; for worker threads. A load already running in the
+// background is not waited for but repeated.
+pub fn module_symbols(module_path: &Path) -> CachedSymbols {
+    if let Ok(cache) = SYMBOLS.lock() {
+        if let Some((_, Some(symbols))) = cache.modules.iter().find(|(path, _)| path == module_path)
+        {
+            return symbols.clone();
+        }
+    }
+    // Loaded without holding the lock; a PDB can take a while.
+    let symbols = load_symbols(module_path, &symbol_path()).map(Arc::new);
+    store_symbols(module_path, symbols.clone());
+    symbols
+}
+
+// The symbols if they are loaded, for the UI thread. Otherwise None, and
+// they are loaded on a background thread; the outcome is logged.
+pub fn request_symbols(module_path: &Path) -> Option<CachedSymbols> {
+    let path = symbol_path();
+    let generation = {
+        let mut cache = SYMBOLS.lock().ok()?;
+        if let Some((_, symbols)) = cache.modules.iter().find(|(path, _)| path == module_path) {
+            return symbols.clone();
+        }
+        cache.modules.push((module_path.to_owned(), None));
+        cache.generation
+    };
+    let module_path = module_path.to_owned();
+    let spawned = thread::Builder::new().name("symbol-load".to_owned()).spawn({
+        let module_path = module_path.clone();
+        move || {
+            let symbols = load_symbols(&module_path, &path).map(Arc::new);
+            match &symbols {
+                Ok(symbols) => info!(
+                    "Loaded {} symbols for {} from {}",
+                    symbols.len(),
+                    module_path.display(),
+                    symbols.source.display()
+                ),
+                Err(err) => warn!("No debug symbols for {}: {}", module_path.display(), err),
+            }
+            if SYMBOLS.lock().is_ok_and(|cache| cache.generation == generation) {
+                store_symbols(&module_path, symbols);
+            }
+        }
+    });
+    if let Err(err) = spawned {
+        store_symbols(&module_path, Err(format!("failed to start loading symbols: {}", err)));
+    }
+    None
+}
+
+pub fn symbols_loading(module_path: &Path) -> bool {
+    SYMBOLS.lock().is_ok_and(|cache| {
+        cache.modules.iter().any(|(path, symbols)| path == module_path && symbols.is_none())
+    })
+}
+
+pub fn any_symbols_loading() -> bool {
+    SYMBOLS.lock().is_ok_and(|cache| cache.modules.iter().any(|(_, symbols)| symbols.is_none()))
+}
+
+// "module!function+0x10" for an address inside a function of `module`.
+pub fn symbol_name(module: &Module, symbols: &ModuleSymbols, address: usize) -> Option<String> {
+    let (symbol, offset) = symbols.containing(address.checked_sub(module.base)?)?;
+    Some(match offset {
+        0 => format!("{}!{}", module.name, symbol.name),
+        offset => format!("{}!{}+{:#x}", module.name, symbol.name, offset),
+    })
+}
+
+// symbol_name for an address in any of `modules`, waiting for the symbols.
+pub fn symbolize(modules: &[Module], address: usize) -> Option<String> {
+    let module = modules.iter().find(|module| (module.base..module.end).contains(&address))?;
+    let symbols = module_symbols(Path::new(&module.path)).ok()?;
+    symbol_name(module, &symbols, address)
+}
+
+fn load_symbols(module_path: &Path, symbol_path: &SymbolPath) -> Result<ModuleSymbols, String> {
+    let bytes = fs::read(module_path).map_err(|err| err.to_string())?;
+    if bytes.starts_with(ELF_MAGIC) {
+        load_elf_symbols(module_path, &bytes, symbol_path)
+    } else {
+        load_pdb_symbols(module_path, &bytes, symbol_path)
+    }
+}
+
+// The CodeView (RSDS) record: which PDB was written with the image.
+struct PdbReference {
+    path: PathBuf,
+    guid: (u32, u16, u16, [u8; 8]),
+    age: u32,
+}
+
+impl PdbReference {
+    // Directory name a symbol store keeps this PDB under.
+    fn store_key(&self) -> String {
+        let (data1, data2, data3, data4) = self.guid;
+        let data4: String = data4.iter().map(|byte| format!("{:02X}", byte)).collect();
+        format!("{:08X}{:04X}{:04X}{}{:X}", data1, data2, data3, data4, self.age)
+    }
+}
+
+fn pdb_reference(pe: PeFile) -> Result<PdbReference, String> {
+    let debug = pe.debug().map_err(|err| match err {
+        pelite::Error::Null => "no debug directory".to_owned(),
+        err => err.to_string(),
+    })?;
+    debug
+        .iter()
+        .find_map(|directory| match directory.entry() {
+            Ok(Entry::CodeView(CodeView::Cv70 { image, pdb_file_name })) => Some(PdbReference {
+                path: PathBuf::from(pdb_file_name.to_string()),
+                guid: (
+                    image.Signature.Data1,
+                    image.Signature.Data2,
+                    image.Signature.Data3,
+                    image.Signature.Data4,
+                ),
+                age: image.Age,
+            }),
+            _ => None,
+        })
+        .ok_or_else(|| "no PDB reference in the debug directory".to_owned())
+}
+
+fn load_pdb_symbols(
+    module_path: &Path,
+    bytes: &[u8],
+    symbol_path: &SymbolPath,
+) -> Result<ModuleSymbols, String> {
+    let pe = PeFile::from_bytes(bytes).map_err(|err| err.to_string())?;
+    let reference = pdb_reference(pe)?;
+    // The recorded path may be a Windows path on another machine.
+    let file_name = reference
+        .path
+        .to_string_lossy()
+        .rsplit(['\\', '/'])
+        .next()
+        .map(str::to_owned)
+        .unwrap_or_default();
+
+    let mut candidates = vec![reference.path.clone()];
+    if let Some(directory) = module_path.parent() {
+        candidates.push(directory.join(&file_name));
+    }
+    for directory in &symbol_path.directories {
+        candidates.push(directory.join(&file_name));
+        candidates.push(directory.join(&file_name).join(reference.store_key()).join(&file_name));
+    }
+
+    // A broken or mismatched file does not stop the search; the first error
+    // is reported if no candidate matches.
+    let mut failed = None;
+    let mut mismatched = None;
+    for candidate in candidates.iter().filter(|candidate| candidate.is_file()) {
+        match read_pdb(candidate, &reference) {
+            Ok(Some(symbols)) => return Ok(ModuleSymbols::new(candidate.clone(), symbols)),
+            Ok(None) => {
+                mismatched.get_or_insert(candidate);
+            },
+            Err(err) => {
+                failed.get_or_insert_with(|| format!("{}: {}", candidate.display(), err));
+            },
+        }
+    }
+    Err(match (failed, mismatched) {
+        (Some(err), _) => err,
+        (None, Some(candidate)) => format!("{} does not match the module", candidate.display()),
+        (None, None) => format!("{} not found in the symbol path", file_name),
+    })
+}
+
+// None when the PDB belongs to another build of the module.
+fn read_pdb(path: &Path, reference: &PdbReference) -> pdb::Result<Option<Vec<DebugSymbol>>> {
+    let mut pdb = PDB::open(fs::File::open(path)?)?;
+    let information = pdb.pdb_information()?;
+    let (data1, data2, data3, data4) = information.guid.as_fields();
+    let dbi = pdb.debug_information()?;
+    if (data1, data2, data3, *data4) != reference.guid
+        || dbi.age().is_some_and(|age| age != reference.age)
+    {
+        return Ok(None);
+    }
+    let address_map = pdb.address_map()?;
+    let mut symbols = Vec::new();
+
+    // Procedures from the module streams have sizes and undecorated names,
+    // so they go first and the publics add what the modules lack.
+    let mut modules = dbi.modules()?;
+    while let Some(module) = modules.next()? {
+        let Some(info) = pdb.module_info(&module)? else {
+            continue;
+        };
+        let mut module_symbols = info.symbols()?;
+        while let Some(symbol) = module_symbols.next()? {
+            if let Ok(SymbolData::Procedure(procedure)) = symbol.parse() {
+                if let Some(rva) = procedure.offset.to_rva(&address_map) {
+                    symbols.push(DebugSymbol {
+                        rva: rva.0 as usize,
+                        size: procedure.len as usize,
+                        name: procedure.name.to_string().into_owned(),
+                    });
+                }
+            }
+        }
+    }
+    let globals = pdb.global_symbols()?;
+    let mut global_symbols = globals.iter();
+    while let Some(symbol) = global_symbols.next()? {
+        if let Ok(SymbolData::Public(public)) = symbol.parse() {
+            if let (true, Some(rva)) = (public.function, public.offset.to_rva(&address_map)) {
+                symbols.push(DebugSymbol {
+                    rva: rva.0 as usize,
+                    size: 0,
+                    name: public.name.to_string().into_owned(),
+                });
+            }
+        }
+    }
+    Ok(Some(symbols))
+}
+
+fn elf_section<'a>(elf: &Elf, bytes: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    let header = elf.section_headers.iter().find(|header| {
+        header.sh_type != SHT_NOBITS && elf.shdr_strtab.get_at(header.sh_name) == Some(name)
+    })?;
+    if header.sh_flags & SHF_COMPRESSED as u64 != 0 {
+        return None;
+    }
+    let start = header.sh_offset as usize;
+    start.checked_add(header.sh_size as usize).and_then(|end| bytes.get(start..end))
+}
+
+// Where a separate debug file for this ELF may be: by build ID, then by the
+// .gnu_debuglink name next to it, in .debug and in the debug directories.
+fn separate_debug_files(
+    elf: &Elf,
+    bytes: &[u8],
+    module_path: &Path,
+    symbol_path: &SymbolPath,
+) -> Vec<PathBuf> {
+    let mut roots = symbol_path.directories.clone();
+    roots.push(PathBuf::from(SYSTEM_DEBUG_DIRECTORY));
+    let mut candidates = Vec::new();
+
+    let build_id = elf
+        .iter_note_sections(bytes, Some(".note.gnu.build-id"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .find(|note| note.n_type == goblin::elf::note::NT_GNU_BUILD_ID)
+        .map(|note| note.desc.iter().map(|byte| format!("{:02x}", byte)).collect::<String>());
+    if let Some(build_id) = build_id.filter(|build_id| build_id.len() > 2) {
+        for root in &roots {
+            candidates.push(
+                root.join(".build-id")
+                    .join(&build_id[..2])
+                    .join(format!("{}.debug", &build_id[2..])),
+            );
+        }
+    }
+
+    let debug_link = elf_section(elf, bytes, ".gnu_debuglink").and_then(|section| {
+        let end = section.iter().position(|byte| *byte == 0)?;
+        String::from_utf8(section[..end].to_vec()).ok()
+    });
+    if let (Some(debug_link), Some(directory)) = (debug_link, module_path.parent()) {
+        candidates.push(directory.join(&debug_link));
+        candidates.push(directory.join(".debug").join(&debug_link));
+        for root in &roots {
+            candidates.push(root.join(&debug_link));
+            // /usr/lib/debug mirrors the directory the library is in.
+            candidates.push(
+                root.join(directory.strip_prefix("/").unwrap_or(directory)).join(&debug_link),
+            );
+        }
+    }
+    candidates
+}
+
+fn load_elf_symbols(
+    module_path: &Path,
+    bytes: &[u8],
+    symbol_path: &SymbolPath,
+) -> Result<ModuleSymbols, String> {
+    let elf = Elf::parse(bytes).map_err(|err| err.to_string())?;
+    // Addresses in the debug info are virtual addresses; the module base is
+    // where the first segment starts.
+    let bias = elf
+        .program_headers
+        .iter()
+        .filter(|header| header.p_type == PT_LOAD)
+        .map(|header| header.p_vaddr & !0xfff)
+        .min()
+        .unwrap_or(0);
+
+    if elf_section(&elf, bytes, ".debug_info").is_some() {
+        return elf_file_symbols(module_path, bytes, bias);
+    }
+    for candidate in separate_debug_files(&elf, bytes, module_path, symbol_path) {
+        if let Ok(debug_bytes) = fs::read(&candidate) {
+            return elf_file_symbols(&candidate, &debug_bytes, bias);
+        }
+    }
+    // Without DWARF the symbol table still names the functions.
+    let symbols = symbol_table(&elf, bias);
+    if symbols.is_empty() {
+        return Err("no DWARF or symbol table".to_owned());
+    }
+    Ok(ModuleSymbols::new(module_path.to_owned(), symbols))
+}
+
+fn elf_file_symbols(path: &Path, bytes: &[u8], bias: u64) -> Result<ModuleSymbols, String> {
+    let elf = Elf::parse(bytes).map_err(|err| err.to_string())?;
+    let mut symbols = dwarf_functions(&elf, bytes, bias).map_err(|err| err.to_string())?;
+    symbols.extend(symbol_table(&elf, bias));
+    Ok(ModuleSymbols::new(path.to_owned(), symbols))
+}
+
+fn symbol_table(elf: &Elf, bias: u64) -> Vec<DebugSymbol> {
+    elf.syms
+        .iter()
+        .filter(|sym| sym.st_type() == STT_FUNC && sym.st_value >= bias && sym.st_value != 0)
+        .filter_map(|sym| {
+            Some((sym, elf.strtab.get_at(sym.st_name).filter(|name| !name.is_empty())?))
+        })
+        .flat_map(|(sym, name)| {
+            demangle(name).into_iter().chain([name.to_owned()]).map(move |name| DebugSymbol {
+                rva: (sym.st_value - bias) as usize,
+                size: sym.st_size as usize,
+                name,
+            })
+        })
+        .collect()
+}
+
+type DwarfReader<'a> = EndianSlice<'a, RunTimeEndian>;
+
+// The name to show (demangled when there is a linkage name), then the plain
+// and mangled names the function can also be looked up by.
+fn dwarf_names<'a>(
+    dwarf: &gimli::Dwarf<DwarfReader<'a>>,
+    unit: &gimli::Unit<DwarfReader<'a>>,
+    entry: &gimli::DebuggingInformationEntry<DwarfReader<'a>>,
+) -> gimli::Result<Vec<String>> {
+    let string =
+        |value| dwarf.attr_string(unit, value).map(|string| string.to_string_lossy().into_owned());
+    let mut name = entry.attr_value(gimli::DW_AT_name)?.map(string).transpose()?;
+    let mut linkage_name = entry.attr_value(gimli::DW_AT_linkage_name)?.map(string).transpose()?;
+    // Out-of-line definitions name the declaration they implement.
+    for origin in [gimli::DW_AT_specification, gimli::DW_AT_abstract_origin] {
+        if let Some(AttributeValue::UnitRef(offset)) = entry.attr_value(origin)? {
+            let declaration = unit.entry(offset)?;
+            if name.is_none() {
+                name = declaration.attr_value(gimli::DW_AT_name)?.map(string).transpose()?;
+            }
+            if linkage_name.is_none() {
+                linkage_name =
+                    declaration.attr_value(gimli::DW_AT_linkage_name)?.map(string).transpose()?;
+            }
+        }
+    }
+    let mut names: Vec<String> = linkage_name.as_deref().and_then(demangle).into_iter().collect();
+    names.extend(name);
+    names.extend(linkage_name);
+    Ok(names)
+}
+
+fn dwarf_functions(elf: &Elf, bytes: &[u8], bias: u64) -> gimli::Result<Vec<DebugSymbol>> {
+    let endian = if elf.little_endian { RunTimeEndian::Little } else { RunTimeEndian::Big };
+    let dwarf = gimli::Dwarf::load(|section: gimli::SectionId| -> gimli::Result<DwarfReader> {
+        Ok(EndianSlice::new(elf_section(elf, bytes, section.name()).unwrap_or(&[]), endian))
+    })?;
+    let mut symbols = Vec::new();
+    let mut headers = dwarf.units();
+    while let Some(header) = headers.next()? {
+        let unit = dwarf.unit(header)?;
+        let mut entries = unit.entries();
+        while let Some((_, entry)) = entries.next_dfs()? {
+            if entry.tag() != gimli::DW_TAG_subprogram {
+                continue;
+            }
+            let Some(low) = entry.attr_value(gimli::DW_AT_low_pc)? else {
+                continue;
+            };
+            let Some(low) = dwarf.attr_address(&unit, low)?.filter(|low| *low >= bias && *low != 0)
+            else {
+                continue;
+            };
+            let size = match entry.attr_value(gimli::DW_AT_high_pc)? {
+                Some(AttributeValue::Udata(size)) => size,
+                Some(high) => {
+                    dwarf.attr_address(&unit, high)?.map_or(0, |high| high.saturating_sub(low))
+                },
+                None => 0,
+            };
+            for name in dwarf_names(&dwarf, &unit, entry)? {
+                symbols.push(DebugSymbol { rva: (low - bias) as usize, size: size as usize, name });
+            }
+        }
+    }
+    Ok(symbols)
+}
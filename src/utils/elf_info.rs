@@ -0,0 +1,185 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use goblin::elf::Elf;
+use goblin::elf::header::{EM_386, EM_AARCH64, EM_ARM, EM_X86_64, ET_DYN};
+use goblin::elf::section_header::SHT_INIT_ARRAY;
+use goblin::elf::sym::{STB_GLOBAL, STB_WEAK, STT_FUNC, STT_GNU_IFUNC, STT_OBJECT};
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+
+// What the inject list shows for a shared object, the ELF counterpart of the
+// PE machine type.
+#[derive(Debug, Clone)]
+pub struct ElfDetails {
+    // 32 or 64
+    pub class: u8,
+    pub machine: String,
+    pub soname: Option<String>,
+    pub needed: Vec<String>,
+    // Functions and objects the library defines in its dynamic symbol table.
+    pub exports: Vec<String>,
+    // DT_INIT followed by the .init_array entries, as offsets from the load
+    // base.
+    pub constructors: Vec<u64>,
+}
+
+impl ElfDetails {
+    // In the same "short/long" form as the PE architectures.
+    pub fn arch(&self) -> String {
+        format!("{} (ELF{})", self.machine, self.class)
+    }
+}
+
+pub fn is_elf_file(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|_| &magic == ELF_MAGIC)
+}
+
+pub fn inspect_elf(path: &Path) -> Result<ElfDetails, String> {
+    let bytes = fs::read(path).map_err(|err| err.to_string())?;
+    let elf = Elf::parse(&bytes).map_err(|err| err.to_string())?;
+    if elf.header.e_type != ET_DYN {
+        return Err("not a shared object".to_owned());
+    }
+
+    let machine = match elf.header.e_machine {
+        EM_X86_64 => "x64/x86_64".to_owned(),
+        EM_386 => "x86/i386".to_owned(),
+        EM_AARCH64 => "arm64/aarch64".to_owned(),
+        EM_ARM => "arm/ARM".to_owned(),
+        machine => format!("unknown ({})", machine),
+    };
+
+    let mut exports: Vec<String> = elf
+        .dynsyms
+        .iter()
+        .filter(|sym| {
+            !sym.is_import()
+                && matches!(sym.st_bind(), STB_GLOBAL | STB_WEAK)
+                && matches!(sym.st_type(), STT_FUNC | STT_GNU_IFUNC | STT_OBJECT)
+        })
+        .filter_map(|sym| elf.dynstrtab.get_at(sym.st_name))
+        .filter(|name| !name.is_empty())
+        .map(str::to_owned)
+        .collect();
+    exports.sort();
+    exports.dedup();
+
+    let mut constructors: Vec<u64> = elf
+        .dynamic
+        .as_ref()
+        .map(|dynamic| dynamic.info.init)
+        .filter(|init| *init != 0)
+        .into_iter()
+        .collect();
+    constructors.extend(init_array(&elf, &bytes));
+
+    Ok(ElfDetails {
+        class: if elf.is_64 { 64 } else { 32 },
+        machine,
+        soname: elf.soname.map(str::to_owned),
+        needed: elf.libraries.iter().map(|name| name.to_string()).collect(),
+        exports,
+        constructors,
+    })
+}
+
+// The .init_array entries. In position independent code they are usually
+// zero on disk and filled in by RELATIVE relocations, so take the addend of
+// the relocation that targets the slot instead.
+fn init_array(elf: &Elf, bytes: &[u8]) -> Vec<u64> {
+    let entry_size = if elf.is_64 { 8 } else { 4 };
+    let mut entries = Vec::new();
+    for section in elf.section_headers.iter().filter(|section| section.sh_type == SHT_INIT_ARRAY) {
+        let start = section.sh_offset as usize;
+        let Some(data) =
+            start.checked_add(section.sh_size as usize).and_then(|end| bytes.get(start..end))
+        else {
+            continue;
+        };
+        for (index, slot) in data.chunks_exact(entry_size).enumerate() {
+            let value = match (elf.is_64, elf.little_endian) {
+                (true, true) => u64::from_le_bytes(slot.try_into().unwrap_or_default()),
+                (true, false) => u64::from_be_bytes(slot.try_into().unwrap_or_default()),
+                (false, true) => u32::from_le_bytes(slot.try_into().unwrap_or_default()) as u64,
+                (false, false) => u32::from_be_bytes(slot.try_into().unwrap_or_default()) as u64,
+            };
+            let address = section.sh_addr.wrapping_add((index * entry_size) as u64);
+            let value = if value == 0 {
+                elf.dynrelas
+                    .iter()
+                    .find(|reloc| reloc.r_offset == address)
+                    .and_then(|reloc| reloc.r_addend)
+                    .map_or(0, |addend| addend as u64)
+            } else {
+                value
+            };
+            // 0 and -1 are placeholders some linkers leave at the ends.
+            if value != 0 && value != u64::MAX && value != u32::MAX as u64 {
+                entries.push(value);
+            }
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    // Built by tests/fixtures/elf/build.sh from fixture.c.
+    fn fixture() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/elf/libfixture.so")
+    }
+
+    #[test]
+    fn inspects_a_shared_object() {
+        assert!(is_elf_file(&fixture()));
+        let details = inspect_elf(&fixture()).unwrap();
+        assert_eq!(details.class, 64);
+        assert_eq!(details.arch(), "x64/x86_64 (ELF64)");
+        assert_eq!(details.soname.as_deref(), Some("libfixture.so.1"));
+        assert_eq!(details.needed, ["libm.so.6", "libc.so.6"]);
+        // Static functions stay out, functions and objects are listed.
+        assert_eq!(details.exports, [
+            "fixture_add",
+            "fixture_keep",
+            "fixture_scale",
+            "initialized",
+            "scale"
+        ]);
+        // DT_INIT, then frame_dummy and fixture_init from .init_array, whose
+        // slots are zero on disk and filled in by RELATIVE relocations.
+        assert_eq!(details.constructors, [0x1000, 0x1100, 0x1040]);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let source = fixture().with_file_name("fixture.c");
+        assert!(!is_elf_file(&source));
+        assert!(inspect_elf(&source).is_err());
+    }
+
+    #[test]
+    fn init_array_past_the_end_is_skipped() {
+        let mut bytes = fs::read(fixture()).unwrap();
+        let elf = Elf::parse(&bytes).unwrap();
+        let index = elf
+            .section_headers
+            .iter()
+            .position(|section| section.sh_type == SHT_INIT_ARRAY)
+            .unwrap();
+        // sh_size of a 64-bit section header.
+        let size = elf.header.e_shoff as usize + index * elf.header.e_shentsize as usize + 0x20;
+        drop(elf);
+        bytes[size..size + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        let elf = Elf::parse(&bytes).unwrap();
+        assert!(init_array(&elf, &bytes).is_empty());
+    }
+}
@@ -0,0 +1,450 @@
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use iced_x86::{Decoder, DecoderOptions, Mnemonic, OpKind, Register};
+use libmem::memory::read_memory_ex;
+use libmem::module::enum_modules_ex;
+use libmem::process::Process;
+use libmem::{Bits, Module};
+use pelite::image::{
+    IMAGE_DIRECTORY_ENTRY_IAT, IMAGE_REL_BASED_DIR64, IMAGE_REL_BASED_HIGHLOW, IMAGE_SCN_CNT_CODE,
+    IMAGE_SCN_MEM_EXECUTE, IMAGE_SCN_MEM_WRITE, IMAGE_SECTION_HEADER,
+};
+use pelite::pe64::imports::Import;
+use pelite::{PeFile, Wrap};
+
+use crate::utils::debug_symbols::{module_symbols, symbolize};
+use crate::utils::pe_exports::{ExportSymbol, api_set_host};
+use crate::utils::remote_exports::{RemoteExportResolver, read_remote_bytes};
+use crate::utils::text_assembler::{DisassembledInstruction, disassemble};
+
+// Compares the modules loaded in a target with their files on disk: code
+// sections byte for byte after applying relocations, and import address
+// table entries against what the exports they name resolve to.
+
+// Differences this close together are reported as one patch.
+const MERGE_GAP: usize = 8;
+// Bytes disassembled past the end of a patch.
+const CONTEXT_BYTES: usize = 16;
+// A patch this close after an export is disassembled from the export.
+const MAX_SYMBOL_LEAD: usize = 32;
+// Exports further away than this do not name a patch.
+const MAX_SYMBOL_OFFSET: usize = 0x1000;
+// Instructions at the start of a patch searched for where it jumps to.
+const DESTINATION_INSTRUCTIONS: usize = 4;
+
+// Where hooked code or an import slot leads.
+#[derive(Debug, Clone)]
+pub struct HookDestination {
+    pub address: usize,
+    // The module and offset the address falls in; None for memory no module
+    // backs, which is where injected hook handlers usually live.
+    pub module: Option<(String, usize)>,
+    // module!function+offset when the module has debug symbols.
+    pub symbol: Option<String>,
+}
+
+impl HookDestination {
+    fn locate(modules: &[Module], address: usize) -> Self {
+        let module = modules
+            .iter()
+            .find(|module| (module.base..module.end).contains(&address))
+            .map(|module| (module.name.clone(), address - module.base));
+        HookDestination { address, module, symbol: symbolize(modules, address) }
+    }
+
+    pub fn describe(&self) -> String {
+        match (&self.symbol, &self.module) {
+            (Some(symbol), _) => symbol.clone(),
+            (None, Some((module, offset))) => format!("{}+{:#x}", module, offset),
+            (None, None) => format!("{:#x} (no module)", self.address),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CodePatch {
+    pub address: usize,
+    pub section: String,
+    // Nearest preceding export, e.g. NtOpenProcess+0x3.
+    pub symbol: Option<String>,
+    pub original: Vec<u8>,
+    pub current: Vec<u8>,
+    // Both listings cover the same bytes, starting at the export when the
+    // patch is close enough to it.
+    pub original_code: Vec<DisassembledInstruction>,
+    pub current_code: Vec<DisassembledInstruction>,
+    // Set when the patched code jumps out of the module.
+    pub destination: Option<HookDestination>,
+}
+
+impl CodePatch {
+    pub fn range(&self) -> Range<usize> {
+        self.address..self.address + self.current.len()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IatHook {
+    // DLL!name or DLL!#ordinal as the module imports it.
+    pub import: String,
+    pub slot: usize,
+    pub expected: usize,
+    pub current: HookDestination,
+}
+
+#[derive(Debug, Clone)]
+pub struct ModuleScan {
+    pub name: String,
+    pub path: String,
+    pub base: usize,
+    pub patches: Vec<CodePatch>,
+    pub iat_hooks: Vec<IatHook>,
+    pub code_bytes: usize,
+    pub imports_checked: usize,
+    // Why the module could not be compared (fully or partly).
+    pub error: Option<String>,
+}
+
+impl ModuleScan {
+    pub fn has_findings(&self) -> bool {
+        !self.patches.is_empty() || !self.iat_hooks.is_empty()
+    }
+}
+
+pub fn scan_hooks(process: &Process) -> Result<Vec<ModuleScan>, String> {
+    let modules = enum_modules_ex(process).ok_or("failed to enumerate the modules")?;
+    let mut exports = RemoteExportResolver::new(process);
+    Ok(modules.iter().map(|module| scan_module(process, module, &modules, &mut exports)).collect())
+}
+
+fn scan_module(
+    process: &Process,
+    module: &Module,
+    modules: &[Module],
+    exports: &mut RemoteExportResolver,
+) -> ModuleScan {
+    let mut scan = ModuleScan {
+        name: module.name.clone(),
+        path: module.path.clone(),
+        base: module.base,
+        patches: Vec::new(),
+        iat_hooks: Vec::new(),
+        code_bytes: 0,
+        imports_checked: 0,
+        error: None,
+    };
+    if let Err(err) = compare_module(process, module, modules, exports, &mut scan) {
+        scan.error = Some(err);
+    }
+    scan
+}
+
+fn compare_module(
+    process: &Process,
+    module: &Module,
+    modules: &[Module],
+    exports: &mut RemoteExportResolver,
+    scan: &mut ModuleScan,
+) -> Result<(), String> {
+    let file = fs::read(&module.path).map_err(|err| err.to_string())?;
+    let pe = PeFile::from_bytes(&file).map_err(|err| err.to_string())?;
+    // A WOW64 redirect or an update on disk leaves a different file there.
+    let nt_headers = read_memory_ex::<u32>(process, module.base + 0x3c)
+        .ok_or("failed to read the DOS header")? as usize;
+    let time_date_stamp = read_memory_ex::<u32>(process, module.base + nt_headers + 8)
+        .ok_or("failed to read the file header")?;
+    if time_date_stamp != pe.file_header().TimeDateStamp {
+        return Err("the file on disk is not the image that is loaded".to_owned());
+    }
+
+    let (image_base, bitness) = match pe.optional_header() {
+        Wrap::T32(header) => (header.ImageBase as u64, 32),
+        Wrap::T64(header) => (header.ImageBase, 64),
+    };
+    let delta = (module.base as u64).wrapping_sub(image_base);
+    let mut relocations = Vec::new();
+    if let Ok(base_relocs) = pe.base_relocs() {
+        base_relocs.for_each(|rva, kind| relocations.push((rva as usize, kind)));
+    }
+    relocations.sort_unstable();
+    // The loader writes the IAT, which some linkers merge into .text.
+    let iat = pe
+        .data_directory()
+        .get(IMAGE_DIRECTORY_ENTRY_IAT)
+        .map_or(0..0, |iat| iat.VirtualAddress as usize..(iat.VirtualAddress + iat.Size) as usize);
+    let symbols = export_symbols(pe);
+    // Loaded on the first patch; most modules have none.
+    let mut debug_symbols = None;
+
+    for section in pe.section_headers() {
+        let characteristics = section.Characteristics;
+        if characteristics & (IMAGE_SCN_CNT_CODE | IMAGE_SCN_MEM_EXECUTE) == 0
+            || characteristics & IMAGE_SCN_MEM_WRITE != 0
+        {
+            continue;
+        }
+        let section_rva = section.VirtualAddress as usize;
+        let expected = expected_section(&file, section, delta, &relocations, bitness);
+        let section_name = section.name().map_or_else(|_| "?".to_owned(), str::to_owned);
+        let current = read_remote_bytes(process, module.base + section_rva, expected.len())
+            .ok_or_else(|| format!("failed to read {}", section_name))?;
+        scan.code_bytes += expected.len();
+
+        let masked = |offset: usize| iat.contains(&(section_rva + offset));
+        for run in differing_runs(&expected, &current, masked) {
+            let rva = section_rva + run.start;
+            // Debug symbols also name functions that are not exported.
+            let nearest = debug_symbols
+                .get_or_insert_with(|| module_symbols(Path::new(&module.path)).ok())
+                .as_ref()
+                .and_then(|debug_symbols| debug_symbols.containing(rva))
+                .map(|(symbol, offset)| (rva - offset, symbol.name.clone()))
+                .or_else(|| {
+                    symbols
+                        .iter()
+                        .rev()
+                        .find(|(export_rva, _)| *export_rva <= rva)
+                        .filter(|(export_rva, _)| rva - export_rva < MAX_SYMBOL_OFFSET)
+                        .cloned()
+                });
+            let listing_start = match &nearest {
+                Some((symbol_rva, _))
+                    if rva - symbol_rva <= MAX_SYMBOL_LEAD && *symbol_rva >= section_rva =>
+                {
+                    symbol_rva - section_rva
+                },
+                _ => run.start,
+            };
+            let listing = listing_start..(run.end + CONTEXT_BYTES).min(expected.len());
+            let listing_address = (module.base + section_rva + listing.start) as u64;
+            let address = module.base + rva;
+            scan.patches.push(CodePatch {
+                address,
+                section: section_name.clone(),
+                symbol: nearest.map(|(symbol_rva, name)| match rva - symbol_rva {
+                    0 => name,
+                    offset => format!("{}+{:#x}", name, offset),
+                }),
+                original: expected[run.clone()].to_vec(),
+                current: current[run.clone()].to_vec(),
+                original_code: disassemble(&expected[listing.clone()], bitness, listing_address),
+                current_code: disassemble(&current[listing], bitness, listing_address),
+                destination: hook_destination(process, &current[run.start..], address, bitness)
+                    .filter(|target| !(module.base..module.end).contains(target))
+                    .map(|target| HookDestination::locate(modules, target)),
+            });
+        }
+    }
+
+    // The IAT of a 64-bit module in a WOW64 process is not the one in use.
+    let process_bitness = match process.bits {
+        Bits::Bits32 => 32,
+        Bits::Bits64 => 64,
+    };
+    if bitness == process_bitness {
+        check_imports(process, module, pe, modules, exports, scan)?;
+    }
+    Ok(())
+}
+
+// Exported function RVAs with a name for each, sorted by RVA.
+fn export_symbols(pe: PeFile) -> Vec<(usize, String)> {
+    let Ok(by) = pe.exports().and_then(|exports| exports.by()) else {
+        return Vec::new();
+    };
+    let mut symbols: Vec<(usize, String)> = by
+        .iter_names()
+        .filter_map(|(name, export)| {
+            Some((export.ok()?.symbol()? as usize, name.ok()?.to_string()))
+        })
+        .collect();
+    symbols.sort();
+    symbols.dedup_by_key(|(rva, _)| *rva);
+    symbols
+}
+
+// The section as the loader maps it at the module's base: raw data padded
+// to the virtual size with relocations applied.
+fn expected_section(
+    file: &[u8],
+    section: &IMAGE_SECTION_HEADER,
+    delta: u64,
+    relocations: &[(usize, u8)],
+    bitness: u32,
+) -> Vec<u8> {
+    let len = match section.VirtualSize {
+        0 => section.SizeOfRawData as usize,
+        size => size as usize,
+    };
+    let mut bytes = vec![0; len];
+    let raw_start = section.PointerToRawData as usize;
+    let raw_len = (section.SizeOfRawData as usize).min(len);
+    if let Some(raw) = file.get(raw_start..raw_start + raw_len) {
+        bytes[..raw.len()].copy_from_slice(raw);
+    }
+
+    let start = section.VirtualAddress as usize;
+    let first = relocations.partition_point(|(rva, _)| *rva < start);
+    for &(rva, kind) in relocations[first..].iter().take_while(|(rva, _)| *rva < start + len) {
+        let offset = rva - start;
+        match kind {
+            IMAGE_REL_BASED_HIGHLOW if offset + 4 <= len => {
+                let field = &mut bytes[offset..offset + 4];
+                let value =
+                    u32::from_le_bytes(field.try_into().unwrap()).wrapping_add(delta as u32);
+                field.copy_from_slice(&value.to_le_bytes());
+            },
+            IMAGE_REL_BASED_DIR64 if offset + 8 <= len && bitness == 64 => {
+                let field = &mut bytes[offset..offset + 8];
+                let value = u64::from_le_bytes(field.try_into().unwrap()).wrapping_add(delta);
+                field.copy_from_slice(&value.to_le_bytes());
+            },
+            _ => {},
+        }
+    }
+    bytes
+}
+
+fn differing_runs(
+    expected: &[u8],
+    current: &[u8],
+    masked: impl Fn(usize) -> bool,
+) -> Vec<Range<usize>> {
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    for offset in 0..expected.len().min(current.len()) {
+        if expected[offset] == current[offset] || masked(offset) {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run) if offset - run.end <= MERGE_GAP => run.end = offset + 1,
+            _ => runs.push(offset..offset + 1),
+        }
+    }
+    runs
+}
+
+fn read_pointer(process: &Process, address: usize, bitness: u32) -> Option<usize> {
+    match bitness {
+        32 => read_memory_ex::<u32>(process, address).map(|pointer| pointer as usize),
+        _ => read_memory_ex::<u64>(process, address).map(|pointer| pointer as usize),
+    }
+}
+
+// Where the code at `address` transfers control to, for the usual hook
+// stubs: jmp/call rel, jmp [slot], mov reg, imm + jmp reg and push imm + ret.
+fn hook_destination(process: &Process, code: &[u8], address: usize, bitness: u32) -> Option<usize> {
+    let mut decoder = Decoder::with_ip(bitness, code, address as u64, DecoderOptions::NONE);
+    let mut pushed = None;
+    let mut loaded: Option<(Register, u64)> = None;
+    for instruction in decoder.iter().take(DESTINATION_INSTRUCTIONS) {
+        let target = match (instruction.mnemonic(), instruction.op0_kind()) {
+            (
+                Mnemonic::Jmp | Mnemonic::Call,
+                OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64,
+            ) => Some(instruction.near_branch_target()),
+            (Mnemonic::Jmp | Mnemonic::Call, OpKind::Memory) => {
+                let slot = if instruction.is_ip_rel_memory_operand() {
+                    Some(instruction.ip_rel_memory_address())
+                } else if instruction.memory_base() == Register::None
+                    && instruction.memory_index() == Register::None
+                {
+                    Some(instruction.memory_displacement64())
+                } else {
+                    None
+                };
+                slot.and_then(|slot| read_pointer(process, slot as usize, bitness))
+                    .map(|pointer| pointer as u64)
+            },
+            (Mnemonic::Jmp | Mnemonic::Call, OpKind::Register) => loaded
+                .filter(|(register, _)| *register == instruction.op0_register().full_register())
+                .map(|(_, value)| value),
+            (
+                Mnemonic::Push,
+                OpKind::Immediate8to32 | OpKind::Immediate32 | OpKind::Immediate32to64,
+            ) => {
+                pushed = Some(instruction.immediate(0));
+                continue;
+            },
+            (Mnemonic::Mov, OpKind::Register)
+                if matches!(
+                    instruction.op1_kind(),
+                    OpKind::Immediate32 | OpKind::Immediate64 | OpKind::Immediate32to64
+                ) =>
+            {
+                loaded =
+                    Some((instruction.op0_register().full_register(), instruction.immediate(1)));
+                continue;
+            },
+            (Mnemonic::Ret, _) => pushed,
+            _ => None,
+        };
+        return target.map(|target| target as usize);
+    }
+    None
+}
+
+fn check_imports(
+    process: &Process,
+    module: &Module,
+    pe: PeFile,
+    modules: &[Module],
+    exports: &mut RemoteExportResolver,
+    scan: &mut ModuleScan,
+) -> Result<(), String> {
+    let imports = match pe.imports() {
+        Ok(imports) => imports,
+        Err(pelite::Error::Null) => return Ok(()),
+        Err(err) => return Err(err.to_string()),
+    };
+    let (bitness, pointer_size) = match pe {
+        Wrap::T32(_) => (32, 4),
+        Wrap::T64(_) => (64, 8),
+    };
+    for descriptor in imports {
+        let (Ok(dll), Ok(names)) = (descriptor.dll_name(), descriptor.int()) else {
+            continue;
+        };
+        let dll = dll.to_string();
+        let host = api_set_host(&dll).map_or(dll.as_str(), |host| host);
+        let first_thunk = descriptor.image().FirstThunk as usize;
+        for (index, import) in names.enumerate() {
+            let symbol = match import {
+                Ok(Import::ByName { name, .. }) => ExportSymbol::Name(name.to_string()),
+                Ok(Import::ByOrdinal { ord }) => ExportSymbol::Ordinal(ord),
+                Err(_) => continue,
+            };
+            let slot = module.base + first_thunk + index * pointer_size;
+            let Some(current) = read_pointer(process, slot, bitness) else {
+                continue;
+            };
+            scan.imports_checked += 1;
+            // Imports the resolver cannot follow (API sets it does not know)
+            // are left alone rather than reported.
+            let Ok(expected) = exports.resolve_symbol(host, &symbol) else {
+                continue;
+            };
+            if current == expected {
+                continue;
+            }
+            let current = HookDestination::locate(modules, current);
+            let expected_module = HookDestination::locate(modules, expected).module;
+            // Still inside the exporting module: a forwarder the loader
+            // bound differently, not a redirect.
+            if current.module.as_ref().map(|(name, _)| name)
+                == expected_module.as_ref().map(|(name, _)| name)
+                && current.module.is_some()
+            {
+                continue;
+            }
+            scan.iat_hooks.push(IatHook {
+                import: format!("{}!{}", dll, symbol),
+                slot,
+                expected,
+                current,
+            });
+        }
+    }
+    Ok(())
+}
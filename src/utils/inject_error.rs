@@ -0,0 +1,150 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::utils::managed_injection::ManagedCode;
+
+// Everything that can go wrong while injecting, one variant per stage of the
+// pipeline, with enough context to tell what exactly failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InjectError {
+    UnsupportedArchitecture { arch: String },
+    InvalidDllPath { path: String, reason: String },
+    // A pinned DLL changed on disk since it was added to the list.
+    DllModified { path: String, expected: String, actual: Option<String> },
+    // Blocked by the signature policy.
+    Signature { path: String, reason: String },
+    OpenProcess { pid: u32 },
+    Allocate { what: &'static str, size: usize },
+    Write { what: &'static str, address: usize, size: usize },
+    Protect { what: &'static str, address: usize, size: usize },
+    ModuleNotFound { module: String },
+    ReadModule { module: String, reason: String },
+    ResolveExport { module: String, export: String, reason: String },
+    BuildShellcode { reason: String },
+    CreateThread { status: i32 },
+    WaitAbandoned,
+    WaitTimedOut { timeout: Duration },
+    Cancelled,
+    ModuleWaitTimedOut { module: String, timeout: Duration },
+    WaitFailed { reason: String },
+    LoadModule { path: String },
+    // LoadLibrary returned NULL in the target; the code is its GetLastError.
+    RemoteLoad { error_code: Option<u32> },
+    // The technique reported success but the module is not in the target.
+    NotLoaded { path: String },
+    // A step of the CLR bootstrap failed in the target. None if the result
+    // could not be read back.
+    Managed { step: &'static str, code: Option<ManagedCode> },
+}
+
+impl InjectError {
+    // Short name of the failing stage, stored with the injection history.
+    pub fn stage(&self) -> &'static str {
+        match self {
+            InjectError::UnsupportedArchitecture { .. } => "architecture",
+            InjectError::InvalidDllPath { .. } => "dll path",
+            InjectError::DllModified { .. } => "integrity",
+            InjectError::Signature { .. } => "signature",
+            InjectError::OpenProcess { .. } => "open process",
+            InjectError::Allocate { .. } => "allocate",
+            InjectError::Write { .. } => "write",
+            InjectError::Protect { .. } => "protect",
+            InjectError::ModuleNotFound { .. } | InjectError::ReadModule { .. } => "find module",
+            InjectError::ResolveExport { .. } => "resolve export",
+            InjectError::BuildShellcode { .. } => "build shellcode",
+            InjectError::CreateThread { .. } => "create thread",
+            InjectError::WaitAbandoned
+            | InjectError::WaitTimedOut { .. }
+            | InjectError::WaitFailed { .. } => "wait",
+            InjectError::Cancelled => "cancelled",
+            InjectError::ModuleWaitTimedOut { .. } => "wait for module",
+            InjectError::LoadModule { .. } | InjectError::RemoteLoad { .. } => "load module",
+            InjectError::NotLoaded { .. } => "verify",
+            InjectError::Managed { .. } => "managed",
+        }
+    }
+}
+
+impl fmt::Display for InjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InjectError::UnsupportedArchitecture { arch } => {
+                write!(f, "Process architecture {} is not supported", arch)
+            },
+            InjectError::InvalidDllPath { path, reason } => {
+                write!(f, "Invalid DLL path {}: {}", path, reason)
+            },
+            InjectError::DllModified { path, expected, actual: Some(actual) } => write!(
+                f,
+                "{} changed since it was pinned: SHA-256 is {}, expected {}",
+                path, actual, expected
+            ),
+            InjectError::DllModified { path, expected, actual: None } => write!(
+                f,
+                "{} could not be read to check it against its pinned SHA-256 {}",
+                path, expected
+            ),
+            InjectError::Signature { path, reason } => {
+                write!(f, "{} is blocked by the signature policy: {}", path, reason)
+            },
+            InjectError::OpenProcess { pid } => write!(f, "Failed to open process {}", pid),
+            InjectError::Allocate { what, size } => {
+                write!(f, "Failed to allocate {} bytes for the {} in the target", size, what)
+            },
+            InjectError::Write { what, address, size } => {
+                write!(f, "Failed to write the {} ({} bytes) at {:#x}", what, size, address)
+            },
+            InjectError::Protect { what, address, size } => write!(
+                f,
+                "Failed to change the protection of the {} ({} bytes) at {:#x}",
+                what, size, address
+            ),
+            InjectError::ModuleNotFound { module } => {
+                write!(f, "{} is not loaded in the target", module)
+            },
+            InjectError::ReadModule { module, reason } => {
+                write!(f, "Failed to read {}: {}", module, reason)
+            },
+            InjectError::ResolveExport { module, export, reason } => {
+                write!(f, "Failed to resolve {}!{}: {}", module, export, reason)
+            },
+            InjectError::BuildShellcode { reason } => {
+                write!(f, "Failed to build shellcode: {}", reason)
+            },
+            InjectError::CreateThread { status } => {
+                write!(f, "NtCreateThreadEx failed with NTSTATUS {:#010x}", status)
+            },
+            InjectError::WaitAbandoned => write!(f, "Wait for the remote thread was abandoned"),
+            InjectError::WaitTimedOut { timeout } => {
+                write!(f, "Remote thread did not finish within {:?}", timeout)
+            },
+            InjectError::Cancelled => write!(f, "Injection was cancelled"),
+            InjectError::ModuleWaitTimedOut { module, timeout } => {
+                write!(f, "{} was not loaded in the target within {:?}", module, timeout)
+            },
+            InjectError::WaitFailed { reason } => {
+                write!(f, "Failed to wait for the remote thread: {}", reason)
+            },
+            InjectError::LoadModule { path } => {
+                write!(f, "Failed to load {} in the target process", path)
+            },
+            InjectError::RemoteLoad { error_code: Some(code) } => {
+                write!(f, "LoadLibraryW failed in the target with error {} ({:#x})", code, code)
+            },
+            InjectError::RemoteLoad { error_code: None } => {
+                write!(f, "LoadLibraryW failed in the target, its result could not be read")
+            },
+            InjectError::NotLoaded { path } => {
+                write!(f, "{} is not among the target's modules after injection", path)
+            },
+            InjectError::Managed { step, code: Some(code) } => {
+                write!(f, "{} failed in the target with {}", step, code)
+            },
+            InjectError::Managed { step, code: None } => {
+                write!(f, "The CLR bootstrap failed, {} did not complete", step)
+            },
+        }
+    }
+}
+
+impl std::error::Error for InjectError {}
@@ -0,0 +1,190 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{error, warn};
+
+use crate::utils::data_dir::app_data_directory;
+use crate::utils::managed_injection::ManagedCall;
+use crate::utils::processlist::InjectionTechnique;
+
+// The oldest records are dropped once the history holds this many.
+const MAX_HISTORY_RECORDS: usize = 5000;
+
+// One injection attempt, successful or not.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InjectionRecord {
+    pub id: u64,
+    pub timestamp: DateTime<Local>,
+    pub target_name: String,
+    pub target_pid: u32,
+    pub target_path: String,
+    pub target_arch: String,
+    pub dll_path: String,
+    pub dll_sha256: Option<String>,
+    pub technique: Option<InjectionTechnique>,
+    pub duration_ms: u64,
+    pub module_base: Option<u64>,
+    pub success: bool,
+    pub error: Option<String>,
+    // Stage of the pipeline that failed, see `InjectError::stage`.
+    #[serde(default)]
+    pub error_stage: Option<String>,
+    // The method called for a managed injection, and what it returned.
+    #[serde(default)]
+    pub managed: Option<ManagedCall>,
+    #[serde(default)]
+    pub return_value: Option<i32>,
+    // Our remote buffers still allocated in the target afterwards, from the
+    // post-injection audit.
+    #[serde(default)]
+    pub leftovers: Option<String>,
+}
+
+impl InjectionRecord {
+    pub fn dll_name(&self) -> &str {
+        Path::new(&self.dll_path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(&self.dll_path)
+    }
+
+    // Case-insensitive match against the target, DLL, hash and error.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        query.is_empty()
+            || [
+                self.target_name.as_str(),
+                self.target_path.as_str(),
+                self.dll_path.as_str(),
+                self.dll_sha256.as_deref().unwrap_or_default(),
+                self.error.as_deref().unwrap_or_default(),
+            ]
+            .iter()
+            .any(|field| field.to_lowercase().contains(&query))
+            || self.target_pid.to_string() == query
+    }
+}
+
+// Injection attempts, persisted as JSON in the per-user data directory.
+pub struct InjectionHistory {
+    path: Option<PathBuf>,
+    records: Vec<InjectionRecord>,
+}
+
+impl Default for InjectionHistory {
+    fn default() -> Self {
+        InjectionHistory::load(default_history_path())
+    }
+}
+
+impl InjectionHistory {
+    pub fn load(path: PathBuf) -> Self {
+        let records = match File::open(&path) {
+            Ok(file) => match serde_json::from_reader(BufReader::new(file)) {
+                Ok(records) => records,
+                Err(err) => {
+                    // Keep the unreadable file instead of overwriting it.
+                    let backup = path.with_extension("json.bak");
+                    warn!(
+                        "Failed to parse {}: {}, moving it to {}",
+                        path.display(),
+                        err,
+                        backup.display()
+                    );
+                    let _ = fs::rename(&path, &backup);
+                    Vec::new()
+                },
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => {
+                error!("Failed to open {}: {}", path.display(), err);
+                Vec::new()
+            },
+        };
+        InjectionHistory { path: Some(path), records }
+    }
+
+    pub fn records(&self) -> &[InjectionRecord] {
+        &self.records
+    }
+
+    pub fn next_id(&self) -> u64 {
+        self.records.iter().map(|record| record.id).max().unwrap_or(0) + 1
+    }
+
+    pub fn push(&mut self, record: InjectionRecord) {
+        if self.records.len() == MAX_HISTORY_RECORDS {
+            self.records.remove(0);
+        }
+        self.records.push(record);
+        self.save();
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.save();
+    }
+
+    fn save(&self) {
+        let Some(path) = self.path.as_ref() else {
+            return;
+        };
+        // Write to a temporary file first so a crash never leaves a truncated
+        // history behind.
+        let temp_path = path.with_extension("json.tmp");
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| write_json(&temp_path, &self.records.iter().collect::<Vec<_>>()))
+            .and_then(|_| fs::rename(&temp_path, path));
+        if let Err(err) = result {
+            error!("Failed to save injection history to {}: {}", path.display(), err);
+        }
+    }
+}
+
+pub fn default_history_path() -> PathBuf {
+    app_data_directory().join("history.json")
+}
+
+pub fn export_json(path: &Path, records: &[&InjectionRecord]) -> io::Result<()> {
+    write_json(path, records)
+}
+
+pub fn export_csv(path: &Path, records: &[&InjectionRecord]) -> io::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    for record in records {
+        writer.serialize(record).map_err(io::Error::other)?;
+    }
+    writer.flush()
+}
+
+fn write_json(path: &Path, records: &[&InjectionRecord]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, records).map_err(io::Error::other)?;
+    io::Write::flush(&mut writer)
+}
+
+// What to do when a pinned DLL no longer matches the hash recorded when it
+// was added to the list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinPolicy {
+    Warn,
+    Refuse,
+}
+
+#[derive(Debug, Clone)]
+pub struct PinnedHash {
+    pub sha256: String,
+    pub policy: PinPolicy,
+}
+
+pub fn file_sha256(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut BufReader::new(File::open(path)?), &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
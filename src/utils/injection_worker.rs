@@ -0,0 +1,399 @@
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use libmem::module::{enum_modules_ex, find_module_ex, unload_module_ex};
+use libmem::process::Process;
+use tracing::{error, info, info_span, warn};
+
+use crate::utils::authenticode::{SignaturePolicy, verify_file};
+use crate::utils::inject_error::InjectError;
+use crate::utils::injection_history::{InjectionRecord, PinPolicy, PinnedHash, file_sha256};
+use crate::utils::managed_injection::{ManagedCall, inject_managed};
+use crate::utils::process_watcher::process_key;
+use crate::utils::processlist::{InjectOptions, WAIT_SLICE, inject_dll_test_fix};
+use crate::utils::remote_allocations::{RemoteAllocation, allocation_mark, allocations_since};
+
+// What happens to the rest of a batch when one DLL fails to inject.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailurePolicy {
+    Continue,
+    Stop,
+    // Stop and eject the DLLs this batch already injected, newest first.
+    RollBack,
+}
+
+pub struct InjectionJob {
+    // Becomes the history id of the attempt and the injection id the log
+    // console groups by. Assigned when the batch is started.
+    pub id: u64,
+    pub target: Process,
+    pub dll_path: String,
+    pub delay: Duration,
+    pub wait_for_module: Option<String>,
+    pub pinned: Option<PinnedHash>,
+    // Run a method of a .NET assembly instead of loading it as a native DLL.
+    pub managed: Option<ManagedCall>,
+}
+
+impl InjectionJob {
+    pub fn new(target: Process, dll_path: String) -> Self {
+        InjectionJob {
+            id: 0,
+            target,
+            dll_path,
+            delay: Duration::ZERO,
+            wait_for_module: None,
+            pinned: None,
+            managed: None,
+        }
+    }
+
+    pub fn dll_name(&self) -> String {
+        Path::new(&self.dll_path)
+            .file_name()
+            .map_or_else(|| self.dll_path.clone(), |name| name.to_string_lossy().into_owned())
+    }
+}
+
+enum InjectionUpdate {
+    Progress { id: u64, step: &'static str },
+    Finished(Box<InjectionRecord>),
+}
+
+// Runs a batch of injections one after another on a background thread, so a
+// slow or hung target never blocks the UI.
+pub struct InjectionWorker {
+    updates: Receiver<InjectionUpdate>,
+    options: InjectOptions,
+    worker: Option<JoinHandle<()>>,
+    jobs: Vec<(u64, String)>,
+    finished: usize,
+    current: Option<(u64, &'static str)>,
+    done: bool,
+}
+
+impl InjectionWorker {
+    pub fn spawn(
+        jobs: Vec<InjectionJob>,
+        options: InjectOptions,
+        failure_policy: FailurePolicy,
+    ) -> Self {
+        let (update_tx, update_rx) = mpsc::channel();
+        let job_names = jobs.iter().map(|job| (job.id, job.dll_name())).collect();
+        let worker_options = options.clone();
+
+        let worker = thread::Builder::new()
+            .name("injection-worker".to_owned())
+            .spawn(move || worker_loop(jobs, worker_options, failure_policy, update_tx))
+            .map_err(|err| error!("Failed to spawn injection worker thread: {}", err))
+            .ok();
+
+        InjectionWorker {
+            updates: update_rx,
+            options,
+            done: worker.is_none(),
+            worker,
+            jobs: job_names,
+            finished: 0,
+            current: None,
+        }
+    }
+
+    // Stops waiting on the current remote thread and skips the remaining
+    // jobs. A running load_module_ex call still runs to completion.
+    pub fn cancel(&self) {
+        self.options.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.options.cancel.load(Ordering::Relaxed)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    // Return the attempts that finished since the last call.
+    pub fn poll(&mut self) -> Vec<InjectionRecord> {
+        let mut records = Vec::new();
+        loop {
+            match self.updates.try_recv() {
+                Ok(InjectionUpdate::Progress { id, step }) => self.current = Some((id, step)),
+                Ok(InjectionUpdate::Finished(record)) => {
+                    self.finished += 1;
+                    records.push(*record);
+                },
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.done = true;
+                    if let Some(worker) = self.worker.take() {
+                        let _ = worker.join();
+                    }
+                    break;
+                },
+            }
+        }
+        records
+    }
+
+    // e.g. "example.dll (2/3): waiting for remote thread"
+    pub fn status(&self) -> String {
+        let Some((id, step)) = self.current else {
+            return format!("Starting ({} DLLs)", self.jobs.len());
+        };
+        let (position, name) = self
+            .jobs
+            .iter()
+            .enumerate()
+            .find(|(_, (job_id, _))| *job_id == id)
+            .map_or((0, ""), |(index, (_, name))| (index + 1, name.as_str()));
+        format!("{} ({}/{}): {}", name, position, self.jobs.len(), step)
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.finished as f32 / self.jobs.len().max(1) as f32
+    }
+}
+
+impl Drop for InjectionWorker {
+    fn drop(&mut self) {
+        // Not joined: the thread may be stuck inside load_module_ex, and it
+        // exits on its own once that returns.
+        self.cancel();
+    }
+}
+
+fn worker_loop(
+    jobs: Vec<InjectionJob>,
+    options: InjectOptions,
+    failure_policy: FailurePolicy,
+    updates: Sender<InjectionUpdate>,
+) {
+    // DLLs this batch injected so far, for rolling back.
+    let mut injected: Vec<(&InjectionJob, u64)> = Vec::new();
+    let mut stopped = false;
+
+    for job in &jobs {
+        if stopped || options.cancel.load(Ordering::Relaxed) {
+            warn!("Injection stopped, skipping {}", job.dll_name());
+            continue;
+        }
+        let record = run_job(job, &options, &updates);
+        match (record.success, failure_policy) {
+            (true, _) => match (record.module_base, job.managed.as_ref()) {
+                (Some(base), None) => injected.push((job, base)),
+                // The CLR cannot unload an assembly from the default domain.
+                (_, Some(_)) => info!("{} cannot be rolled back", job.dll_name()),
+                (None, None) => {},
+            },
+            (false, FailurePolicy::Continue) => {},
+            (false, FailurePolicy::Stop) => stopped = true,
+            (false, FailurePolicy::RollBack) => {
+                stopped = true;
+                roll_back(&injected);
+                injected.clear();
+            },
+        }
+        if updates.send(InjectionUpdate::Finished(Box::new(record))).is_err() {
+            break;
+        }
+    }
+}
+
+fn roll_back(injected: &[(&InjectionJob, u64)]) {
+    for (job, base) in injected.iter().rev() {
+        let _span = info_span!("injection", injection_id = job.id).entered();
+        let module = enum_modules_ex(&job.target)
+            .and_then(|modules| modules.into_iter().find(|module| module.base as u64 == *base));
+        match module {
+            Some(module) => match unload_module_ex(&job.target, &module) {
+                Some(()) => info!("Rolled back: ejected {} from {:#x}", module.name, base),
+                None => error!("Rollback failed: could not eject {} from {:#x}", module.name, base),
+            },
+            None => warn!("Rollback: {} is no longer loaded at {:#x}", job.dll_name(), base),
+        }
+    }
+}
+
+// Sleep for `duration`, giving up early if the batch is cancelled.
+fn wait_cancellable(duration: Duration, options: &InjectOptions) -> Result<(), InjectError> {
+    let deadline = Instant::now() + duration;
+    loop {
+        if options.cancel.load(Ordering::Relaxed) {
+            return Err(InjectError::Cancelled);
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(());
+        }
+        thread::sleep(remaining.min(WAIT_SLICE));
+    }
+}
+
+// The delay and the wait-for-module condition that run before the DLL itself
+// is injected.
+fn run_preconditions(
+    job: &InjectionJob,
+    options: &InjectOptions,
+    progress: &dyn Fn(&'static str),
+) -> Result<(), InjectError> {
+    if !job.delay.is_zero() {
+        progress("delaying");
+        info!("Waiting {:?} before injecting", job.delay);
+        wait_cancellable(job.delay, options)?;
+    }
+
+    if let Some(module) = job.wait_for_module.as_ref() {
+        progress("waiting for module");
+        info!("Waiting for {} to be loaded in the target", module);
+        let deadline = Instant::now() + options.module_wait_timeout;
+        while find_module_ex(&job.target, module).is_none() {
+            if Instant::now() >= deadline {
+                return Err(InjectError::ModuleWaitTimedOut {
+                    module: module.clone(),
+                    timeout: options.module_wait_timeout,
+                });
+            }
+            wait_cancellable(WAIT_SLICE, options)?;
+        }
+    }
+    Ok(())
+}
+
+// Compare the DLL on disk with the hash it was pinned to. `actual` is None if
+// the file could not be hashed.
+fn check_pinned_hash(job: &InjectionJob, actual: Option<&str>) -> Result<(), InjectError> {
+    let Some(pinned) = job.pinned.as_ref() else {
+        return Ok(());
+    };
+    if actual.is_some_and(|actual| actual.eq_ignore_ascii_case(&pinned.sha256)) {
+        return Ok(());
+    }
+    let err = InjectError::DllModified {
+        path: job.dll_path.clone(),
+        expected: pinned.sha256.clone(),
+        actual: actual.map(str::to_owned),
+    };
+    match pinned.policy {
+        PinPolicy::Warn => {
+            warn!("{}, injecting anyway", err);
+            Ok(())
+        },
+        PinPolicy::Refuse => Err(err),
+    }
+}
+
+fn check_signature(job: &InjectionJob, options: &InjectOptions) -> Result<(), InjectError> {
+    if options.signature_policy == SignaturePolicy::Allow {
+        return Ok(());
+    }
+    let check = verify_file(Path::new(&job.dll_path), &options.trust_store);
+    info!(signature = %check.summary(), "Checked the DLL signature");
+    if options.signature_policy.allows(&check) {
+        Ok(())
+    } else {
+        Err(InjectError::Signature { path: job.dll_path.clone(), reason: check.summary() })
+    }
+}
+
+// Hash the DLL and check it against its pin and the signature policy.
+fn check_integrity(
+    job: &InjectionJob,
+    options: &InjectOptions,
+) -> (Option<String>, Result<(), InjectError>) {
+    let sha256 = file_sha256(Path::new(&job.dll_path))
+        .map_err(|err| warn!("Failed to hash {}: {}", job.dll_path, err))
+        .ok();
+    let result =
+        check_pinned_hash(job, sha256.as_deref()).and_then(|_| check_signature(job, options));
+    (sha256, result)
+}
+
+fn run_job(
+    job: &InjectionJob,
+    options: &InjectOptions,
+    updates: &Sender<InjectionUpdate>,
+) -> InjectionRecord {
+    let dll_name = job.dll_name();
+    let _span =
+        info_span!("injection", injection_id = job.id, dll = %dll_name, pid = job.target.pid)
+            .entered();
+
+    let (mut dll_sha256, integrity) = check_integrity(job, options);
+    info!(
+        path = %job.dll_path,
+        sha256 = dll_sha256.as_deref().unwrap_or("unknown"),
+        "Injecting DLL {}",
+        dll_name
+    );
+
+    let progress = |step: &'static str| {
+        let _ = updates.send(InjectionUpdate::Progress { id: job.id, step });
+    };
+    let timestamp = Local::now();
+    let started_at = Instant::now();
+    let mark = allocation_mark();
+    let result = integrity
+        .and_then(|_| run_preconditions(job, options, &progress))
+        .and_then(|_| {
+            // The file may have been replaced during the delay or while
+            // waiting, so check it again right before it is loaded.
+            progress("verifying");
+            let (sha256, integrity) = check_integrity(job, options);
+            dll_sha256 = sha256;
+            integrity
+        })
+        .and_then(|_| match job.managed.as_ref() {
+            Some(call) => inject_managed(&job.target, &job.dll_path, call, options, &progress),
+            None => inject_dll_test_fix(&job.target, &job.dll_path, options, &progress),
+        });
+    let duration = started_at.elapsed();
+    match &result {
+        Ok(_) => info!(outcome = "success", ?duration, "Successfully injected {}", dll_name),
+        Err(e) => error!(
+            outcome = "failure",
+            stage = e.stage(),
+            error = %e,
+            "Failed to inject {}",
+            dll_name
+        ),
+    }
+
+    // Audit: whatever the outcome, none of our buffers should be left in the
+    // target unless the cleanup policy kept them for a thread still running.
+    let leftovers = allocations_since(process_key(&job.target), mark);
+    for allocation in &leftovers {
+        warn!(
+            address = format_args!("{:#x}", allocation.address),
+            size = allocation.size,
+            "Remote {} is still allocated in the target",
+            allocation.what
+        );
+    }
+
+    InjectionRecord {
+        id: job.id,
+        timestamp,
+        target_name: job.target.name.clone(),
+        target_pid: job.target.pid,
+        target_path: job.target.path.clone(),
+        target_arch: format!("{:?}", job.target.arch),
+        dll_path: job.dll_path.clone(),
+        dll_sha256,
+        technique: result.as_ref().ok().map(|module| module.technique),
+        duration_ms: duration.as_millis() as u64,
+        module_base: result.as_ref().ok().and_then(|module| module.module_base).map(|b| b as u64),
+        success: result.is_ok(),
+        error_stage: result.as_ref().err().map(|err| err.stage().to_owned()),
+        managed: job.managed.clone(),
+        return_value: result.as_ref().ok().and_then(|module| module.return_value),
+        leftovers: (!leftovers.is_empty()).then(|| {
+            leftovers.iter().map(RemoteAllocation::describe).collect::<Vec<_>>().join("; ")
+        }),
+        error: result.err().map(|err| err.to_string()),
+    }
+}
@@ -0,0 +1,458 @@
+use std::fmt;
+
+use iced_x86::IcedError;
+use iced_x86::code_asm::{
+    CodeAssembler, dword_ptr, eax, ecx, qword_ptr, r8, r9, r10, rax, rcx, rdx, rsp,
+};
+use libmem::Arch;
+use libmem::memory::read_memory_ex;
+use libmem::process::Process;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+use widestring::U16CString;
+
+use crate::utils::inject_error::InjectError;
+use crate::utils::processlist::{
+    InjectOptions, InjectedModule, InjectionTechnique, run_remote_thread, verify_module_loaded,
+};
+use crate::utils::remote_allocations::RemoteBuffer;
+use crate::utils::remote_exports::RemoteExportResolver;
+
+// Runs a static `int Method(string)` of a .NET Framework assembly in the
+// target: the stub loads mscoree.dll, starts the CLR through the hosting
+// API and calls ICLRRuntimeHost::ExecuteInDefaultAppDomain. The COM objects
+// are not released, the runtime stays loaded in the target either way.
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManagedCall {
+    // Namespace-qualified type name.
+    pub type_name: String,
+    pub method: String,
+    pub argument: String,
+    // e.g. "v4.0.30319"
+    pub runtime_version: String,
+}
+
+// What the stub reports back, at the start of the data block.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct ManagedResult {
+    // 1-based index into MANAGED_STEPS of the step that ran last.
+    step: u32,
+    // HRESULT, or GetLastError for the first two steps.
+    code: u32,
+    return_value: u32,
+    finished: u32,
+}
+
+// Steps before CLRCreateInstance report GetLastError, the rest an HRESULT.
+const WIN32_STEPS: u32 = 2;
+
+const MANAGED_STEPS: [&str; 7] = [
+    "LoadLibraryW(mscoree.dll)",
+    "GetProcAddress(CLRCreateInstance)",
+    "CLRCreateInstance",
+    "ICLRMetaHost::GetRuntime",
+    "ICLRRuntimeInfo::GetInterface",
+    "ICLRRuntimeHost::Start",
+    "ICLRRuntimeHost::ExecuteInDefaultAppDomain",
+];
+
+// Vtable slots, counting the three IUnknown methods.
+const GET_RUNTIME: i32 = 3;
+const GET_INTERFACE: i32 = 9;
+const START: i32 = 3;
+const EXECUTE_IN_DEFAULT_APP_DOMAIN: i32 = 11;
+
+const CLSID_CLR_META_HOST: [u8; 16] =
+    guid(0x9280188d, 0x0e8e, 0x4867, [0xb3, 0x0c, 0x7f, 0xa8, 0x38, 0x84, 0xe8, 0xde]);
+const IID_ICLR_META_HOST: [u8; 16] =
+    guid(0xd332db9e, 0xb9b3, 0x4125, [0x82, 0x07, 0xa1, 0x48, 0x84, 0xf5, 0x32, 0x16]);
+const IID_ICLR_RUNTIME_INFO: [u8; 16] =
+    guid(0xbd39d1d2, 0xba2f, 0x486a, [0x89, 0xb0, 0xb4, 0xb0, 0xcb, 0x46, 0x68, 0x91]);
+const CLSID_CLR_RUNTIME_HOST: [u8; 16] =
+    guid(0x90f1a06e, 0x7712, 0x4762, [0x86, 0xb5, 0x7a, 0x5e, 0xba, 0x6b, 0xdb, 0x02]);
+const IID_ICLR_RUNTIME_HOST: [u8; 16] =
+    guid(0x90f1a06c, 0x7712, 0x4762, [0x86, 0xb5, 0x7a, 0x5e, 0xba, 0x6b, 0xdb, 0x02]);
+
+const fn guid(data1: u32, data2: u16, data3: u16, data4: [u8; 8]) -> [u8; 16] {
+    let a = data1.to_le_bytes();
+    let b = data2.to_le_bytes();
+    let c = data3.to_le_bytes();
+    let d = data4;
+    [a[0], a[1], a[2], a[3], b[0], b[1], c[0], c[1], d[0], d[1], d[2], d[3], d[4], d[5], d[6], d[7]]
+}
+
+// Everything the stub reads or writes, laid out in one remote allocation.
+// Fields are offsets into `bytes`.
+struct DataBlock {
+    bytes: Vec<u8>,
+    meta_host: usize,
+    runtime_info: usize,
+    runtime_host: usize,
+    clsid_meta_host: usize,
+    iid_meta_host: usize,
+    iid_runtime_info: usize,
+    clsid_runtime_host: usize,
+    iid_runtime_host: usize,
+    mscoree: usize,
+    clr_create_instance: usize,
+    runtime_version: usize,
+    assembly_path: usize,
+    type_name: usize,
+    method: usize,
+    argument: usize,
+}
+
+impl DataBlock {
+    fn new(assembly_path: &str, call: &ManagedCall) -> Result<Self, InjectError> {
+        let mut bytes = vec![0u8; size_of::<ManagedResult>()];
+        let mut push = |data: &[u8]| {
+            bytes.resize(bytes.len().next_multiple_of(8), 0);
+            let offset = bytes.len();
+            bytes.extend_from_slice(data);
+            offset
+        };
+        let wide = |text: &str| -> Result<Vec<u8>, InjectError> {
+            let text = U16CString::from_str(text).map_err(|err| InjectError::InvalidDllPath {
+                path: assembly_path.to_owned(),
+                reason: err.to_string(),
+            })?;
+            Ok(text.as_slice_with_nul().iter().flat_map(|unit| unit.to_le_bytes()).collect())
+        };
+
+        let mut block = DataBlock {
+            meta_host: push(&[0; 8]),
+            runtime_info: push(&[0; 8]),
+            runtime_host: push(&[0; 8]),
+            clsid_meta_host: push(&CLSID_CLR_META_HOST),
+            iid_meta_host: push(&IID_ICLR_META_HOST),
+            iid_runtime_info: push(&IID_ICLR_RUNTIME_INFO),
+            clsid_runtime_host: push(&CLSID_CLR_RUNTIME_HOST),
+            iid_runtime_host: push(&IID_ICLR_RUNTIME_HOST),
+            mscoree: push(&wide("mscoree.dll")?),
+            clr_create_instance: push(b"CLRCreateInstance\0"),
+            runtime_version: push(&wide(&call.runtime_version)?),
+            assembly_path: push(&wide(assembly_path)?),
+            type_name: push(&wide(&call.type_name)?),
+            method: push(&wide(&call.method)?),
+            argument: push(&wide(&call.argument)?),
+            bytes: Vec::new(),
+        };
+        block.bytes = bytes;
+        Ok(block)
+    }
+}
+
+// Remote addresses the stub is built against.
+struct StubImports {
+    load_library_w: u64,
+    get_proc_address: u64,
+    get_last_error: u64,
+    // Base of the remote data block.
+    data: u64,
+}
+
+fn build_code_x64(imports: &StubImports, block: &DataBlock) -> Result<Vec<u8>, IcedError> {
+    let at = |offset: usize| imports.data + offset as u64;
+    let mut asm = CodeAssembler::new(64)?;
+    let mut fail_last_error = asm.create_label();
+    let mut fail_hresult = asm.create_label();
+    let mut done = asm.create_label();
+    let set_step = |asm: &mut CodeAssembler, step: u32| -> Result<(), IcedError> {
+        asm.mov(r10, imports.data)?;
+        asm.mov(dword_ptr(r10), step)
+    };
+
+    // Shadow space and two stack arguments, keeping rsp 16-byte aligned.
+    asm.sub(rsp, 0x38)?;
+
+    set_step(&mut asm, 1)?;
+    asm.mov(rcx, at(block.mscoree))?;
+    asm.mov(rax, imports.load_library_w)?;
+    asm.call(rax)?;
+    asm.test(rax, rax)?;
+    asm.jz(fail_last_error)?;
+
+    set_step(&mut asm, 2)?;
+    asm.mov(rcx, rax)?;
+    asm.mov(rdx, at(block.clr_create_instance))?;
+    asm.mov(rax, imports.get_proc_address)?;
+    asm.call(rax)?;
+    asm.test(rax, rax)?;
+    asm.jz(fail_last_error)?;
+
+    set_step(&mut asm, 3)?;
+    asm.mov(rcx, at(block.clsid_meta_host))?;
+    asm.mov(rdx, at(block.iid_meta_host))?;
+    asm.mov(r8, at(block.meta_host))?;
+    asm.call(rax)?;
+    asm.test(eax, eax)?;
+    asm.js(fail_hresult)?;
+
+    set_step(&mut asm, 4)?;
+    asm.mov(rax, at(block.meta_host))?;
+    asm.mov(rcx, qword_ptr(rax))?;
+    asm.mov(rdx, at(block.runtime_version))?;
+    asm.mov(r8, at(block.iid_runtime_info))?;
+    asm.mov(r9, at(block.runtime_info))?;
+    asm.mov(rax, qword_ptr(rcx))?;
+    asm.call(qword_ptr(rax + GET_RUNTIME * 8))?;
+    asm.test(eax, eax)?;
+    asm.js(fail_hresult)?;
+
+    set_step(&mut asm, 5)?;
+    asm.mov(rax, at(block.runtime_info))?;
+    asm.mov(rcx, qword_ptr(rax))?;
+    asm.mov(rdx, at(block.clsid_runtime_host))?;
+    asm.mov(r8, at(block.iid_runtime_host))?;
+    asm.mov(r9, at(block.runtime_host))?;
+    asm.mov(rax, qword_ptr(rcx))?;
+    asm.call(qword_ptr(rax + GET_INTERFACE * 8))?;
+    asm.test(eax, eax)?;
+    asm.js(fail_hresult)?;
+
+    set_step(&mut asm, 6)?;
+    asm.mov(rax, at(block.runtime_host))?;
+    asm.mov(rcx, qword_ptr(rax))?;
+    asm.mov(rax, qword_ptr(rcx))?;
+    asm.call(qword_ptr(rax + START * 8))?;
+    asm.test(eax, eax)?;
+    asm.js(fail_hresult)?;
+
+    set_step(&mut asm, 7)?;
+    asm.mov(rax, at(block.argument))?;
+    asm.mov(qword_ptr(rsp + 0x20), rax)?;
+    asm.mov(rax, imports.data + 8)?; // ManagedResult::return_value
+    asm.mov(qword_ptr(rsp + 0x28), rax)?;
+    asm.mov(rax, at(block.runtime_host))?;
+    asm.mov(rcx, qword_ptr(rax))?;
+    asm.mov(rdx, at(block.assembly_path))?;
+    asm.mov(r8, at(block.type_name))?;
+    asm.mov(r9, at(block.method))?;
+    asm.mov(rax, qword_ptr(rcx))?;
+    asm.call(qword_ptr(rax + EXECUTE_IN_DEFAULT_APP_DOMAIN * 8))?;
+    asm.test(eax, eax)?;
+    asm.js(fail_hresult)?;
+
+    asm.mov(r10, imports.data + 12)?; // ManagedResult::finished
+    asm.mov(dword_ptr(r10), 1u32)?;
+    asm.jmp(done)?;
+
+    asm.set_label(&mut fail_last_error)?;
+    asm.mov(rax, imports.get_last_error)?;
+    asm.call(rax)?;
+    asm.set_label(&mut fail_hresult)?;
+    asm.mov(r10, imports.data + 4)?; // ManagedResult::code
+    asm.mov(dword_ptr(r10), eax)?;
+
+    asm.set_label(&mut done)?;
+    asm.add(rsp, 0x38)?;
+    asm.ret()?;
+    asm.assemble(0x1234_5678)
+}
+
+fn build_code_x86(imports: &StubImports, block: &DataBlock) -> Result<Vec<u8>, IcedError> {
+    let at = |offset: usize| (imports.data + offset as u64) as u32;
+    let data = imports.data as u32;
+    let mut asm = CodeAssembler::new(32)?;
+    let mut fail_last_error = asm.create_label();
+    let mut fail_hresult = asm.create_label();
+    let mut done = asm.create_label();
+
+    // Everything is stdcall, arguments are pushed right to left.
+    asm.mov(dword_ptr(data), 1u32)?;
+    asm.push(at(block.mscoree))?;
+    asm.mov(eax, imports.load_library_w as u32)?;
+    asm.call(eax)?;
+    asm.test(eax, eax)?;
+    asm.jz(fail_last_error)?;
+
+    asm.mov(dword_ptr(data), 2u32)?;
+    asm.push(at(block.clr_create_instance))?;
+    asm.push(eax)?;
+    asm.mov(eax, imports.get_proc_address as u32)?;
+    asm.call(eax)?;
+    asm.test(eax, eax)?;
+    asm.jz(fail_last_error)?;
+
+    asm.mov(dword_ptr(data), 3u32)?;
+    asm.push(at(block.meta_host))?;
+    asm.push(at(block.iid_meta_host))?;
+    asm.push(at(block.clsid_meta_host))?;
+    asm.call(eax)?;
+    asm.test(eax, eax)?;
+    asm.js(fail_hresult)?;
+
+    asm.mov(dword_ptr(data), 4u32)?;
+    asm.mov(ecx, dword_ptr(at(block.meta_host)))?;
+    asm.push(at(block.runtime_info))?;
+    asm.push(at(block.iid_runtime_info))?;
+    asm.push(at(block.runtime_version))?;
+    asm.push(ecx)?;
+    asm.mov(eax, dword_ptr(ecx))?;
+    asm.call(dword_ptr(eax + GET_RUNTIME * 4))?;
+    asm.test(eax, eax)?;
+    asm.js(fail_hresult)?;
+
+    asm.mov(dword_ptr(data), 5u32)?;
+    asm.mov(ecx, dword_ptr(at(block.runtime_info)))?;
+    asm.push(at(block.runtime_host))?;
+    asm.push(at(block.iid_runtime_host))?;
+    asm.push(at(block.clsid_runtime_host))?;
+    asm.push(ecx)?;
+    asm.mov(eax, dword_ptr(ecx))?;
+    asm.call(dword_ptr(eax + GET_INTERFACE * 4))?;
+    asm.test(eax, eax)?;
+    asm.js(fail_hresult)?;
+
+    asm.mov(dword_ptr(data), 6u32)?;
+    asm.mov(ecx, dword_ptr(at(block.runtime_host)))?;
+    asm.push(ecx)?;
+    asm.mov(eax, dword_ptr(ecx))?;
+    asm.call(dword_ptr(eax + START * 4))?;
+    asm.test(eax, eax)?;
+    asm.js(fail_hresult)?;
+
+    asm.mov(dword_ptr(data), 7u32)?;
+    asm.mov(ecx, dword_ptr(at(block.runtime_host)))?;
+    asm.push(data + 8)?; // ManagedResult::return_value
+    asm.push(at(block.argument))?;
+    asm.push(at(block.method))?;
+    asm.push(at(block.type_name))?;
+    asm.push(at(block.assembly_path))?;
+    asm.push(ecx)?;
+    asm.mov(eax, dword_ptr(ecx))?;
+    asm.call(dword_ptr(eax + EXECUTE_IN_DEFAULT_APP_DOMAIN * 4))?;
+    asm.test(eax, eax)?;
+    asm.js(fail_hresult)?;
+
+    asm.mov(dword_ptr(data + 12), 1u32)?; // ManagedResult::finished
+    asm.jmp(done)?;
+
+    asm.set_label(&mut fail_last_error)?;
+    asm.mov(eax, imports.get_last_error as u32)?;
+    asm.call(eax)?;
+    asm.set_label(&mut fail_hresult)?;
+    asm.mov(dword_ptr(data + 4), eax)?; // ManagedResult::code
+
+    asm.set_label(&mut done)?;
+    asm.ret_1(4)?; // Thread parameter, callee cleanup
+    asm.assemble(0x1234_5678)
+}
+
+// A few HRESULTs ExecuteInDefaultAppDomain commonly fails with.
+fn hresult_hint(code: u32) -> Option<&'static str> {
+    match code {
+        0x8007_0002 => Some("assembly not found"),
+        0x8013_1522 => Some("type not found"),
+        0x8013_1513 => Some("method not found or has the wrong signature"),
+        0x8013_1700 => Some("runtime version not installed"),
+        _ => None,
+    }
+}
+
+pub fn inject_managed(
+    process: &Process,
+    assembly_path: &str,
+    call: &ManagedCall,
+    options: &InjectOptions,
+    progress: &dyn Fn(&'static str),
+) -> Result<InjectedModule, InjectError> {
+    if !matches!(process.arch, Arch::X64 | Arch::X86) {
+        return Err(InjectError::UnsupportedArchitecture { arch: format!("{:?}", process.arch) });
+    }
+    info!(
+        runtime = %call.runtime_version,
+        method = format_args!("{}::{}", call.type_name, call.method),
+        "Bootstrapping the CLR in the target"
+    );
+
+    progress("resolving CLR bootstrap imports");
+    let mut exports = RemoteExportResolver::new(process);
+    let load_library_w = exports.resolve("KERNEL32.DLL", "LoadLibraryW")?;
+    let get_proc_address = exports.resolve("KERNEL32.DLL", "GetProcAddress")?;
+    let get_last_error = exports.resolve("KERNEL32.DLL", "GetLastError")?;
+
+    // Both buffers are freed on every return from here on.
+    progress("writing CLR bootstrap data");
+    let block = DataBlock::new(assembly_path, call)?;
+    let remote_data =
+        RemoteBuffer::with_value(process, block.bytes.as_slice(), "CLR bootstrap data")?;
+
+    let imports = StubImports {
+        load_library_w: load_library_w as u64,
+        get_proc_address: get_proc_address as u64,
+        get_last_error: get_last_error as u64,
+        data: remote_data.address() as u64,
+    };
+    let shellcode = match process.arch {
+        Arch::X64 => build_code_x64(&imports, &block),
+        _ => build_code_x86(&imports, &block),
+    }
+    .map_err(|err| InjectError::BuildShellcode { reason: err.to_string() })?;
+
+    progress("writing CLR bootstrap stub");
+    let remote_code = RemoteBuffer::with_code(process, &shellcode, "CLR bootstrap stub")?;
+    debug!(
+        code = format_args!("{:#x}", remote_code.address()),
+        data = format_args!("{:#x}", remote_data.address()),
+        len = shellcode.len(),
+        "Wrote CLR bootstrap stub"
+    );
+
+    // Starting the runtime can take a while, the normal remote thread timeout
+    // and cleanup policy apply.
+    run_remote_thread(process, remote_code.address(), options, progress, &[
+        &remote_data,
+        &remote_code,
+    ])?;
+
+    let result = read_memory_ex::<ManagedResult>(process, remote_data.address());
+    drop(remote_data);
+    drop(remote_code);
+    let result = result.ok_or(InjectError::Managed { step: "read result", code: None })?;
+    if result.finished == 0 {
+        let step = (result.step as usize)
+            .checked_sub(1)
+            .and_then(|index| MANAGED_STEPS.get(index))
+            .copied()
+            .unwrap_or("bootstrap");
+        let code = if result.step <= WIN32_STEPS {
+            ManagedCode::Win32(result.code)
+        } else {
+            ManagedCode::HResult(result.code)
+        };
+        return Err(InjectError::Managed { step, code: Some(code) });
+    }
+    let return_value = result.return_value as i32;
+    info!(return_value, "{}::{} returned", call.type_name, call.method);
+
+    // .NET Framework maps the assembly as an image, so it is usually among the
+    // modules; it does not have to be.
+    let module_base = verify_module_loaded(process, assembly_path).ok().map(|module| module.base);
+    Ok(InjectedModule {
+        technique: InjectionTechnique::Managed,
+        module_base,
+        return_value: Some(return_value),
+    })
+}
+
+// What a failed step of the bootstrap reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManagedCode {
+    Win32(u32),
+    HResult(u32),
+}
+
+impl fmt::Display for ManagedCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ManagedCode::Win32(code) => write!(f, "error {} ({:#x})", code, code),
+            ManagedCode::HResult(code) => match hresult_hint(code) {
+                Some(hint) => write!(f, "{:#010x}, {}", code, hint),
+                None => write!(f, "{:#010x}", code),
+            },
+        }
+    }
+}
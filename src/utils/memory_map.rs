@@ -0,0 +1,205 @@
+use libmem::module::enum_modules_ex;
+use libmem::process::Process;
+use libmem::segment::enum_segments_ex;
+use libmem::{Module, Prot};
+
+use crate::utils::process_watcher::process_key;
+use crate::utils::remote_allocations::{RemoteAllocation, outstanding_allocations};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    // A mapped executable image (EXE or DLL).
+    Image,
+    // A mapped file or section that is not an image.
+    Mapped,
+    // VirtualAlloc'd or heap memory; where shellcode lives.
+    Private,
+    Unknown,
+}
+
+impl RegionKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            RegionKind::Image => "Image",
+            RegionKind::Mapped => "Mapped",
+            RegionKind::Private => "Private",
+            RegionKind::Unknown => "?",
+        }
+    }
+}
+
+pub fn protection_label(prot: Prot) -> &'static str {
+    match prot {
+        Prot::None => "no access",
+        Prot::X => "X",
+        Prot::R => "R",
+        Prot::W => "W",
+        Prot::XR => "RX",
+        Prot::XW => "WX",
+        Prot::RW => "RW",
+        Prot::XRW => "RWX",
+    }
+}
+
+pub fn is_executable(prot: Prot) -> bool {
+    matches!(prot, Prot::X | Prot::XR | Prot::XW | Prot::XRW)
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryRegion {
+    pub base: usize,
+    pub size: usize,
+    pub prot: Prot,
+    pub kind: RegionKind,
+    // The module or file mapped here, if any.
+    pub file: Option<String>,
+    // Buffers our injections allocated in this region and did not free.
+    pub allocations: Vec<RemoteAllocation>,
+}
+
+impl MemoryRegion {
+    pub fn end(&self) -> usize {
+        self.base + self.size
+    }
+
+    pub fn contains(&self, address: usize) -> bool {
+        (self.base..self.end()).contains(&address)
+    }
+
+    // Executable memory that no image backs: injected code, JIT output or
+    // unpacked payloads.
+    pub fn is_executable_private(&self) -> bool {
+        self.kind == RegionKind::Private && is_executable(self.prot)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryMap {
+    pub regions: Vec<MemoryRegion>,
+    // Allocations still registered for the target that no region contains
+    // any more, e.g. because the target freed them itself.
+    pub unmatched: Vec<RemoteAllocation>,
+}
+
+impl MemoryMap {
+    pub fn collect(process: &Process) -> Result<Self, String> {
+        let segments = enum_segments_ex(process).ok_or("failed to enumerate the memory regions")?;
+        let modules = enum_modules_ex(process).unwrap_or_default();
+        let mut regions: Vec<MemoryRegion> = segments
+            .into_iter()
+            .map(|segment| MemoryRegion {
+                base: segment.base,
+                size: segment.size,
+                prot: segment.prot,
+                kind: RegionKind::Unknown,
+                file: None,
+                allocations: Vec::new(),
+            })
+            .collect();
+        describe_regions(process.pid, &mut regions);
+        attribute_modules(&mut regions, &modules);
+
+        let mut unmatched = Vec::new();
+        for allocation in outstanding_allocations(process_key(process)) {
+            match regions.iter_mut().find(|region| region.contains(allocation.address)) {
+                Some(region) => region.allocations.push(allocation),
+                None => unmatched.push(allocation),
+            }
+        }
+        Ok(MemoryMap { regions, unmatched })
+    }
+
+    pub fn outstanding(&self) -> impl Iterator<Item = &RemoteAllocation> {
+        self.regions.iter().flat_map(|region| region.allocations.iter())
+    }
+}
+
+// Module paths are friendlier than the device paths the OS reports.
+fn attribute_modules(regions: &mut [MemoryRegion], modules: &[Module]) {
+    for region in regions {
+        if let Some(module) =
+            modules.iter().find(|module| (module.base..module.end).contains(&region.base))
+        {
+            if matches!(region.kind, RegionKind::Mapped | RegionKind::Unknown) {
+                region.kind = RegionKind::Image;
+            }
+            region.file = Some(module.path.clone());
+        }
+    }
+}
+
+#[cfg(windows)]
+fn describe_regions(pid: u32, regions: &mut [MemoryRegion]) {
+    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::System::Memory::{
+        MEM_IMAGE, MEM_MAPPED, MEM_PRIVATE, MEMORY_BASIC_INFORMATION, VirtualQueryEx,
+    };
+    use windows::Win32::System::ProcessStatus::K32GetMappedFileNameW;
+    use winsafe::prelude::*;
+    use winsafe::{HPROCESS, co};
+
+    let Ok(process) =
+        HPROCESS::OpenProcess(co::PROCESS::QUERY_INFORMATION | co::PROCESS::VM_READ, false, pid)
+    else {
+        return;
+    };
+    let handle = HANDLE(process.ptr() as isize);
+    for region in regions {
+        let mut info = MEMORY_BASIC_INFORMATION::default();
+        let written = unsafe {
+            VirtualQueryEx(
+                handle,
+                Some(region.base as *const _),
+                &mut info,
+                size_of::<MEMORY_BASIC_INFORMATION>(),
+            )
+        };
+        if written == 0 {
+            continue;
+        }
+        region.kind = match info.Type {
+            MEM_IMAGE => RegionKind::Image,
+            MEM_MAPPED => RegionKind::Mapped,
+            MEM_PRIVATE => RegionKind::Private,
+            _ => RegionKind::Unknown,
+        };
+        if region.kind != RegionKind::Private {
+            let mut buffer = [0u16; 1024];
+            let length =
+                unsafe { K32GetMappedFileNameW(handle, region.base as *const _, &mut buffer) };
+            if length > 0 {
+                region.file = Some(String::from_utf16_lossy(&buffer[..length as usize]));
+            }
+        }
+    }
+}
+
+// /proc/<pid>/maps: "start-end perms offset dev inode path".
+#[cfg(unix)]
+fn describe_regions(pid: u32, regions: &mut [MemoryRegion]) {
+    let Ok(maps) = std::fs::read_to_string(format!("/proc/{}/maps", pid)) else {
+        return;
+    };
+    for line in maps.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [range, perms, _, _, inode, path @ ..] = fields.as_slice() else {
+            continue;
+        };
+        let Some(start) =
+            range.split_once('-').and_then(|(start, _)| usize::from_str_radix(start, 16).ok())
+        else {
+            continue;
+        };
+        let Some(region) = regions.iter_mut().find(|region| region.contains(start)) else {
+            continue;
+        };
+        region.kind = if *inode != "0" || perms.ends_with('s') {
+            RegionKind::Mapped
+        } else {
+            RegionKind::Private
+        };
+        if !path.is_empty() {
+            region.file = Some(path.join(" "));
+        }
+    }
+}
@@ -1 +1,22 @@
+pub mod access_check;
+pub mod address_expression;
+pub mod authenticode;
+pub mod clr_metadata;
+pub mod data_dir;
+pub mod debug_symbols;
+pub mod elf_info;
+pub mod hook_scan;
+pub mod inject_error;
+pub mod injection_history;
+pub mod injection_worker;
+pub mod managed_injection;
+pub mod memory_map;
+pub mod pe_exports;
+pub mod pe_machine;
+pub mod process_details;
+pub mod process_watcher;
 pub mod processlist;
+pub mod remote_allocations;
+pub mod remote_exports;
+pub mod target_process;
+pub mod text_assembler;
@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::{fmt, fs};
+
+use pelite::pe64::exports::Export;
+use pelite::{FileMap, PeFile, Wrap};
+use windows::Win32::System::Diagnostics::Debug::UnDecorateSymbolName;
+
+// Forwarders can chain (KERNEL32 → KERNELBASE → NTDLL); give up on loops.
+pub const MAX_FORWARDER_DEPTH: usize = 8;
+
+// An export looked up by name, or by ordinal for "#123" forwarders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExportSymbol {
+    Name(String),
+    Ordinal(u16),
+}
+
+impl fmt::Display for ExportSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportSymbol::Name(name) => write!(f, "{}", name),
+            ExportSymbol::Ordinal(ordinal) => write!(f, "#{}", ordinal),
+        }
+    }
+}
+
+pub fn lookup_export<'a>(pe: PeFile<'a>, symbol: &ExportSymbol) -> pelite::Result<Export<'a>> {
+    match symbol {
+        ExportSymbol::Name(name) => pe.get_export_by_name(name.as_str()),
+        ExportSymbol::Ordinal(ordinal) => pe.get_export_by_ordinal(*ordinal),
+    }
+}
+
+// "NTDLL.RtlGetLastWin32Error" → ("NTDLL.dll", RtlGetLastWin32Error). The
+// module never has an extension in a forwarder, the loader appends .dll.
+pub fn parse_forwarder(forwarder: &str) -> Option<(String, ExportSymbol)> {
+    let (module, symbol) = forwarder.rsplit_once('.')?;
+    let symbol = match symbol.strip_prefix('#') {
+        Some(ordinal) => ExportSymbol::Ordinal(ordinal.parse().ok()?),
+        None => ExportSymbol::Name(symbol.to_owned()),
+    };
+    Some((format!("{}.dll", module), symbol))
+}
+
+// API sets are virtual modules the loader maps through the ApiSetSchema. Only
+// the contract families kernel32 and the CRT forward to are mapped here, to
+// the DLLs that host them on Windows 8 and later.
+pub fn api_set_host(module: &str) -> Option<&'static str> {
+    let module = module.to_ascii_lowercase();
+    if module.starts_with("api-ms-win-core-") {
+        Some("KERNELBASE.dll")
+    } else if module.starts_with("api-ms-win-crt-") {
+        Some("ucrtbase.dll")
+    } else {
+        None
+    }
+}
+
+// The end of a forwarder chain.
+#[derive(Debug, Clone)]
+pub struct ResolvedExport {
+    pub module: String,
+    pub rva: u32,
+    // Every forwarder string that was followed, in order.
+    pub chain: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Forwarder {
+    // As written in the export table, e.g. "NTDLL.RtlGetLastWin32Error".
+    pub target: String,
+    pub resolved: Result<ResolvedExport, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportEntry {
+    pub ordinal: u16,
+    pub name: Option<String>,
+    // For forwarded exports, the RVA of the forwarder string.
+    pub rva: u32,
+    pub forwarder: Option<Forwarder>,
+    pub demangled: Option<String>,
+}
+
+impl ExportEntry {
+    // Tab separated, for copying into a spreadsheet or a bug report.
+    pub fn to_line(&self) -> String {
+        let forwarded =
+            self.forwarder.as_ref().map_or(String::new(), |forwarder| match &forwarder.resolved {
+                Ok(resolved) => {
+                    format!("{} → {}+{:#x}", forwarder.target, resolved.module, resolved.rva)
+                },
+                Err(err) => format!("{} ({})", forwarder.target, err),
+            });
+        format!(
+            "{}\t{}\t{:#x}\t{}\t{}",
+            self.ordinal,
+            self.name.as_deref().unwrap_or(""),
+            self.rva,
+            forwarded,
+            self.demangled.as_deref().unwrap_or("")
+        )
+    }
+
+    // Case-insensitive match against the names, the ordinal and the forwarder.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        query.is_empty()
+            || [
+                self.name.as_deref(),
+                self.demangled.as_deref(),
+                self.forwarder.as_ref().map(|forwarder| forwarder.target.as_str()),
+            ]
+            .iter()
+            .flatten()
+            .any(|field| field.to_lowercase().contains(&query))
+            || self.ordinal.to_string() == query
+            || format!("{:#x}", self.rva) == query
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ModuleExports {
+    // The name the module was linked as, from the export directory.
+    pub dll_name: Option<String>,
+    pub entries: Vec<ExportEntry>,
+}
+
+// Follows forwarders through the module files on disk. `locate` maps a
+// module file name to its path, and the files are read once per resolver.
+pub struct ForwarderResolver<'a> {
+    locate: &'a dyn Fn(&str) -> Option<PathBuf>,
+    files: HashMap<String, Result<Vec<u8>, String>>,
+}
+
+impl<'a> ForwarderResolver<'a> {
+    pub fn new(locate: &'a dyn Fn(&str) -> Option<PathBuf>) -> Self {
+        ForwarderResolver { locate, files: HashMap::new() }
+    }
+
+    fn file(&mut self, module: &str) -> Result<&[u8], String> {
+        let locate = self.locate;
+        self.files
+            .entry(module.to_lowercase())
+            .or_insert_with(|| {
+                let path = locate(module).ok_or_else(|| format!("{} not found", module))?;
+                fs::read(&path).map_err(|err| format!("{}: {}", path.display(), err))
+            })
+            .as_deref()
+            .map_err(Clone::clone)
+    }
+
+    pub fn resolve(&mut self, forwarder: &str) -> Result<ResolvedExport, String> {
+        let mut chain = vec![forwarder.to_owned()];
+        for _ in 0..MAX_FORWARDER_DEPTH {
+            let current = chain.last().cloned().unwrap_or_default();
+            let (module, symbol) = parse_forwarder(&current)
+                .ok_or_else(|| format!("malformed forwarder {}", current))?;
+            let module = api_set_host(&module).map_or(module, str::to_owned);
+            let bytes = self.file(&module)?;
+            let pe = PeFile::from_bytes(bytes).map_err(|err| format!("{}: {}", module, err))?;
+            match lookup_export(pe, &symbol) {
+                Ok(Export::Symbol(&rva)) => return Ok(ResolvedExport { module, rva, chain }),
+                Ok(Export::Forward(next)) => chain.push(next.to_string()),
+                Err(err) => return Err(format!("{}!{}: {}", module, symbol, err)),
+            }
+        }
+        Err(format!("more than {} forwarders", MAX_FORWARDER_DEPTH))
+    }
+}
+
+// Where Windows looks for a forwarded-to module: next to the forwarding one,
+// then in the system directory matching its bitness.
+pub fn system_module_locator(module_path: &Path) -> impl Fn(&str) -> Option<PathBuf> {
+    let directory = module_path.parent().map(Path::to_path_buf);
+    let is_64 = FileMap::open(module_path)
+        .ok()
+        .and_then(|file_map| {
+            PeFile::from_bytes(&file_map).ok().map(|pe| matches!(pe, Wrap::T64(_)))
+        })
+        .unwrap_or(true);
+    let system = std::env::var_os("SystemRoot").map(PathBuf::from).map(|root| {
+        root.join(if is_64 || !cfg!(target_pointer_width = "64") { "System32" } else { "SysWOW64" })
+    });
+    move |module: &str| {
+        [directory.as_ref(), system.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(|directory| directory.join(module))
+            .find(|path| path.is_file())
+    }
+}
+
+pub fn read_exports(
+    path: &Path,
+    resolver: &mut ForwarderResolver,
+) -> Result<ModuleExports, String> {
+    let bytes = fs::read(path).map_err(|err| err.to_string())?;
+    let pe = PeFile::from_bytes(&bytes).map_err(|err| err.to_string())?;
+    let exports = match pe.exports() {
+        Ok(exports) => exports,
+        Err(pelite::Error::Null) => {
+            return Ok(ModuleExports { dll_name: None, entries: Vec::new() });
+        },
+        Err(err) => return Err(err.to_string()),
+    };
+    let by = exports.by().map_err(|err| err.to_string())?;
+
+    let mut names: HashMap<usize, Vec<String>> = HashMap::new();
+    for (name, index) in by.iter_name_indices() {
+        if let Ok(name) = name {
+            names.entry(index).or_default().push(name.to_string());
+        }
+    }
+
+    let mut entries = Vec::new();
+    for (index, &rva) in by.functions().iter().enumerate() {
+        // Unused slots between ordinals.
+        if rva == 0 {
+            continue;
+        }
+        let forwarder = match by.index(index) {
+            Ok(Export::Forward(target)) => {
+                let target = target.to_string();
+                Some(Forwarder { resolved: resolver.resolve(&target), target })
+            },
+            _ => None,
+        };
+        let ordinal = exports.ordinal_base().wrapping_add(index as u16);
+        let entry_names =
+            names.remove(&index).map_or(vec![None], |names| names.into_iter().map(Some).collect());
+        for name in entry_names {
+            entries.push(ExportEntry {
+                ordinal,
+                demangled: name.as_deref().and_then(demangle),
+                name,
+                rva,
+                forwarder: forwarder.clone(),
+            });
+        }
+    }
+    Ok(ModuleExports { dll_name: exports.dll_name().ok().map(|name| name.to_string()), entries })
+}
+
+// Readable form of an MSVC, Itanium (MinGW, Clang) or Rust mangled name.
+pub fn demangle(name: &str) -> Option<String> {
+    if name.starts_with('?') {
+        let name = std::ffi::CString::new(name).ok()?;
+        let mut buffer = [0u8; 1024];
+        // UNDNAME_COMPLETE
+        let length = unsafe {
+            UnDecorateSymbolName(windows::core::PCSTR(name.as_ptr() as *const u8), &mut buffer, 0)
+        };
+        return (length > 0)
+            .then(|| String::from_utf8_lossy(&buffer[..length as usize]).into_owned());
+    }
+    if let Ok(demangled) = rustc_demangle::try_demangle(name) {
+        return Some(format!("{:#}", demangled));
+    }
+    if name.starts_with("_Z") {
+        let symbol = cpp_demangle::Symbol::new(name).ok()?;
+        return symbol.demangle(&Default::default()).ok();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forwarders() {
+        let name = |name: &str| ExportSymbol::Name(name.to_owned());
+        let table = [
+            ("NTDLL.RtlGetLastWin32Error", Some(("NTDLL.dll", name("RtlGetLastWin32Error")))),
+            ("NTDLL.#12", Some(("NTDLL.dll", ExportSymbol::Ordinal(12)))),
+            ("msvcrt.#65535", Some(("msvcrt.dll", ExportSymbol::Ordinal(65535)))),
+            (
+                "api-ms-win-core-synch-l1-2-0.WaitOnAddress",
+                Some(("api-ms-win-core-synch-l1-2-0.dll", name("WaitOnAddress"))),
+            ),
+            ("NTDLL.#65536", None),
+            ("NTDLL.#-1", None),
+            ("NTDLL.#", None),
+            ("RtlGetLastWin32Error", None),
+        ];
+        for (forwarder, expected) in table {
+            let expected = expected.map(|(module, symbol)| (module.to_owned(), symbol));
+            assert_eq!(parse_forwarder(forwarder), expected, "{}", forwarder);
+        }
+        assert_eq!(ExportSymbol::Ordinal(12).to_string(), "#12");
+    }
+
+    #[test]
+    fn api_set_hosts() {
+        let table = [
+            ("api-ms-win-core-synch-l1-2-0.dll", Some("KERNELBASE.dll")),
+            ("API-MS-Win-Core-ProcessThreads-L1-1-2.dll", Some("KERNELBASE.dll")),
+            ("api-ms-win-crt-runtime-l1-1-0.dll", Some("ucrtbase.dll")),
+            ("ext-ms-win-ntuser-window-l1-1-0.dll", None),
+            ("kernel32.dll", None),
+        ];
+        for (module, host) in table {
+            assert_eq!(api_set_host(module), host, "{}", module);
+        }
+    }
+
+    #[test]
+    fn demangles_itanium_and_rust() {
+        let table = [
+            ("_Z3addii", Some("add(int, int)")),
+            ("_ZN6widget4drawEv", Some("widget::draw()")),
+            ("_ZN4core3fmt5write17h0123456789abcdefE", Some("core::fmt::write")),
+            ("_RNvCs1234_7mycrate3foo", Some("mycrate::foo")),
+            ("CreateFileW", None),
+            ("_Z", None),
+        ];
+        for (name, demangled) in table {
+            assert_eq!(demangle(name).as_deref(), demangled, "{}", name);
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn demangles_msvc() {
+        assert_eq!(demangle("?Fn@@YAXH@Z").as_deref(), Some("void __cdecl Fn(int)"));
+        assert_eq!(demangle("?"), None);
+    }
+
+    #[test]
+    fn matches_names_ordinals_and_forwarders() {
+        let forwarded = ExportEntry {
+            ordinal: 42,
+            name: Some("CreateFileW".to_owned()),
+            rva: 0x1234,
+            forwarder: Some(Forwarder {
+                target: "KERNELBASE.CreateFileW".to_owned(),
+                resolved: Err("not found".to_owned()),
+            }),
+            demangled: None,
+        };
+        let mangled = ExportEntry {
+            ordinal: 7,
+            name: Some("_Z3addii".to_owned()),
+            rva: 0x2000,
+            forwarder: None,
+            demangled: Some("add(int, int)".to_owned()),
+        };
+        let table = [
+            (&forwarded, "", true),
+            (&forwarded, "createfile", true),
+            (&forwarded, "KERNELBASE", true),
+            (&forwarded, "42", true),
+            (&forwarded, "4", false),
+            (&forwarded, "0x1234", true),
+            (&forwarded, "0X1234", true),
+            (&forwarded, "1234", false),
+            (&mangled, "ADD(", true),
+            (&mangled, "_z3", true),
+            (&mangled, "kernelbase", false),
+        ];
+        for (entry, query, expected) in table {
+            assert_eq!(entry.matches(query), expected, "{:?} in {:?}", query, entry.name);
+        }
+    }
+}
@@ -0,0 +1,369 @@
+use std::fmt;
+
+use pelite::PeFile;
+use pelite::image::{IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR, IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG};
+
+// IMAGE_FILE_HEADER.Machine values, from the PE format specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum PeMachine {
+    Unknown = 0x0000,
+    I386 = 0x014c,
+    R3000 = 0x0162,
+    R4000 = 0x0166,
+    R10000 = 0x0168,
+    WceMipsV2 = 0x0169,
+    Alpha = 0x0184,
+    Sh3 = 0x01a2,
+    Sh3Dsp = 0x01a3,
+    Sh3E = 0x01a4,
+    Sh4 = 0x01a6,
+    Sh5 = 0x01a8,
+    Arm = 0x01c0,
+    Thumb = 0x01c2,
+    ArmNt = 0x01c4,
+    Am33 = 0x01d3,
+    PowerPc = 0x01f0,
+    PowerPcFp = 0x01f1,
+    Ia64 = 0x0200,
+    Mips16 = 0x0266,
+    Alpha64 = 0x0284,
+    MipsFpu = 0x0366,
+    MipsFpu16 = 0x0466,
+    TriCore = 0x0520,
+    Cef = 0x0cef,
+    RiscV32 = 0x5032,
+    RiscV64 = 0x5064,
+    RiscV128 = 0x5128,
+    LoongArch32 = 0x6232,
+    LoongArch64 = 0x6264,
+    Ebc = 0x0ebc,
+    Amd64 = 0x8664,
+    M32R = 0x9041,
+    Arm64Ec = 0xa641,
+    Arm64X = 0xa64e,
+    Arm64 = 0xaa64,
+    Cee = 0xc0ee,
+}
+
+impl PeMachine {
+    pub const ALL: [PeMachine; 37] = [
+        PeMachine::Unknown,
+        PeMachine::I386,
+        PeMachine::R3000,
+        PeMachine::R4000,
+        PeMachine::R10000,
+        PeMachine::WceMipsV2,
+        PeMachine::Alpha,
+        PeMachine::Sh3,
+        PeMachine::Sh3Dsp,
+        PeMachine::Sh3E,
+        PeMachine::Sh4,
+        PeMachine::Sh5,
+        PeMachine::Arm,
+        PeMachine::Thumb,
+        PeMachine::ArmNt,
+        PeMachine::Am33,
+        PeMachine::PowerPc,
+        PeMachine::PowerPcFp,
+        PeMachine::Ia64,
+        PeMachine::Mips16,
+        PeMachine::Alpha64,
+        PeMachine::MipsFpu,
+        PeMachine::MipsFpu16,
+        PeMachine::TriCore,
+        PeMachine::Cef,
+        PeMachine::RiscV32,
+        PeMachine::RiscV64,
+        PeMachine::RiscV128,
+        PeMachine::LoongArch32,
+        PeMachine::LoongArch64,
+        PeMachine::Ebc,
+        PeMachine::Amd64,
+        PeMachine::M32R,
+        PeMachine::Arm64Ec,
+        PeMachine::Arm64X,
+        PeMachine::Arm64,
+        PeMachine::Cee,
+    ];
+
+    pub fn from_raw(raw: u16) -> Option<Self> {
+        PeMachine::ALL.into_iter().find(|machine| *machine as u16 == raw)
+    }
+
+    // The short family name shown before the slash, e.g. "x64" in
+    // "x64/AMD64".
+    pub fn family(self) -> &'static str {
+        match self {
+            PeMachine::I386 => "x86",
+            PeMachine::Amd64 => "x64",
+            PeMachine::Ia64 => "ia64",
+            PeMachine::Arm64 | PeMachine::Arm64X => "arm64",
+            PeMachine::Arm64Ec => "arm64ec",
+            PeMachine::Arm | PeMachine::Thumb | PeMachine::ArmNt => "arm",
+            PeMachine::R3000
+            | PeMachine::R4000
+            | PeMachine::R10000
+            | PeMachine::WceMipsV2
+            | PeMachine::Mips16
+            | PeMachine::MipsFpu
+            | PeMachine::MipsFpu16 => "mips",
+            PeMachine::Alpha | PeMachine::Alpha64 => "alpha",
+            PeMachine::Sh3
+            | PeMachine::Sh3Dsp
+            | PeMachine::Sh3E
+            | PeMachine::Sh4
+            | PeMachine::Sh5 => "sh",
+            PeMachine::PowerPc | PeMachine::PowerPcFp => "ppc",
+            PeMachine::RiscV32 | PeMachine::RiscV64 | PeMachine::RiscV128 => "riscv",
+            PeMachine::LoongArch32 | PeMachine::LoongArch64 => "loongarch",
+            PeMachine::Ebc | PeMachine::Cef | PeMachine::Cee => "bytecode",
+            PeMachine::Am33 | PeMachine::TriCore | PeMachine::M32R => "embedded",
+            PeMachine::Unknown => "any",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PeMachine::Unknown => "UNKNOWN",
+            PeMachine::I386 => "I386",
+            PeMachine::R3000 => "R3000",
+            PeMachine::R4000 => "R4000",
+            PeMachine::R10000 => "R10000",
+            PeMachine::WceMipsV2 => "WCEMIPSV2",
+            PeMachine::Alpha => "ALPHA",
+            PeMachine::Sh3 => "SH3",
+            PeMachine::Sh3Dsp => "SH3DSP",
+            PeMachine::Sh3E => "SH3E",
+            PeMachine::Sh4 => "SH4",
+            PeMachine::Sh5 => "SH5",
+            PeMachine::Arm => "ARM",
+            PeMachine::Thumb => "THUMB",
+            PeMachine::ArmNt => "ARMNT",
+            PeMachine::Am33 => "AM33",
+            PeMachine::PowerPc => "POWERPC",
+            PeMachine::PowerPcFp => "POWERPCFP",
+            PeMachine::Ia64 => "IA64",
+            PeMachine::Mips16 => "MIPS16",
+            PeMachine::Alpha64 => "ALPHA64",
+            PeMachine::MipsFpu => "MIPSFPU",
+            PeMachine::MipsFpu16 => "MIPSFPU16",
+            PeMachine::TriCore => "TRICORE",
+            PeMachine::Cef => "CEF",
+            PeMachine::RiscV32 => "RISCV32",
+            PeMachine::RiscV64 => "RISCV64",
+            PeMachine::RiscV128 => "RISCV128",
+            PeMachine::LoongArch32 => "LOONGARCH32",
+            PeMachine::LoongArch64 => "LOONGARCH64",
+            PeMachine::Ebc => "EBC",
+            PeMachine::Amd64 => "AMD64",
+            PeMachine::M32R => "M32R",
+            PeMachine::Arm64Ec => "ARM64EC",
+            PeMachine::Arm64X => "ARM64X",
+            PeMachine::Arm64 => "ARM64",
+            PeMachine::Cee => "CEE",
+        }
+    }
+}
+
+// IMAGE_COR20_HEADER.Flags
+const COMIMAGE_FLAGS_ILONLY: u32 = 0x0000_0001;
+const COMIMAGE_FLAGS_32BITREQUIRED: u32 = 0x0000_0002;
+const COMIMAGE_FLAGS_32BITPREFERRED: u32 = 0x0002_0000;
+// Offset of Flags in IMAGE_COR20_HEADER.
+const COR20_FLAGS_OFFSET: usize = 16;
+// Offset of CHPEMetadataPointer in IMAGE_LOAD_CONFIG_DIRECTORY64.
+const LOAD_CONFIG64_CHPE_METADATA_OFFSET: usize = 0xc8;
+
+// Which processes a managed assembly can load into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClrPlatform {
+    AnyCpu,
+    AnyCpu32BitPreferred,
+    X86Only,
+    // Mixed-mode or platform specific: only the PE machine counts.
+    Native,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClrHeader {
+    pub runtime_version: (u16, u16),
+    pub flags: u32,
+}
+
+impl ClrHeader {
+    pub fn platform(&self, machine: u16) -> ClrPlatform {
+        let il_only = self.flags & COMIMAGE_FLAGS_ILONLY != 0;
+        if !il_only || machine != PeMachine::I386 as u16 {
+            ClrPlatform::Native
+        } else if self.flags & COMIMAGE_FLAGS_32BITREQUIRED == 0 {
+            ClrPlatform::AnyCpu
+        } else if self.flags & COMIMAGE_FLAGS_32BITPREFERRED != 0 {
+            ClrPlatform::AnyCpu32BitPreferred
+        } else {
+            ClrPlatform::X86Only
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeArchitecture {
+    // The raw header value, kept for machines this table does not know.
+    pub raw_machine: u16,
+    // ARM64X and ARM64EC images carry ARM64 and AMD64 in the header and are
+    // told apart by their CHPE metadata.
+    pub machine: Option<PeMachine>,
+    pub clr: Option<ClrHeader>,
+}
+
+impl PeArchitecture {
+    // Pure function of the header values, so every combination can be checked
+    // without a binary.
+    pub fn from_headers(raw_machine: u16, has_chpe_metadata: bool, clr: Option<ClrHeader>) -> Self {
+        let machine = match PeMachine::from_raw(raw_machine) {
+            Some(PeMachine::Arm64) if has_chpe_metadata => Some(PeMachine::Arm64X),
+            Some(PeMachine::Amd64) if has_chpe_metadata => Some(PeMachine::Arm64Ec),
+            machine => machine,
+        };
+        PeArchitecture { raw_machine, machine, clr }
+    }
+
+    pub fn from_pe(pe: PeFile) -> Self {
+        PeArchitecture::from_headers(
+            pe.file_header().Machine,
+            has_chpe_metadata(pe),
+            clr_header(pe),
+        )
+    }
+
+    pub fn clr_platform(&self) -> Option<ClrPlatform> {
+        self.clr.map(|clr| clr.platform(self.raw_machine))
+    }
+}
+
+impl fmt::Display for PeArchitecture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.clr_platform() {
+            Some(ClrPlatform::AnyCpu) => return write!(f, "any/.NET AnyCPU"),
+            Some(ClrPlatform::AnyCpu32BitPreferred) => {
+                return write!(f, "any/.NET AnyCPU (32-bit preferred)");
+            },
+            Some(ClrPlatform::X86Only) => return write!(f, "x86/.NET 32BITREQUIRED"),
+            Some(ClrPlatform::Native) | None => {},
+        }
+        match self.machine {
+            Some(machine) => write!(f, "{}/{}", machine.family(), machine.name())?,
+            None => write!(f, "unknown ({:#06x})", self.raw_machine)?,
+        }
+        if self.clr.is_some() {
+            write!(f, " (.NET)")?;
+        }
+        Ok(())
+    }
+}
+
+fn clr_header(pe: PeFile) -> Option<ClrHeader> {
+    let directory = pe.data_directory().get(IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR)?;
+    if directory.VirtualAddress == 0 || directory.Size == 0 {
+        return None;
+    }
+    let header = pe.slice(directory.VirtualAddress, COR20_FLAGS_OFFSET + 4, 1).ok()?;
+    let read_u16 = |offset: usize| u16::from_le_bytes([header[offset], header[offset + 1]]);
+    Some(ClrHeader {
+        runtime_version: (read_u16(4), read_u16(6)),
+        flags: u32::from_le_bytes(
+            header[COR20_FLAGS_OFFSET..COR20_FLAGS_OFFSET + 4].try_into().ok()?,
+        ),
+    })
+}
+
+// Hybrid ARM64X/ARM64EC images point to CHPE metadata from their load
+// config. Only 64-bit images can be hybrids of this kind.
+fn has_chpe_metadata(pe: PeFile) -> bool {
+    let PeFile::T64(_) = pe else {
+        return false;
+    };
+    let Some(directory) = pe.data_directory().get(IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG) else {
+        return false;
+    };
+    let end = LOAD_CONFIG64_CHPE_METADATA_OFFSET + 8;
+    if directory.VirtualAddress == 0 || (directory.Size as usize) < end {
+        return false;
+    }
+    let Ok(load_config) = pe.slice(directory.VirtualAddress, end, 1) else {
+        return false;
+    };
+    // The structure's own Size field can be smaller than the directory says.
+    let size = u32::from_le_bytes([load_config[0], load_config[1], load_config[2], load_config[3]]);
+    size as usize >= end
+        && load_config[LOAD_CONFIG64_CHPE_METADATA_OFFSET..end].iter().any(|byte| *byte != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ILONLY: u32 = COMIMAGE_FLAGS_ILONLY;
+    const REQUIRED: u32 = COMIMAGE_FLAGS_32BITREQUIRED;
+    const PREFERRED: u32 = COMIMAGE_FLAGS_32BITPREFERRED;
+
+    #[test]
+    fn architecture_from_headers() {
+        use ClrPlatform::*;
+        use PeMachine::*;
+
+        // (machine, CHPE metadata, COR20 flags) -> (machine, CLR platform,
+        // display)
+        let table = [
+            (0x014c, false, None, Some(I386), None, "x86/I386"),
+            (0x8664, false, None, Some(Amd64), None, "x64/AMD64"),
+            (0xaa64, false, None, Some(Arm64), None, "arm64/ARM64"),
+            (0xaa64, true, None, Some(Arm64X), None, "arm64/ARM64X"),
+            (0x8664, true, None, Some(Arm64Ec), None, "arm64ec/ARM64EC"),
+            (0x014c, true, None, Some(I386), None, "x86/I386"),
+            (0x1234, false, None, None, None, "unknown (0x1234)"),
+            (0x014c, false, Some(ILONLY), Some(I386), Some(AnyCpu), "any/.NET AnyCPU"),
+            (
+                0x014c,
+                false,
+                Some(ILONLY | REQUIRED),
+                Some(I386),
+                Some(X86Only),
+                "x86/.NET 32BITREQUIRED",
+            ),
+            (
+                0x014c,
+                false,
+                Some(ILONLY | REQUIRED | PREFERRED),
+                Some(I386),
+                Some(AnyCpu32BitPreferred),
+                "any/.NET AnyCPU (32-bit preferred)",
+            ),
+            // 32BITPREFERRED means nothing without 32BITREQUIRED.
+            (0x014c, false, Some(ILONLY | PREFERRED), Some(I386), Some(AnyCpu), "any/.NET AnyCPU"),
+            // Mixed-mode assemblies are bound to their machine.
+            (0x014c, false, Some(REQUIRED), Some(I386), Some(Native), "x86/I386 (.NET)"),
+            (0x014c, false, Some(0), Some(I386), Some(Native), "x86/I386 (.NET)"),
+            (0x8664, false, Some(ILONLY), Some(Amd64), Some(Native), "x64/AMD64 (.NET)"),
+            (0x8664, false, Some(ILONLY | REQUIRED), Some(Amd64), Some(Native), "x64/AMD64 (.NET)"),
+            (0xaa64, false, Some(ILONLY), Some(Arm64), Some(Native), "arm64/ARM64 (.NET)"),
+        ];
+        for (raw_machine, chpe, flags, machine, platform, display) in table {
+            let clr = flags.map(|flags| ClrHeader { runtime_version: (2, 5), flags });
+            let architecture = PeArchitecture::from_headers(raw_machine, chpe, clr);
+            let case = format!("{:#06x} chpe={} flags={:?}", raw_machine, chpe, flags);
+            assert_eq!(architecture.raw_machine, raw_machine, "{}", case);
+            assert_eq!(architecture.machine, machine, "{}", case);
+            assert_eq!(architecture.clr_platform(), platform, "{}", case);
+            assert_eq!(architecture.to_string(), display, "{}", case);
+        }
+    }
+
+    #[test]
+    fn every_machine_round_trips() {
+        for machine in PeMachine::ALL {
+            assert_eq!(PeMachine::from_raw(machine as u16), Some(machine));
+        }
+        assert_eq!(PeMachine::from_raw(0xffff), None);
+    }
+}
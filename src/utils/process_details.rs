@@ -0,0 +1,269 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use libmem::Module;
+use libmem::module::enum_modules_ex;
+use libmem::process::Process;
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind, Users};
+use tracing::debug;
+
+use crate::utils::process_watcher::{ProcessKey, process_key};
+
+// Extra information about a process that libmem does not provide. Every field
+// is optional because most of it needs rights on the target we may not have.
+#[derive(Debug, Clone)]
+pub struct ProcessDetails {
+    pub key: ProcessKey,
+    pub command_line: Option<String>,
+    pub working_directory: Option<String>,
+    pub environment: Vec<String>,
+    pub user: Option<String>,
+    pub security: ProcessSecurity,
+    pub resident_memory: Option<u64>,
+    pub virtual_memory: Option<u64>,
+    pub thread_count: Option<u32>,
+    pub started_at: Option<SystemTime>,
+    // Empty if the modules could not be enumerated.
+    pub modules: Vec<Module>,
+}
+
+impl ProcessDetails {
+    pub fn collect(process: &Process) -> Self {
+        let pid = Pid::from_u32(process.pid);
+        let mut system = System::new();
+        system.refresh_processes_specifics(
+            ProcessesToUpdate::Some(&[pid]),
+            ProcessRefreshKind::new()
+                .with_cmd(UpdateKind::Always)
+                .with_cwd(UpdateKind::Always)
+                .with_environ(UpdateKind::Always)
+                .with_user(UpdateKind::Always)
+                .with_memory(),
+        );
+
+        let mut details = ProcessDetails {
+            key: process_key(process),
+            command_line: None,
+            working_directory: None,
+            environment: Vec::new(),
+            user: None,
+            security: ProcessSecurity::query(process.pid),
+            resident_memory: None,
+            virtual_memory: None,
+            thread_count: query_thread_count(process.pid),
+            started_at: None,
+            modules: enum_modules_ex(process).unwrap_or_default(),
+        };
+
+        match system.process(pid) {
+            Some(info) => {
+                let cmd = info.cmd();
+                if !cmd.is_empty() {
+                    details.command_line = Some(
+                        cmd.iter().map(|arg| arg.to_string_lossy()).collect::<Vec<_>>().join(" "),
+                    );
+                }
+                details.working_directory = info.cwd().map(|cwd| cwd.display().to_string());
+                details.environment =
+                    info.environ().iter().map(|var| var.to_string_lossy().into_owned()).collect();
+                details.user = info.user_id().map(|uid| {
+                    Users::new_with_refreshed_list()
+                        .get_user_by_id(uid)
+                        .map(|user| user.name().to_owned())
+                        .unwrap_or_else(|| format!("{:?}", uid))
+                });
+                details.resident_memory = Some(info.memory());
+                details.virtual_memory = Some(info.virtual_memory());
+                details.started_at = Some(UNIX_EPOCH + Duration::from_secs(info.start_time()));
+            },
+            None => debug!("No extended information available for PID {}", process.pid),
+        }
+
+        details
+    }
+
+    pub fn uptime(&self) -> Option<Duration> {
+        self.started_at.and_then(|started_at| started_at.elapsed().ok())
+    }
+
+    // A short description of the environment block: how many variables it
+    // holds and a few names.
+    pub fn environment_summary(&self) -> String {
+        if self.environment.is_empty() {
+            return "unavailable".to_owned();
+        }
+        let names: Vec<&str> = self
+            .environment
+            .iter()
+            .take(5)
+            .map(|var| var.split_once('=').map_or(var.as_str(), |(name, _)| name))
+            .collect();
+        let more = if self.environment.len() > names.len() { ", ..." } else { "" };
+        format!("{} variables ({}{})", self.environment.len(), names.join(", "), more)
+    }
+}
+
+#[cfg(windows)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrityLevel {
+    Untrusted,
+    Low,
+    Medium,
+    MediumPlus,
+    High,
+    System,
+    Protected,
+    Other(u32),
+}
+
+#[cfg(windows)]
+impl IntegrityLevel {
+    pub fn from_rid(rid: u32) -> Self {
+        match rid {
+            0x0000 => IntegrityLevel::Untrusted,
+            0x1000 => IntegrityLevel::Low,
+            0x2000 => IntegrityLevel::Medium,
+            0x2100 => IntegrityLevel::MediumPlus,
+            0x3000 => IntegrityLevel::High,
+            0x4000 => IntegrityLevel::System,
+            0x5000 => IntegrityLevel::Protected,
+            other => IntegrityLevel::Other(other),
+        }
+    }
+}
+
+#[cfg(windows)]
+#[derive(Debug, Clone, Default)]
+pub struct ProcessSecurity {
+    pub elevated: Option<bool>,
+    pub integrity: Option<IntegrityLevel>,
+}
+
+#[cfg(windows)]
+impl ProcessSecurity {
+    pub fn query(pid: u32) -> Self {
+        use winsafe::prelude::*;
+        use winsafe::{HPROCESS, TokenInfo, co};
+
+        let token = match HPROCESS::OpenProcess(co::PROCESS::QUERY_LIMITED_INFORMATION, false, pid)
+            .and_then(|process| process.OpenProcessToken(co::TOKEN::QUERY))
+        {
+            Ok(token) => token,
+            Err(err) => {
+                debug!("Failed to open token of PID {}: {}", pid, err);
+                return ProcessSecurity::default();
+            },
+        };
+
+        let elevated = match token.GetTokenInformation(co::TOKEN_INFORMATION_CLASS::Elevation) {
+            Ok(TokenInfo::Elevation(elevation)) => Some(elevation.TokenIsElevated()),
+            _ => None,
+        };
+        let integrity = match token.GetTokenInformation(co::TOKEN_INFORMATION_CLASS::IntegrityLevel)
+        {
+            Ok(TokenInfo::IntegrityLevel(label)) => label
+                .Label
+                .Sid()
+                .and_then(|sid| sid.SubAuthority().last().map(|rid| rid.raw()))
+                .map(IntegrityLevel::from_rid),
+            _ => None,
+        };
+
+        ProcessSecurity { elevated, integrity }
+    }
+
+    pub fn describe(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Elevated", self.elevated.map_or("unknown".to_owned(), |e| e.to_string())),
+            ("Integrity", self.integrity.map_or("unknown".to_owned(), |i| format!("{:?}", i))),
+        ]
+    }
+}
+
+#[cfg(unix)]
+#[derive(Debug, Clone, Default)]
+pub struct ProcessSecurity {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub effective_capabilities: Option<u64>,
+}
+
+#[cfg(unix)]
+impl ProcessSecurity {
+    pub fn query(pid: u32) -> Self {
+        let status = read_proc_status(pid);
+        let field = |name: &str| {
+            status.as_deref().and_then(|status| {
+                status
+                    .lines()
+                    .find_map(|line| line.strip_prefix(name))
+                    .map(|value| value.trim().to_owned())
+            })
+        };
+        // "Uid:" and "Gid:" list real, effective, saved and filesystem ids.
+        let first_id = |value: Option<String>| {
+            value.and_then(|v| v.split_whitespace().next().and_then(|id| id.parse().ok()))
+        };
+
+        ProcessSecurity {
+            uid: first_id(field("Uid:")),
+            gid: first_id(field("Gid:")),
+            effective_capabilities: field("CapEff:")
+                .and_then(|caps| u64::from_str_radix(&caps, 16).ok()),
+        }
+    }
+
+    pub fn describe(&self) -> Vec<(&'static str, String)> {
+        let unknown = || "unknown".to_owned();
+        vec![
+            ("UID", self.uid.map_or_else(unknown, |uid| uid.to_string())),
+            ("GID", self.gid.map_or_else(unknown, |gid| gid.to_string())),
+            (
+                "Capabilities",
+                self.effective_capabilities.map_or_else(unknown, |caps| format!("{:#018x}", caps)),
+            ),
+        ]
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn read_proc_status(pid: u32) -> Option<String> {
+    std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()
+}
+
+#[cfg(windows)]
+fn query_thread_count(pid: u32) -> Option<u32> {
+    use winsafe::prelude::*;
+    use winsafe::{HPROCESSLIST, co};
+
+    let mut snapshot =
+        HPROCESSLIST::CreateToolhelp32Snapshot(co::TH32CS::SNAPPROCESS, None).ok()?;
+    snapshot
+        .iter_processes()
+        .filter_map(Result::ok)
+        .find(|entry| entry.th32ProcessID == pid)
+        .map(|entry| entry.cntThreads)
+}
+
+#[cfg(unix)]
+fn query_thread_count(pid: u32) -> Option<u32> {
+    read_proc_status(pid)?
+        .lines()
+        .find_map(|line| line.strip_prefix("Threads:"))
+        .and_then(|count| count.trim().parse().ok())
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}d {:02}:{:02}:{:02}", secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60)
+}
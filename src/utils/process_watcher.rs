@@ -0,0 +1,228 @@
+use std::collections::HashSet;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use libmem::process::Process;
+use tracing::{debug, error};
+
+use crate::utils::processlist::get_process_list;
+
+// How long a started/exited process keeps its marker in the process lists.
+pub const PROCESS_CHANGE_MARK_DURATION: Duration = Duration::from_secs(10);
+
+// PIDs get recycled, so a process is identified by its PID together with its
+// start time.
+pub type ProcessKey = (u32, u64);
+
+pub fn process_key(process: &Process) -> ProcessKey {
+    (process.pid, process.start_time)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProcessDiff {
+    pub started: Vec<Process>,
+    pub exited: Vec<Process>,
+}
+
+// Compare two enumerations and report which processes appeared and which went
+// away in between.
+pub fn diff_process_lists(old: &[Process], new: &[Process]) -> ProcessDiff {
+    let old_keys: HashSet<ProcessKey> = old.iter().map(process_key).collect();
+    let new_keys: HashSet<ProcessKey> = new.iter().map(process_key).collect();
+
+    ProcessDiff {
+        started: new.iter().filter(|p| !old_keys.contains(&process_key(p))).cloned().collect(),
+        exited: old.iter().filter(|p| !new_keys.contains(&process_key(p))).cloned().collect(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessChangeKind {
+    Started,
+    Exited,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcessChange {
+    pub process: Process,
+    pub kind: ProcessChangeKind,
+    pub seen_at: Instant,
+}
+
+// Recent started/exited processes, kept around for a while so the UI can mark
+// them instead of having entries silently appear or disappear.
+#[derive(Default)]
+pub struct ProcessChangeTracker {
+    changes: Vec<ProcessChange>,
+}
+
+impl ProcessChangeTracker {
+    pub fn record(&mut self, diff: &ProcessDiff) {
+        let now = Instant::now();
+        // A process that exited is no longer "new", and a recycled key that
+        // started again is no longer "exited".
+        let touched: HashSet<ProcessKey> =
+            diff.started.iter().chain(diff.exited.iter()).map(process_key).collect();
+        self.changes.retain(|change| !touched.contains(&process_key(&change.process)));
+
+        self.changes.extend(diff.started.iter().map(|process| ProcessChange {
+            process: process.clone(),
+            kind: ProcessChangeKind::Started,
+            seen_at: now,
+        }));
+        self.changes.extend(diff.exited.iter().map(|process| ProcessChange {
+            process: process.clone(),
+            kind: ProcessChangeKind::Exited,
+            seen_at: now,
+        }));
+    }
+
+    pub fn prune(&mut self) {
+        self.changes.retain(|change| change.seen_at.elapsed() < PROCESS_CHANGE_MARK_DURATION);
+    }
+
+    pub fn kind_of(&self, process: &Process) -> Option<ProcessChangeKind> {
+        let key = process_key(process);
+        self.changes.iter().find(|change| process_key(&change.process) == key).map(|c| c.kind)
+    }
+
+    pub fn exited(&self) -> impl Iterator<Item = &Process> {
+        self.changes
+            .iter()
+            .filter(|change| change.kind == ProcessChangeKind::Exited)
+            .map(|change| &change.process)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+pub struct ProcessSnapshot {
+    pub processes: Vec<Process>,
+    pub diff: ProcessDiff,
+    pub enumeration_time: Duration,
+}
+
+enum WatcherCommand {
+    Refresh,
+    SetInterval(Option<Duration>),
+    Shutdown,
+}
+
+// Enumerates processes on a background thread, either on request or every
+// `interval`, so the UI thread never blocks on `enum_processes`.
+pub struct ProcessWatcher {
+    commands: Sender<WatcherCommand>,
+    snapshots: Receiver<ProcessSnapshot>,
+    worker: Option<JoinHandle<()>>,
+    refresh_pending: bool,
+}
+
+impl ProcessWatcher {
+    pub fn spawn(initial: Vec<Process>, interval: Option<Duration>) -> Self {
+        let (command_tx, command_rx) = mpsc::channel();
+        let (snapshot_tx, snapshot_rx) = mpsc::channel();
+
+        let worker = thread::Builder::new()
+            .name("process-watcher".to_owned())
+            .spawn(move || watcher_loop(initial, interval, command_rx, snapshot_tx))
+            .map_err(|err| error!("Failed to spawn process watcher thread: {}", err))
+            .ok();
+
+        ProcessWatcher {
+            commands: command_tx,
+            snapshots: snapshot_rx,
+            worker,
+            refresh_pending: false,
+        }
+    }
+
+    // Ask for a new enumeration. Repeated requests are coalesced until the
+    // pending one has been delivered.
+    pub fn request_refresh(&mut self) {
+        if !self.refresh_pending && self.commands.send(WatcherCommand::Refresh).is_ok() {
+            self.refresh_pending = true;
+        }
+    }
+
+    pub fn set_interval(&self, interval: Option<Duration>) {
+        let _ = self.commands.send(WatcherCommand::SetInterval(interval));
+    }
+
+    pub fn is_refreshing(&self) -> bool {
+        self.refresh_pending
+    }
+
+    // Return the most recent snapshot received since the last call, merging
+    // the diffs of any snapshots that were skipped over.
+    pub fn poll(&mut self) -> Option<ProcessSnapshot> {
+        let mut latest: Option<ProcessSnapshot> = None;
+        while let Ok(mut snapshot) = self.snapshots.try_recv() {
+            if let Some(previous) = latest.take() {
+                let mut merged = previous.diff;
+                merged.started.retain(|p| {
+                    snapshot.processes.iter().any(|n| process_key(n) == process_key(p))
+                });
+                merged.exited.extend(snapshot.diff.exited);
+                merged.started.extend(snapshot.diff.started);
+                snapshot.diff = merged;
+            }
+            latest = Some(snapshot);
+        }
+        if latest.is_some() {
+            self.refresh_pending = false;
+        }
+        latest
+    }
+}
+
+impl Drop for ProcessWatcher {
+    fn drop(&mut self) {
+        let _ = self.commands.send(WatcherCommand::Shutdown);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn watcher_loop(
+    mut previous: Vec<Process>,
+    mut interval: Option<Duration>,
+    commands: Receiver<WatcherCommand>,
+    snapshots: Sender<ProcessSnapshot>,
+) {
+    loop {
+        let command = match interval {
+            Some(interval) => commands.recv_timeout(interval),
+            None => commands.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match command {
+            Ok(WatcherCommand::Refresh) | Err(RecvTimeoutError::Timeout) => {},
+            Ok(WatcherCommand::SetInterval(new_interval)) => {
+                interval = new_interval;
+                continue;
+            },
+            Ok(WatcherCommand::Shutdown) | Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let started_at = Instant::now();
+        let processes = get_process_list();
+        let enumeration_time = started_at.elapsed();
+        let diff = diff_process_lists(&previous, &processes);
+        debug!(
+            "Enumerated {} processes in {:?} ({} started, {} exited)",
+            processes.len(),
+            enumeration_time,
+            diff.started.len(),
+            diff.exited.len()
+        );
+
+        previous.clone_from(&processes);
+        if snapshots.send(ProcessSnapshot { processes, diff, enumeration_time }).is_err() {
+            break;
+        }
+    }
+}
@@ -1,7 +1,17 @@
-use std::{fs, ptr};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use std::{fmt, ptr};
 
 use libmem::process::{Process, enum_processes};
-use tracing::{error, info};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, warn};
+
+use crate::utils::authenticode::{SignaturePolicy, TrustStore};
+use crate::utils::inject_error::InjectError;
+use crate::utils::remote_allocations::RemoteBuffer;
+use crate::utils::remote_exports::RemoteExportResolver;
 
 // info
 
@@ -24,16 +34,24 @@ use dinvoke::{close_handle, nt_create_thread_ex};
 use iced_x86::IcedError;
 // use dinvoke_rs::dinvoke;
 // use dinvoke_rs::dinvoke::{close_handle, nt_create_thread_ex};
-use iced_x86::code_asm::{CodeAssembler, eax};
-use libmem::memory::{alloc_memory_ex, free_memory_ex};
-use libmem::module::find_module_ex;
-use libmem::{Arch, Prot, load_module_ex, write_memory_ex};
+use iced_x86::code_asm::{CodeAssembler, dword_ptr, eax};
+use libmem::memory::read_memory_ex;
+use libmem::module::enum_modules_ex;
+use libmem::{Arch, Module, Prot, load_module_ex};
 use widestring::U16CString;
 
+// Layout of the buffer the x86 stub reports its result in.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct RemoteLoadResult {
+    module: u32,
+    last_error: u32,
+}
+
 fn build_code_x86_fix(
     load_library_w: u32,
-    _get_last_error: u32,
-    _return_buffer: *mut u32,
+    get_last_error: u32,
+    return_buffer: *mut u32,
     dll_path_addr: *mut u32,
 ) -> Result<Vec<u8>, IcedError> {
     let mut asm = CodeAssembler::new(32)?;
@@ -43,15 +61,14 @@ fn build_code_x86_fix(
     asm.push(eax)?; // lpLibFileName
     asm.mov(eax, load_library_w)?;
     asm.call(eax)?;
-    // asm.mov(dword_ptr(return_buffer as u32), eax)?;
-    ///////////////// asm.mov(eax, 0)?;
-    // let mut label = asm.create_label();
-    // asm.test(eax, eax)?;
-    // asm.mov(eax, 0)?;
-    // asm.jnz(label)?;
-    // asm.mov(eax, get_last_error)?;
-    // asm.call(eax)?; // return 0
-    // asm.set_label(&mut label)?;
+    asm.mov(dword_ptr(return_buffer as u32), eax)?; // RemoteLoadResult::module
+    let mut label = asm.create_label();
+    asm.test(eax, eax)?;
+    asm.jnz(label)?;
+    asm.mov(eax, get_last_error)?;
+    asm.call(eax)?;
+    asm.mov(dword_ptr(return_buffer as u32 + 4), eax)?; // RemoteLoadResult::last_error
+    asm.set_label(&mut label)?;
     asm.ret_1(4)?; // Restore stack ptr. (Callee cleanup)
     let code = asm.assemble(0x1234_5678)?;
     debug_assert_eq!(
@@ -67,184 +84,349 @@ use dinvoke_data::{PVOID, PsAttributeList, THREAD_ALL_ACCESS};
 // use dinvoke_rs::dinvoke::open_process;
 use windows::Wdk::Foundation::OBJECT_ATTRIBUTES;
 use windows::Win32::Foundation::HANDLE;
+use windows::Win32::System::Threading::GetExitCodeThread;
 use winsafe::prelude::*;
 
-pub fn inject_dll_test_fix(process: &Process, dll_path: &String) -> Result<(), String> {
-    if process.arch == Arch::X64 {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InjectionTechnique {
+    // libmem's load_module_ex, used for x64 targets.
+    LoadModule,
+    // LoadLibraryW stub started with NtCreateThreadEx, used for x86 targets.
+    ShellcodeThread,
+    // CLR hosting stub calling a static method of a .NET assembly.
+    Managed,
+}
+
+impl fmt::Display for InjectionTechnique {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InjectionTechnique::LoadModule => write!(f, "load_module_ex"),
+            InjectionTechnique::ShellcodeThread => write!(f, "LoadLibraryW + NtCreateThreadEx"),
+            InjectionTechnique::Managed => write!(f, "ExecuteInDefaultAppDomain"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct InjectedModule {
+    pub technique: InjectionTechnique,
+    // Base of the module in the target, as found by the verification step.
+    // Managed assemblies are not always mapped as a module.
+    pub module_base: Option<usize>,
+    // What the managed entry point returned.
+    pub return_value: Option<i32>,
+}
+
+// What to do with the remote allocations when the remote thread does not
+// finish in time or the injection is cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleanupPolicy {
+    // Leave them in the target. The thread may still be running and using
+    // them, so this only costs a few bytes.
+    Leak,
+    // Free them anyway. Crashes the target if the thread touches them later.
+    Free,
+}
+
+// How long the remote thread is waited for between checks of the cancel flag.
+pub(crate) const WAIT_SLICE: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone)]
+pub struct InjectOptions {
+    // Applies to waiting on our own remote thread. load_module_ex (x64) runs
+    // inside libmem and cannot be interrupted.
+    pub timeout: Duration,
+    pub cleanup_on_timeout: CleanupPolicy,
+    // How long a DLL waits for the module it depends on to show up.
+    pub module_wait_timeout: Duration,
+    // Checked against the DLL on disk right before it is injected.
+    pub signature_policy: SignaturePolicy,
+    pub trust_store: Arc<TrustStore>,
+    pub cancel: Arc<AtomicBool>,
+}
+
+impl Default for InjectOptions {
+    fn default() -> Self {
+        InjectOptions {
+            timeout: Duration::from_secs(10),
+            cleanup_on_timeout: CleanupPolicy::Leak,
+            module_wait_timeout: Duration::from_secs(60),
+            signature_policy: SignaturePolicy::Allow,
+            trust_store: Arc::default(),
+            cancel: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+// `progress` is called with a short description of each step as it starts.
+pub fn inject_dll_test_fix(
+    process: &Process,
+    dll_path: &String,
+    options: &InjectOptions,
+    progress: &dyn Fn(&'static str),
+) -> Result<InjectedModule, InjectError> {
+    let technique = if process.arch == Arch::X64 {
         // Handle x64 injection
-        return match load_module_ex(process, dll_path) {
-            None => Err("Failed to load DLL in target process.".into()),
+        progress("loading module");
+        match load_module_ex(process, dll_path) {
+            None => return Err(InjectError::LoadModule { path: dll_path.clone() }),
             Some(result) => {
-                info!("{}", format!("Success!!! {}", result));
-                Ok(())
+                info!(base = format_args!("{:#x}", result.base), "Loaded module {}", result.name);
             },
-        };
+        }
+        InjectionTechnique::LoadModule
     } else if process.arch == Arch::X86 {
         info!(
             "{}",
             format!("Process is x86, going to adapt injection mechanism: {:#?}", process.arch)
         );
 
-        // Allocate memory in the target process for the DLL path
+        // Every remote buffer below is freed when it goes out of scope, on
+        // success and on any error return alike.
+        progress("writing DLL path");
         let dll_path_wcstr = match U16CString::from_str(format!("{}\u{0}", dll_path)) {
-            Err(err) => return Err(format!("Failed to create U16CString: {}", err)),
-            Ok(wcstr) => wcstr,
-        };
-        let dll_path_wcstr_len = dll_path_wcstr.as_slice_with_nul().len();
-        // Step 1: Allocate memory for the DLL path in the target process
-        let remote_dll_path_memory = match alloc_memory_ex(process, dll_path_wcstr_len, Prot::RW) {
-            Some(addr) => addr,
-            None => return Err("Failed to allocate memory for DLL path.".into()),
-        };
-
-        // Step 2: Write the DLL path to the allocated memory
-        match write_memory_ex(process, remote_dll_path_memory, dll_path_wcstr.as_slice_with_nul()) {
-            Some(_) => {
-                println!("Successfully overwrite remote process memory to store DLL path.");
+            Err(err) => {
+                return Err(InjectError::InvalidDllPath {
+                    path: dll_path.clone(),
+                    reason: err.to_string(),
+                });
             },
-            None => {
-                eprintln!("Failed to overwrite remote process memory to store DLL path.");
-                return Err("Failed to write memory.".into());
-            },
-        }
-
-        // Load the KERNEL32 DLL and get the addresses of the functions
-        let kernel_32_dll_module = match find_module_ex(process, "KERNEL32.DLL") {
-            Some(module) => module,
-            None => return Err("Failed to find KERNEL32.DLL".into()),
-        };
-        let kernel_32_dll_bytes = match fs::read(kernel_32_dll_module.path) {
-            Err(err) => return Err(format!("Failed to read KERNEL32.DLL: {}", err)),
-            Ok(bytes) => bytes,
-        };
-        let kernel32_dll_pe_file = match pelite::PeFile::from_bytes(&kernel_32_dll_bytes) {
-            Err(err) => return Err(format!("Failed to parse KERNEL32.DLL: {}", err)),
-            Ok(pe) => pe,
+            Ok(wcstr) => wcstr,
         };
+        let remote_dll_path =
+            RemoteBuffer::with_value(process, dll_path_wcstr.as_slice_with_nul(), "DLL path")?;
+        debug!(
+            address = format_args!("{:#x}", remote_dll_path.address()),
+            "Wrote DLL path to remote process memory"
+        );
 
-        let load_library_w_export = match kernel32_dll_pe_file.get_export_by_name("LoadLibraryW") {
-            Err(err) => return Err(format!("Failed to find LoadLibraryW export: {}", err)),
-            Ok(export) => export,
-        };
-        let load_library_w_symbol = match load_library_w_export.symbol() {
-            Some(symbol) => symbol,
-            None => return Err("Failed to find LoadLibraryW symbol".into()),
-        };
-        let load_library_w_addr = kernel_32_dll_module.base as u32 + load_library_w_symbol;
+        // Resolve the functions from KERNEL32 as mapped in the target
+        progress("resolving LoadLibraryW");
+        let mut exports = RemoteExportResolver::new(process);
+        let load_library_w_addr = exports.resolve("KERNEL32.DLL", "LoadLibraryW")? as u32;
+        let get_last_error_addr = exports.resolve("KERNEL32.DLL", "GetLastError")? as u32;
 
-        let get_last_error_export = match kernel32_dll_pe_file.get_export_by_name("GetLastError") {
-            Err(err) => return Err(format!("Failed to find GetLastError export: {}", err)),
-            Ok(export) => export,
-        };
-        let get_last_error_symbol = match get_last_error_export.symbol() {
-            Some(symbol) => symbol,
-            None => return Err("Failed to find GetLastError symbol".into()),
-        };
-        let get_last_error_addr = kernel_32_dll_module.base as u32 + get_last_error_symbol;
+        // The stub stores LoadLibraryW's result and, on failure, GetLastError
+        // here, since the thread exit code alone cannot carry both.
+        let remote_result =
+            RemoteBuffer::with_value(process, &RemoteLoadResult::default(), "result buffer")?;
 
-        let get_last_error_addr_buffer = get_last_error_addr as *mut u32;
         // Build the shellcode with the address of the remote DLL path
         let shellcode = match build_code_x86_fix(
             load_library_w_addr,
             get_last_error_addr,
-            get_last_error_addr_buffer,
-            remote_dll_path_memory as *mut u32,
+            remote_result.address() as *mut u32,
+            remote_dll_path.address() as *mut u32,
         ) {
-            Err(err) => return Err(format!("Failed to build shellcode: {}", err)),
+            Err(err) => return Err(InjectError::BuildShellcode { reason: err.to_string() }),
             Ok(code) => code,
         };
 
-        // Step 3: Allocate memory for the shellcode in the target process
-        let remote_memory = match alloc_memory_ex(process, shellcode.len(), Prot::XRW) {
-            Some(addr) => addr,
-            None => return Err("Failed to allocate memory for shellcode.".into()),
-        };
+        progress("writing shellcode");
+        let remote_shellcode = RemoteBuffer::with_code(process, &shellcode, "shellcode")?;
+        debug!(
+            address = format_args!("{:#x}", remote_shellcode.address()),
+            len = shellcode.len(),
+            shellcode = ?shellcode,
+            "Wrote shellcode to remote process memory"
+        );
 
-        // Write the shellcode to the allocated memory
-        match write_memory_ex(process, remote_memory, shellcode.as_slice()) {
-            Some(_) => {
-                println!("Successfully overwrite remote process memory to store shellcode.");
+        // The thread may still be running and using the path, the stub and
+        // the result buffer if it does not finish.
+        let allocations = [&remote_dll_path, &remote_shellcode, &remote_result];
+        run_remote_thread(process, remote_shellcode.address(), options, progress, &allocations)?;
+
+        let load_result = read_memory_ex::<RemoteLoadResult>(process, remote_result.address());
+        match load_result {
+            Some(result) if result.module == 0 => {
+                return Err(InjectError::RemoteLoad { error_code: Some(result.last_error) });
             },
-            None => {
-                eprintln!("Failed to overwrite remote process memory to store shellcode.");
-                return Err("Failed to write memory.".into());
+            Some(result) => {
+                info!(module = format_args!("{:#x}", result.module), "LoadLibraryW succeeded");
             },
+            None => return Err(InjectError::RemoteLoad { error_code: None }),
         }
 
-        let access = THREAD_ALL_ACCESS;
-        let process_handle = open_process(access, 0, process.pid);
-
-        let mut thread = HANDLE(0);
-        let access: u32 = THREAD_ALL_ACCESS;
-        let attributes: *mut OBJECT_ATTRIBUTES = ptr::null_mut();
-        let function: PVOID = remote_memory as *mut u8 as PVOID;
-        let args: PVOID = ptr::null_mut();
-        let flags: u32 = 0;
-        let zero: usize = 0;
-        let stack: usize = 0;
-        let reserve: usize = 0;
-        let buffer: *mut PsAttributeList = ptr::null_mut();
-
-        let ntstatus_create_thread = nt_create_thread_ex(
-            &mut thread,
-            access,
-            attributes,
-            process_handle,
-            function,
-            args,
-            flags,
-            zero,
-            stack,
-            reserve,
-            buffer,
-        );
-        if (0..=0x3FFFFFFF).contains(&ntstatus_create_thread) {
-            println!("Successfully created thread! {:#x}", ntstatus_create_thread);
-
-            let waiteress = unsafe {
-                kernel_Hevent::WaitForSingleObject(
-                    &winsafe::HEVENT::from_ptr(thread.0 as *mut c_void),
-                    Some(4294967295u32),
-                )
-            };
-            match waiteress {
-                Ok(waitress_ready) => match waitress_ready.raw() {
-                    0x0000_0080 => {
-                        eprintln!("WaitForSingleObject has been abandoned!");
-                    },
-                    0x0000_0000 => {
-                        println!("WaitForSingleObject has been finished successfully!");
-                    },
-                    0x0000_0102 => {
-                        eprintln!("WaitForSingleObject has been timed out!");
-                    },
-                    0xffff_ffff => {
-                        eprintln!("WaitForSingleObject has failed!");
-                    },
-                    _ => {
-                        eprintln!("WaitForSingleObject has failed! {:#x}", waitress_ready.raw());
-                    },
+        drop(remote_result);
+        drop(remote_dll_path);
+        drop(remote_shellcode);
+        InjectionTechnique::ShellcodeThread
+    } else {
+        return Err(InjectError::UnsupportedArchitecture { arch: format!("{:?}", process.arch) });
+    };
+
+    // Whatever the technique reported, only trust a module that shows up in a
+    // fresh enumeration of the target's modules.
+    progress("verifying module");
+    let module = verify_module_loaded(process, dll_path)?;
+    info!(base = format_args!("{:#x}", module.base), "Verified {} is loaded", module.name);
+    Ok(InjectedModule { technique, module_base: Some(module.base), return_value: None })
+}
+
+// Start `function` in the target with NtCreateThreadEx and wait for it in
+// short slices, so a thread stuck on the loader lock can be given up on, either
+// by the timeout or by the user. `allocations` are the remote buffers the
+// thread uses; the caller's guards free them unless the cleanup policy leaves
+// them in place for a thread that did not finish. Returns the thread's exit
+// code.
+pub(crate) fn run_remote_thread(
+    process: &Process,
+    function: usize,
+    options: &InjectOptions,
+    progress: &dyn Fn(&'static str),
+    allocations: &[&RemoteBuffer],
+) -> Result<u32, InjectError> {
+    let access = THREAD_ALL_ACCESS;
+    let process_handle = open_process(access, 0, process.pid);
+    if process_handle.0 == 0 {
+        return Err(InjectError::OpenProcess { pid: process.pid });
+    }
+
+    let mut thread = HANDLE(0);
+    let access: u32 = THREAD_ALL_ACCESS;
+    let attributes: *mut OBJECT_ATTRIBUTES = ptr::null_mut();
+    let function: PVOID = function as *mut u8 as PVOID;
+    let args: PVOID = ptr::null_mut();
+    let flags: u32 = 0;
+    let zero: usize = 0;
+    let stack: usize = 0;
+    let reserve: usize = 0;
+    let buffer: *mut PsAttributeList = ptr::null_mut();
+
+    progress("creating remote thread");
+    let ntstatus_create_thread = nt_create_thread_ex(
+        &mut thread,
+        access,
+        attributes,
+        process_handle,
+        function,
+        args,
+        flags,
+        zero,
+        stack,
+        reserve,
+        buffer,
+    );
+    if !(0..=0x3FFFFFFF).contains(&ntstatus_create_thread) {
+        close_handle(process_handle);
+        return Err(InjectError::CreateThread { status: ntstatus_create_thread });
+    }
+    info!(status = format_args!("{:#x}", ntstatus_create_thread), "Created remote thread");
+
+    progress("waiting for remote thread");
+    let deadline = Instant::now() + options.timeout;
+    let wait_result = loop {
+        if options.cancel.load(Ordering::Relaxed) {
+            break Err(InjectError::Cancelled);
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break Err(InjectError::WaitTimedOut { timeout: options.timeout });
+        }
+        let waiteress = unsafe {
+            kernel_Hevent::WaitForSingleObject(
+                &winsafe::HEVENT::from_ptr(thread.0 as *mut c_void),
+                Some(remaining.min(WAIT_SLICE).as_millis() as u32),
+            )
+        };
+        match waiteress {
+            Ok(waitress_ready) => match waitress_ready.raw() {
+                0x0000_0000 => {
+                    let mut exit_code = 0;
+                    break match unsafe { GetExitCodeThread(thread, &mut exit_code) } {
+                        Ok(()) => {
+                            info!(
+                                exit_code = format_args!("{:#x}", exit_code),
+                                "Remote thread finished"
+                            );
+                            Ok(exit_code)
+                        },
+                        Err(err) => Err(InjectError::WaitFailed {
+                            reason: format!("GetExitCodeThread failed: {}", err),
+                        }),
+                    };
                 },
-                Err(e) => {
-                    eprintln!("Failed to wait for thread: {:#?}", e);
+                0x0000_0102 => continue,
+                0x0000_0080 => break Err(InjectError::WaitAbandoned),
+                other => {
+                    break Err(InjectError::WaitFailed {
+                        reason: format!("WaitForSingleObject returned {:#x}", other),
+                    });
                 },
-            }
-            close_handle(thread);
-            close_handle(process_handle);
-            free_memory_ex(process, remote_dll_path_memory, dll_path_wcstr_len);
-            free_memory_ex(process, remote_memory, shellcode.len());
-        } else {
-            println!("Failed to create thread! {:#x}", ntstatus_create_thread);
-            close_handle(process_handle);
-            free_memory_ex(process, remote_dll_path_memory, dll_path_wcstr_len);
-            free_memory_ex(process, remote_memory, shellcode.len());
+            },
+            Err(e) => break Err(InjectError::WaitFailed { reason: e.to_string() }),
         }
+    };
+    close_handle(thread);
+    close_handle(process_handle);
+    if wait_result.is_err() {
+        match options.cleanup_on_timeout {
+            // The buffers are freed when the caller drops them.
+            CleanupPolicy::Free => {
+                warn!("Freeing remote allocations while the remote thread may still run");
+            },
+            CleanupPolicy::Leak => {
+                for allocation in allocations {
+                    allocation.leave_in_place();
+                }
+            },
+        }
+    }
+    wait_result
+}
 
-        println!("Allocated address for shellcode is {:#x}", remote_memory);
-        println!("Shellcode = {:?}", shellcode);
-        println!("Shellcode.len = {}", shellcode.len());
-    } else {
-        return Err("Process architecture not supported.".into());
+// Runs code from the shellcode workbench in the target and returns the
+// thread's exit code. `assemble` is called with the address the code will
+// run at, so position dependent code can be assembled for it.
+pub fn execute_code(
+    process: &Process,
+    size: usize,
+    assemble: impl FnOnce(usize) -> Result<Vec<u8>, String>,
+    options: &InjectOptions,
+    progress: &dyn Fn(&'static str),
+) -> Result<u32, InjectError> {
+    // Allocations are page granular anyway; the slack covers code that grows
+    // when assembled at the real address.
+    let capacity = size.next_multiple_of(0x1000).max(0x1000);
+    let buffer = RemoteBuffer::alloc(process, capacity, Prot::RW, "workbench code")?;
+    let code =
+        assemble(buffer.address()).map_err(|reason| InjectError::BuildShellcode { reason })?;
+    if code.len() > capacity {
+        return Err(InjectError::BuildShellcode {
+            reason: format!("{} bytes do not fit in the {} allocated", code.len(), capacity),
+        });
     }
-    Ok(())
+
+    progress("writing code");
+    buffer.write(code.as_slice())?;
+    buffer.protect(Prot::XR)?;
+    info!(
+        address = format_args!("{:#x}", buffer.address()),
+        len = code.len(),
+        "Wrote workbench code to remote process memory"
+    );
+    let exit_code = run_remote_thread(process, buffer.address(), options, progress, &[&buffer])?;
+    drop(buffer);
+    Ok(exit_code)
+}
+
+fn normalize_module_path(path: &str) -> String {
+    path.trim_start_matches(r"\\?\").replace('/', "\\").to_lowercase()
+}
+
+// Look for the injected DLL among the target's modules, by full path first
+// and by file name if the loader reports the path differently.
+pub fn verify_module_loaded(process: &Process, dll_path: &str) -> Result<Module, InjectError> {
+    let not_loaded = || InjectError::NotLoaded { path: dll_path.to_owned() };
+    let modules = enum_modules_ex(process).ok_or_else(not_loaded)?;
+
+    let wanted_path = normalize_module_path(dll_path);
+    let wanted_name =
+        Path::new(dll_path).file_name().map(|name| name.to_string_lossy().to_lowercase());
+    modules
+        .iter()
+        .find(|module| normalize_module_path(&module.path) == wanted_path)
+        .or_else(|| modules.iter().find(|module| Some(module.name.to_lowercase()) == wanted_name))
+        .cloned()
+        .ok_or_else(not_loaded)
 }
@@ -0,0 +1,208 @@
+use std::cell::Cell;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::{self, ThreadId};
+use std::time::SystemTime;
+
+use libmem::Prot;
+use libmem::memory::{alloc_memory_ex, free_memory_ex, prot_memory_ex, write_memory_ex};
+use libmem::process::Process;
+use tracing::warn;
+
+use crate::utils::inject_error::InjectError;
+use crate::utils::process_watcher::{ProcessKey, process_key};
+
+// A buffer an injection allocated in a target and has not freed (yet).
+#[derive(Debug, Clone)]
+pub struct RemoteAllocation {
+    pub key: ProcessKey,
+    pub address: usize,
+    pub size: usize,
+    pub prot: Prot,
+    // What the buffer holds, e.g. "DLL path" or "shellcode".
+    pub what: &'static str,
+    pub allocated_at: SystemTime,
+    // Order of allocation and the thread that made it, so an injection audits
+    // only its own buffers and not those of earlier attempts or a workbench
+    // run at the same time.
+    sequence: u64,
+    thread: ThreadId,
+}
+
+impl RemoteAllocation {
+    pub fn describe(&self) -> String {
+        format!("{} at {:#x} ({} bytes)", self.what, self.address, self.size)
+    }
+}
+
+// Every allocation made through RemoteBuffer that has not been released,
+// across all targets. Entries for a freed buffer are only dropped once the
+// free succeeded, so failed cleanups and leaked buffers of timed out threads
+// stay listed.
+static ALLOCATIONS: Mutex<Vec<RemoteAllocation>> = Mutex::new(Vec::new());
+static NEXT_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+fn update_allocations(update: impl FnOnce(&mut Vec<RemoteAllocation>)) {
+    if let Ok(mut allocations) = ALLOCATIONS.lock() {
+        update(&mut allocations);
+    }
+}
+
+// Allocations still outstanding in the process identified by `key`.
+pub fn outstanding_allocations(key: ProcessKey) -> Vec<RemoteAllocation> {
+    ALLOCATIONS
+        .lock()
+        .map(|allocations| {
+            allocations.iter().filter(|allocation| allocation.key == key).cloned().collect()
+        })
+        .unwrap_or_default()
+}
+
+// Taken before an injection attempt, for `allocations_since`.
+pub fn allocation_mark() -> u64 {
+    NEXT_SEQUENCE.load(Ordering::Relaxed)
+}
+
+// Allocations in `key` the current thread made after `mark` that are still
+// outstanding.
+pub fn allocations_since(key: ProcessKey, mark: u64) -> Vec<RemoteAllocation> {
+    let current = thread::current().id();
+    outstanding_allocations(key)
+        .into_iter()
+        .filter(|allocation| allocation.sequence >= mark && allocation.thread == current)
+        .collect()
+}
+
+// The buffers of an exited process went with it.
+pub fn forget_process(key: ProcessKey) {
+    update_allocations(|allocations| allocations.retain(|allocation| allocation.key != key));
+}
+
+// A buffer in the target that is freed when it goes out of scope, so every
+// exit path of a backend cleans up after itself. Buffers a remote thread may
+// still be using are kept with `leave_in_place`.
+pub struct RemoteBuffer<'a> {
+    process: &'a Process,
+    address: usize,
+    size: usize,
+    what: &'static str,
+    // Freed or deliberately left in place; either way not ours to free.
+    released: Cell<bool>,
+}
+
+impl<'a> RemoteBuffer<'a> {
+    pub fn alloc(
+        process: &'a Process,
+        size: usize,
+        prot: Prot,
+        what: &'static str,
+    ) -> Result<Self, InjectError> {
+        let address =
+            alloc_memory_ex(process, size, prot).ok_or(InjectError::Allocate { what, size })?;
+        update_allocations(|allocations| {
+            allocations.push(RemoteAllocation {
+                key: process_key(process),
+                address,
+                size,
+                prot,
+                what,
+                allocated_at: SystemTime::now(),
+                sequence: NEXT_SEQUENCE.fetch_add(1, Ordering::Relaxed),
+                thread: thread::current().id(),
+            })
+        });
+        Ok(RemoteBuffer { process, address, size, what, released: Cell::new(false) })
+    }
+
+    // A read-write buffer holding `value`.
+    pub fn with_value<T: ?Sized>(
+        process: &'a Process,
+        value: &T,
+        what: &'static str,
+    ) -> Result<Self, InjectError> {
+        let buffer = RemoteBuffer::alloc(process, size_of_val(value), Prot::RW, what)?;
+        buffer.write(value)?;
+        Ok(buffer)
+    }
+
+    // Code is written while the buffer is read-write and only then made
+    // executable, so the target never has a writable and executable page.
+    pub fn with_code(
+        process: &'a Process,
+        code: &[u8],
+        what: &'static str,
+    ) -> Result<Self, InjectError> {
+        let buffer = RemoteBuffer::with_value(process, code, what)?;
+        buffer.protect(Prot::XR)?;
+        Ok(buffer)
+    }
+
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    pub fn write<T: ?Sized>(&self, value: &T) -> Result<(), InjectError> {
+        write_memory_ex(self.process, self.address, value).ok_or(InjectError::Write {
+            what: self.what,
+            address: self.address,
+            size: size_of_val(value),
+        })
+    }
+
+    pub fn protect(&self, prot: Prot) -> Result<(), InjectError> {
+        prot_memory_ex(self.process, self.address, self.size, prot).ok_or(
+            InjectError::Protect { what: self.what, address: self.address, size: self.size },
+        )?;
+        let key = process_key(self.process);
+        update_allocations(|allocations| {
+            for allocation in allocations.iter_mut() {
+                if allocation.key == key && allocation.address == self.address {
+                    allocation.prot = prot;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    pub fn leave_in_place(&self) {
+        warn!(
+            address = format_args!("{:#x}", self.address),
+            size = self.size,
+            "Leaving the {} in place",
+            self.what
+        );
+        self.released.set(true);
+    }
+
+    // A failed free leaves the buffer listed, so the audit after the
+    // injection reports it.
+    fn release(&self) -> Option<()> {
+        if self.released.get() {
+            return Some(());
+        }
+        free_memory_ex(self.process, self.address, self.size)?;
+        self.released.set(true);
+        let key = process_key(self.process);
+        update_allocations(|allocations| {
+            allocations
+                .retain(|allocation| allocation.key != key || allocation.address != self.address)
+        });
+        Some(())
+    }
+}
+
+// A free that fails here only warns: by then the code has run or the attempt
+// has already failed, and that outcome is what the caller reports. The buffer
+// stays listed, so the leftover audit still reports it.
+impl Drop for RemoteBuffer<'_> {
+    fn drop(&mut self) {
+        if self.release().is_none() {
+            warn!(
+                address = format_args!("{:#x}", self.address),
+                size = self.size,
+                "Failed to free the {}",
+                self.what
+            );
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+
+use libmem::memory::read_memory_ex;
+use libmem::module::enum_modules_ex;
+use libmem::process::Process;
+use libmem::{Bits, Module};
+use tracing::debug;
+
+use crate::utils::inject_error::InjectError;
+use crate::utils::pe_exports::{ExportSymbol, MAX_FORWARDER_DEPTH, api_set_host, parse_forwarder};
+
+// Resolves exports from the export directories mapped in the target, so it
+// neither depends on the module files on disk (WOW64 redirects System32 for a
+// SysWOW64 module) nor on the bitness of the injector.
+
+const IMAGE_NT_OPTIONAL_HDR32_MAGIC: u16 = 0x10b;
+const IMAGE_NT_OPTIONAL_HDR64_MAGIC: u16 = 0x20b;
+// Longest export name or forwarder string read from the target.
+const MAX_EXPORT_NAME: usize = 512;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct ImageExportDirectory {
+    characteristics: u32,
+    time_date_stamp: u32,
+    major_version: u16,
+    minor_version: u16,
+    name: u32,
+    base: u32,
+    number_of_functions: u32,
+    number_of_names: u32,
+    address_of_functions: u32,
+    address_of_names: u32,
+    address_of_name_ordinals: u32,
+}
+
+// The parts of one module's export directory that lookups need. Names are
+// read from the target on demand.
+struct RemoteExportTable {
+    base: usize,
+    // Function RVAs inside this range point to forwarder strings.
+    directory: (u32, u32),
+    ordinal_base: u32,
+    functions: Vec<u32>,
+    names: Vec<u32>,
+    name_ordinals: Vec<u16>,
+}
+
+// Reads `len` bytes in the largest chunks read_memory_ex takes without
+// running past the end of the range.
+pub fn read_remote_bytes(process: &Process, address: usize, len: usize) -> Option<Vec<u8>> {
+    fn read_chunk<const N: usize>(
+        process: &Process,
+        address: usize,
+        len: usize,
+        bytes: &mut Vec<u8>,
+    ) -> Option<()> {
+        while len - bytes.len() >= N {
+            let chunk = read_memory_ex::<[u8; N]>(process, address + bytes.len())?;
+            bytes.extend_from_slice(&chunk);
+        }
+        Some(())
+    }
+    // Grows as the reads succeed, so a bogus length fails at the first
+    // unreadable page instead of allocating all of it up front.
+    let mut bytes = Vec::with_capacity(len.min(0x10000));
+    read_chunk::<4096>(process, address, len, &mut bytes)?;
+    read_chunk::<256>(process, address, len, &mut bytes)?;
+    read_chunk::<16>(process, address, len, &mut bytes)?;
+    read_chunk::<1>(process, address, len, &mut bytes)?;
+    Some(bytes)
+}
+
+// A NUL-terminated string, read in small chunks so it never runs far past
+// the end of the image.
+fn read_remote_c_string(process: &Process, address: usize) -> Option<String> {
+    let mut bytes = Vec::new();
+    while bytes.len() < MAX_EXPORT_NAME {
+        let chunk = read_memory_ex::<[u8; 16]>(process, address + bytes.len())
+            .map(|chunk| chunk.to_vec())
+            .or_else(|| read_remote_bytes(process, address + bytes.len(), 1))?;
+        match chunk.iter().position(|byte| *byte == 0) {
+            Some(end) => {
+                bytes.extend_from_slice(&chunk[..end]);
+                return String::from_utf8(bytes).ok();
+            },
+            None => bytes.extend_from_slice(&chunk),
+        }
+    }
+    None
+}
+
+fn read_u32_array(process: &Process, address: usize, count: usize) -> Option<Vec<u32>> {
+    let bytes = read_remote_bytes(process, address, count * 4)?;
+    Some(bytes.as_chunks::<4>().0.iter().map(|chunk| u32::from_le_bytes(*chunk)).collect())
+}
+
+fn read_u16_array(process: &Process, address: usize, count: usize) -> Option<Vec<u16>> {
+    let bytes = read_remote_bytes(process, address, count * 2)?;
+    Some(bytes.as_chunks::<2>().0.iter().map(|chunk| u16::from_le_bytes(*chunk)).collect())
+}
+
+impl RemoteExportTable {
+    fn read(process: &Process, module: &Module) -> Result<Self, String> {
+        let base = module.base;
+        let nt_headers = read_memory_ex::<u32>(process, base + 0x3c)
+            .ok_or("failed to read the DOS header")? as usize;
+        let magic = read_memory_ex::<u16>(process, base + nt_headers + 24)
+            .ok_or("failed to read the optional header")?;
+        // The export directory is the first data directory.
+        let data_directories = match magic {
+            IMAGE_NT_OPTIONAL_HDR32_MAGIC => base + nt_headers + 24 + 96,
+            IMAGE_NT_OPTIONAL_HDR64_MAGIC => base + nt_headers + 24 + 112,
+            magic => return Err(format!("unknown optional header magic {:#x}", magic)),
+        };
+        let (rva, size) = read_memory_ex::<[u32; 2]>(process, data_directories)
+            .map(|[rva, size]| (rva, size))
+            .ok_or("failed to read the data directories")?;
+        if rva == 0 {
+            return Err("module has no exports".to_owned());
+        }
+        let directory = read_memory_ex::<ImageExportDirectory>(process, base + rva as usize)
+            .ok_or("failed to read the export directory")?;
+
+        let read_error = || "failed to read the export tables".to_owned();
+        // The counts come from the target, so bound them by what fits in the
+        // export directory and the module before allocating for them.
+        let entries = |count: u32, table: u32, entry_size: usize| {
+            (count as usize)
+                .min(size as usize / entry_size)
+                .min(module.size.saturating_sub(table as usize) / entry_size)
+        };
+        let functions = entries(directory.number_of_functions, directory.address_of_functions, 4);
+        let names = entries(directory.number_of_names, directory.address_of_names, 4).min(entries(
+            directory.number_of_names,
+            directory.address_of_name_ordinals,
+            2,
+        ));
+        Ok(RemoteExportTable {
+            base,
+            directory: (rva, rva.saturating_add(size)),
+            ordinal_base: directory.base,
+            functions: read_u32_array(
+                process,
+                base + directory.address_of_functions as usize,
+                functions,
+            )
+            .ok_or_else(read_error)?,
+            names: read_u32_array(process, base + directory.address_of_names as usize, names)
+                .ok_or_else(read_error)?,
+            name_ordinals: read_u16_array(
+                process,
+                base + directory.address_of_name_ordinals as usize,
+                names,
+            )
+            .ok_or_else(read_error)?,
+        })
+    }
+
+    // Index into `functions`. Names are sorted, so this is a binary search
+    // that reads one name from the target per step.
+    fn function_index(&self, process: &Process, symbol: &ExportSymbol) -> Result<usize, String> {
+        match symbol {
+            ExportSymbol::Ordinal(ordinal) => (*ordinal as u32)
+                .checked_sub(self.ordinal_base)
+                .map(|index| index as usize)
+                .filter(|index| *index < self.functions.len())
+                .ok_or_else(|| format!("no export with ordinal {}", ordinal)),
+            ExportSymbol::Name(name) => {
+                let (mut low, mut high) = (0, self.names.len());
+                while low < high {
+                    let middle = (low + high) / 2;
+                    let candidate =
+                        read_remote_c_string(process, self.base + self.names[middle] as usize)
+                            .ok_or("failed to read an export name")?;
+                    match candidate.as_str().cmp(name.as_str()) {
+                        std::cmp::Ordering::Less => low = middle + 1,
+                        std::cmp::Ordering::Greater => high = middle,
+                        std::cmp::Ordering::Equal => {
+                            return self
+                                .name_ordinals
+                                .get(middle)
+                                .map(|index| *index as usize)
+                                .filter(|index| *index < self.functions.len())
+                                .ok_or_else(|| "export ordinal is out of range".to_owned());
+                        },
+                    }
+                }
+                Err("export not found".to_owned())
+            },
+        }
+    }
+}
+
+enum RemoteExport {
+    Address(usize),
+    Forward(String),
+}
+
+// Resolves exports of the modules loaded in one target, caching the module
+// list and export tables, so a backend can create one and look up everything
+// it needs.
+pub struct RemoteExportResolver<'a> {
+    process: &'a Process,
+    modules: Option<Vec<Module>>,
+    tables: HashMap<usize, RemoteExportTable>,
+}
+
+impl<'a> RemoteExportResolver<'a> {
+    pub fn new(process: &'a Process) -> Self {
+        RemoteExportResolver { process, modules: None, tables: HashMap::new() }
+    }
+
+    // A WOW64 process has both a 64-bit and a 32-bit ntdll.dll; take the one
+    // matching the target's bitness.
+    pub fn find_module(&mut self, name: &str) -> Result<Module, InjectError> {
+        let process = self.process;
+        let modules =
+            self.modules.get_or_insert_with(|| enum_modules_ex(process).unwrap_or_default());
+        let wanted_magic = match process.bits {
+            Bits::Bits32 => IMAGE_NT_OPTIONAL_HDR32_MAGIC,
+            Bits::Bits64 => IMAGE_NT_OPTIONAL_HDR64_MAGIC,
+        };
+        let mut candidates = modules.iter().filter(|module| module.name.eq_ignore_ascii_case(name));
+        let first = candidates.clone().next().cloned();
+        candidates
+            .find(|module| {
+                read_memory_ex::<u32>(process, module.base + 0x3c)
+                    .and_then(|nt_headers| {
+                        read_memory_ex::<u16>(process, module.base + nt_headers as usize + 24)
+                    })
+                    .is_some_and(|magic| magic == wanted_magic)
+            })
+            .cloned()
+            .or(first)
+            .ok_or_else(|| InjectError::ModuleNotFound { module: name.to_owned() })
+    }
+
+    fn lookup(&mut self, module: &str, symbol: &ExportSymbol) -> Result<RemoteExport, InjectError> {
+        let remote_module = self.find_module(module)?;
+        let process = self.process;
+        let table = match self.tables.entry(remote_module.base) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                let table = RemoteExportTable::read(process, &remote_module).map_err(|reason| {
+                    InjectError::ReadModule { module: module.to_owned(), reason }
+                })?;
+                entry.insert(table)
+            },
+        };
+        let resolve_error = |reason: String| InjectError::ResolveExport {
+            module: module.to_owned(),
+            export: symbol.to_string(),
+            reason,
+        };
+        let index = table.function_index(process, symbol).map_err(resolve_error)?;
+        let rva = *table
+            .functions
+            .get(index)
+            .ok_or_else(|| resolve_error("export ordinal is out of range".to_owned()))?;
+        let (start, end) = table.directory;
+        if (start..end).contains(&rva) {
+            let forwarder = read_remote_c_string(process, table.base + rva as usize)
+                .ok_or_else(|| resolve_error("failed to read the forwarder".to_owned()))?;
+            Ok(RemoteExport::Forward(forwarder))
+        } else if rva == 0 {
+            Err(resolve_error("export slot is empty".to_owned()))
+        } else {
+            Ok(RemoteExport::Address(table.base + rva as usize))
+        }
+    }
+
+    // Address of `export` in the target, following forwarders (KERNEL32 →
+    // KERNELBASE → NTDLL) into the modules they point to.
+    pub fn resolve(&mut self, module: &str, export: &str) -> Result<usize, InjectError> {
+        self.resolve_symbol(module, &ExportSymbol::Name(export.to_owned()))
+    }
+
+    // Like `resolve`, for imports by ordinal as well as by name.
+    pub fn resolve_symbol(
+        &mut self,
+        module: &str,
+        export: &ExportSymbol,
+    ) -> Result<usize, InjectError> {
+        let mut current_module = module.to_owned();
+        let mut symbol = export.clone();
+        for _ in 0..MAX_FORWARDER_DEPTH {
+            match self.lookup(&current_module, &symbol)? {
+                RemoteExport::Address(address) => return Ok(address),
+                RemoteExport::Forward(target) => {
+                    debug!("{}!{} is forwarded to {}", current_module, symbol, target);
+                    let (next_module, next_symbol) =
+                        parse_forwarder(&target).ok_or_else(|| InjectError::ResolveExport {
+                            module: module.to_owned(),
+                            export: export.to_string(),
+                            reason: format!("malformed forwarder {}", target),
+                        })?;
+                    current_module = api_set_host(&next_module).map_or(next_module, str::to_owned);
+                    symbol = next_symbol;
+                },
+            }
+        }
+        Err(InjectError::ResolveExport {
+            module: module.to_owned(),
+            export: export.to_string(),
+            reason: format!("more than {} forwarders", MAX_FORWARDER_DEPTH),
+        })
+    }
+}
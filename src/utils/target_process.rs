@@ -0,0 +1,84 @@
+use std::fmt;
+
+use libmem::process::{Process, get_process_ex};
+
+use crate::utils::process_watcher::{ProcessKey, process_key};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetState {
+    Running,
+    Exited,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetError {
+    Exited { pid: u32, name: String },
+    PidReused { pid: u32, name: String, new_name: String },
+}
+
+impl fmt::Display for TargetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetError::Exited { pid, name } => {
+                write!(f, "Process {} (PID {}) has exited", name, pid)
+            },
+            TargetError::PidReused { pid, name, new_name } => write!(
+                f,
+                "Process {} (PID {}) has exited and its PID now belongs to {}",
+                name, pid, new_name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TargetError {}
+
+// The process picked in the UI, identified by PID + start time so that a
+// recycled PID is never mistaken for the original target. Keeps the last known
+// process information around for display after the process is gone.
+#[derive(Debug, Clone)]
+pub struct TargetProcess {
+    process: Process,
+}
+
+impl TargetProcess {
+    pub fn new(process: &Process) -> Self {
+        TargetProcess { process: process.clone() }
+    }
+
+    pub fn key(&self) -> ProcessKey {
+        process_key(&self.process)
+    }
+
+    pub fn process(&self) -> &Process {
+        &self.process
+    }
+
+    pub fn is(&self, process: &Process) -> bool {
+        self.key() == process_key(process)
+    }
+
+    pub fn state(&self, process_list: &[Process]) -> TargetState {
+        if process_list.iter().any(|p| self.is(p)) {
+            TargetState::Running
+        } else {
+            TargetState::Exited
+        }
+    }
+
+    // Query the live process behind the target right now. Fails if it has
+    // exited, or if its PID has been handed to a different process since.
+    pub fn resolve_live(&self) -> Result<Process, TargetError> {
+        match get_process_ex(self.process.pid) {
+            None => {
+                Err(TargetError::Exited { pid: self.process.pid, name: self.process.name.clone() })
+            },
+            Some(live) if !self.is(&live) => Err(TargetError::PidReused {
+                pid: self.process.pid,
+                name: self.process.name.clone(),
+                new_name: live.name,
+            }),
+            Some(live) => Ok(live),
+        }
+    }
+}
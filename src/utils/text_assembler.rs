@@ -0,0 +1,1514 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use iced_x86::{
+    BlockEncoder, BlockEncoderOptions, BlockEncoderResult, Code, Decoder, DecoderOptions, Encoder,
+    Formatter, Instruction, InstructionBlock, IntelFormatter, MemorySizeOptions, Mnemonic,
+    OpCodeOperandKind, OpKind, Register, SymbolResolver, SymbolResult,
+};
+
+// A small Intel syntax assembler on top of iced-x86's encoder, for the
+// shellcode workbench. iced has no text parser: each line is matched against
+// the `Code`s of its mnemonic by operand kinds and the shortest encoding wins.
+// Branches go through BlockEncoder, which resolves labels and picks short or
+// near forms.
+//
+//     start:  mov eax, [esp+4]      ; comments start with a semicolon
+//             test eax, eax
+//             jz done
+//             mov eax, dword ptr fs:[0x30]
+//     done:   ret 4
+//             db "text", 0
+
+// Branches to a label target the fake IP of the labeled instruction until
+// BlockEncoder places the code, like CodeAssembler does with label ids.
+const LABEL_IP_BASE: u64 = 0xfff0_0000;
+// Second base the code is assembled at to find position dependent lines.
+const PROBE_OFFSET: u64 = 0x0100_0000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    // 1-based source line, 0 for errors not tied to a line.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            0 => write!(f, "{}", self.message),
+            line => write!(f, "line {}: {}", line, self.message),
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Debug, Clone)]
+pub struct AssembledInstruction {
+    pub line: usize,
+    pub offset: usize,
+    pub len: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Relocation {
+    pub offset: usize,
+    pub size: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Assembly {
+    pub bytes: Vec<u8>,
+    pub instructions: Vec<AssembledInstruction>,
+    // Absolute addresses in the code that a loader would have to fix up.
+    pub relocations: Vec<Relocation>,
+    // Lines whose bytes change with the address the code is placed at.
+    pub position_dependent_lines: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DisassembledInstruction {
+    pub address: u64,
+    pub bytes: Vec<u8>,
+    pub text: String,
+}
+
+pub fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" ")
+}
+
+// "90 90 c3", "9090c3", "0x90, 0x1" or "\x90\x90". Every 0x or \x number is
+// one byte; a bare run of digits is read in pairs.
+pub fn parse_hex_bytes(text: &str) -> Option<Vec<u8>> {
+    fn byte(digits: &str) -> Option<u8> {
+        let valid = (1..=2).contains(&digits.len())
+            && digits.bytes().all(|digit| digit.is_ascii_hexdigit());
+        valid.then(|| u8::from_str_radix(digits, 16).ok()).flatten()
+    }
+    let mut bytes = Vec::new();
+    for token in text.split(|c: char| c.is_ascii_whitespace() || c == ',') {
+        if let Some(escaped) = token.strip_prefix("\\x") {
+            for digits in escaped.split("\\x") {
+                bytes.push(byte(digits)?);
+            }
+        } else if let Some(digits) = token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+            bytes.push(byte(digits)?);
+        } else if token.len() > 2 && token.len() % 2 == 0 {
+            for pair in token.as_bytes().chunks(2) {
+                bytes.push(byte(std::str::from_utf8(pair).ok()?)?);
+            }
+        } else if !token.is_empty() {
+            bytes.push(byte(token)?);
+        }
+    }
+    (!bytes.is_empty()).then_some(bytes)
+}
+
+// Formatted so that the output assembles again.
+fn formatter() -> IntelFormatter {
+    formatter_with(None)
+}
+
+fn formatter_with(resolver: Option<Box<dyn SymbolResolver>>) -> IntelFormatter {
+    let mut formatter = IntelFormatter::with_options(resolver, None);
+    let options = formatter.options_mut();
+    options.set_hex_prefix("0x");
+    options.set_hex_suffix("");
+    options.set_uppercase_hex(false);
+    options.set_leading_zeros(false);
+    options.set_branch_leading_zeros(false);
+    options.set_show_branch_size(false);
+    options.set_space_after_operand_separator(true);
+    formatter
+}
+
+pub fn disassemble(bytes: &[u8], bitness: u32, base: u64) -> Vec<DisassembledInstruction> {
+    let mut decoder = Decoder::with_ip(bitness, bytes, base, DecoderOptions::NONE);
+    let mut formatter = formatter();
+    let mut instruction = Instruction::default();
+    let mut lines = Vec::new();
+    while decoder.can_decode() {
+        decoder.decode_out(&mut instruction);
+        let offset = (instruction.ip() - base) as usize;
+        let mut text = String::new();
+        if instruction.is_invalid() {
+            text.push_str("(bad)");
+        } else {
+            formatter.format(&instruction, &mut text);
+        }
+        lines.push(DisassembledInstruction {
+            address: instruction.ip(),
+            bytes: bytes[offset..offset + instruction.len()].to_vec(),
+            text,
+        });
+    }
+    lines
+}
+
+// Names branch targets inside the disassembled bytes, so the source that
+// comes out of `disassemble_to_source` still works when assembled elsewhere.
+struct LabelResolver {
+    labels: HashMap<u64, String>,
+}
+
+impl SymbolResolver for LabelResolver {
+    fn symbol(
+        &mut self,
+        _instruction: &Instruction,
+        _operand: u32,
+        instruction_operand: Option<u32>,
+        address: u64,
+        _address_size: u32,
+    ) -> Option<SymbolResult<'_>> {
+        instruction_operand?;
+        self.labels.get(&address).map(|label| SymbolResult::with_str(address, label.as_str()))
+    }
+}
+
+// Disassembles `bytes` into source `assemble` accepts, with labels for the
+// branches that stay inside the code. Invalid bytes become db lines.
+pub fn disassemble_to_source(bytes: &[u8], bitness: u32, base: u64) -> String {
+    let mut decoder = Decoder::with_ip(bitness, bytes, base, DecoderOptions::NONE);
+    let instructions: Vec<Instruction> = decoder.iter().collect();
+    let starts: Vec<u64> = instructions.iter().map(|instruction| instruction.ip()).collect();
+    let labels: HashMap<u64, String> = instructions
+        .iter()
+        .filter(|instruction| {
+            instruction.is_ip_rel_memory_operand() || instruction.near_branch_target() != 0
+        })
+        .map(|instruction| {
+            if instruction.is_ip_rel_memory_operand() {
+                instruction.ip_rel_memory_address()
+            } else {
+                instruction.near_branch_target()
+            }
+        })
+        .filter(|target| starts.contains(target))
+        .map(|target| (target, format!("loc_{:x}", target - base)))
+        .collect();
+
+    let mut formatter = formatter_with(Some(Box::new(LabelResolver { labels: labels.clone() })));
+    // Spelled out sizes keep `nop dword ptr [rax]` and friends unambiguous.
+    formatter.options_mut().set_memory_size_options(MemorySizeOptions::Always);
+    let mut source = String::new();
+    for instruction in &instructions {
+        if let Some(label) = labels.get(&instruction.ip()) {
+            source.push_str(label);
+            source.push_str(":\n");
+        }
+        source.push_str("    ");
+        if instruction.is_invalid() {
+            let offset = (instruction.ip() - base) as usize;
+            let invalid = &bytes[offset..offset + instruction.len()];
+            let values: Vec<String> =
+                invalid.iter().map(|byte| format!("0x{:02x}", byte)).collect();
+            source.push_str(&format!("db {}", values.join(", ")));
+        } else {
+            formatter.format(instruction, &mut source);
+        }
+        source.push('\n');
+    }
+    source
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Operand {
+    Register(Register),
+    Immediate(i128),
+    Memory {
+        size: Option<usize>,
+        segment: Register,
+        base: Register,
+        index: Register,
+        scale: u32,
+        displacement: i64,
+        // [label]: RIP-relative in 64-bit code, absolute in 32-bit code.
+        label: Option<String>,
+    },
+    Label(String),
+}
+
+fn register_names() -> HashMap<String, Register> {
+    Register::values()
+        .filter(|register| *register != Register::None)
+        .map(|register| (format!("{:?}", register).to_lowercase(), register))
+        .collect()
+}
+
+// 0x10, 10h, 16 or -16.
+fn parse_number(text: &str) -> Option<i128> {
+    let text = text.trim().replace('_', "");
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest.trim().to_owned()),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()?
+    } else if let Some(hex) =
+        text.strip_suffix(['h', 'H']).filter(|hex| hex.starts_with(|c: char| c.is_ascii_digit()))
+    {
+        u64::from_str_radix(hex, 16).ok()?
+    } else {
+        text.parse::<u64>().ok()?
+    } as i128;
+    Some(if negative { -value } else { value })
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.' || c == '@')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || "_.@$".contains(c))
+}
+
+// Conditional jumps have several names; iced knows one of each.
+fn canonical_mnemonic(mnemonic: &str) -> &str {
+    match mnemonic {
+        "jz" => "je",
+        "jnz" => "jne",
+        "jc" | "jnae" => "jb",
+        "jnc" | "jnb" => "jae",
+        "jna" => "jbe",
+        "jnbe" => "ja",
+        "jnge" => "jl",
+        "jnl" => "jge",
+        "jng" => "jle",
+        "jnle" => "jg",
+        "jpe" => "jp",
+        "jpo" => "jnp",
+        "retn" => "ret",
+        "xlat" => "xlatb",
+        other => other,
+    }
+}
+
+struct Parser {
+    registers: HashMap<String, Register>,
+    mnemonics: HashMap<String, Mnemonic>,
+}
+
+impl Parser {
+    fn new() -> Self {
+        Parser {
+            registers: register_names(),
+            mnemonics: Mnemonic::values()
+                .map(|mnemonic| (format!("{:?}", mnemonic).to_lowercase(), mnemonic))
+                .collect(),
+        }
+    }
+
+    fn register(&self, text: &str) -> Option<Register> {
+        // st(1) is st1 to iced.
+        let name: String =
+            text.trim().to_lowercase().chars().filter(|c| *c != '(' && *c != ')').collect();
+        match name.as_str() {
+            "st" => Some(Register::ST0),
+            // r8b is r8l to iced.
+            _ => match name.strip_suffix('b').filter(|name| name.starts_with('r')) {
+                Some(prefix) => self.registers.get(&format!("{}l", prefix)).copied(),
+                None => self.registers.get(&name).copied(),
+            },
+        }
+    }
+
+    fn memory(
+        &self,
+        text: &str,
+        size: Option<usize>,
+        segment: Register,
+    ) -> Result<Operand, String> {
+        let mut base = Register::None;
+        let mut index = Register::None;
+        let mut scale = 1;
+        let mut displacement: i64 = 0;
+        let mut label = None;
+        // Split on + and -, keeping the sign with each term.
+        let mut terms = Vec::new();
+        let mut start = 0;
+        for (position, c) in text.char_indices() {
+            if (c == '+' || c == '-') && position > 0 {
+                terms.push(&text[start..position]);
+                start = position;
+            }
+        }
+        terms.push(&text[start..]);
+
+        for term in terms {
+            let term = term.trim();
+            let (negative, term) = match term.strip_prefix('-') {
+                Some(rest) => (true, rest.trim()),
+                None => (false, term.trim_start_matches('+').trim()),
+            };
+            if let Some((left, right)) = term.split_once('*') {
+                let (register, factor) = match (self.register(left), self.register(right)) {
+                    (Some(register), None) => (register, right),
+                    (None, Some(register)) => (register, left),
+                    _ => return Err(format!("invalid scaled index {}", term)),
+                };
+                if negative || index != Register::None {
+                    return Err(format!("invalid index {}", term));
+                }
+                index = register;
+                scale = match parse_number(factor) {
+                    Some(factor @ (1 | 2 | 4 | 8)) => factor as u32,
+                    _ => return Err(format!("scale must be 1, 2, 4 or 8, not {}", factor)),
+                };
+            } else if let Some(register) = self.register(term) {
+                if negative {
+                    return Err(format!("cannot subtract register {}", term));
+                }
+                if base == Register::None {
+                    base = register;
+                } else if index == Register::None {
+                    index = register;
+                } else {
+                    return Err(format!("too many registers in [{}]", text));
+                }
+            } else if is_identifier(term) && parse_number(term).is_none() {
+                if negative || label.is_some() {
+                    return Err(format!("invalid label reference {}", term));
+                }
+                label = Some(term.to_owned());
+            } else {
+                let value =
+                    parse_number(term).ok_or_else(|| format!("invalid address term {}", term))?;
+                let value = if negative { -value } else { value };
+                displacement = displacement.wrapping_add(value as i64);
+            }
+        }
+        if label.is_some() {
+            if !matches!(base, Register::None | Register::RIP)
+                || index != Register::None
+                || displacement != 0
+            {
+                return Err(format!("a label cannot be combined with other terms in [{}]", text));
+            }
+        } else if base == Register::RIP {
+            return Err("RIP-relative operands need a label, e.g. [rip+data]".to_owned());
+        }
+        Ok(Operand::Memory { size, segment, base, index, scale, displacement, label })
+    }
+
+    fn operand(&self, text: &str) -> Result<Operand, String> {
+        let mut text = text.trim();
+        let mut size = None;
+        for (keyword, bytes) in [
+            ("byte", 1),
+            ("word", 2),
+            ("dword", 4),
+            ("fword", 6),
+            ("qword", 8),
+            ("tword", 10),
+            ("tbyte", 10),
+            ("xmmword", 16),
+            ("oword", 16),
+            ("ymmword", 32),
+            ("zmmword", 64),
+        ] {
+            if let Some(rest) = strip_keyword(text, keyword)
+                .filter(|rest| rest.starts_with(' ') || rest.starts_with('['))
+            {
+                size = Some(bytes);
+                let rest = rest.trim_start();
+                text = strip_keyword(rest, "ptr").map_or(rest, str::trim);
+                break;
+            }
+        }
+        for keyword in ["short ", "near "] {
+            if let Some(rest) = strip_keyword(text, keyword) {
+                text = rest.trim();
+            }
+        }
+        let mut text = text.to_owned();
+
+        // fs:[0x30], or fs:0x30 for an absolute address.
+        let mut segment = Register::None;
+        if let Some((prefix, rest)) = text.split_once(':') {
+            if let Some(register) =
+                self.register(prefix).filter(|register| register.is_segment_register())
+            {
+                segment = register;
+                text = rest.trim().to_owned();
+                if !text.starts_with('[') {
+                    text = format!("[{}]", text);
+                }
+            }
+        }
+
+        if let Some(inner) = text.strip_prefix('[').and_then(|text| text.strip_suffix(']')) {
+            return self.memory(inner, size, segment);
+        }
+        if size.is_some() {
+            return Err(format!("{} is not a memory operand", text));
+        }
+        if let Some(register) = self.register(&text) {
+            return Ok(Operand::Register(register));
+        }
+        if let Some(value) = parse_number(&text) {
+            return Ok(Operand::Immediate(value));
+        }
+        if is_identifier(&text) {
+            return Ok(Operand::Label(text));
+        }
+        Err(format!("cannot parse operand {}", text))
+    }
+}
+
+// `text` without a leading ASCII `keyword` in any case. Only ASCII is folded,
+// so the rest keeps its byte offsets.
+fn strip_keyword<'a>(text: &'a str, keyword: &str) -> Option<&'a str> {
+    text.get(..keyword.len())
+        .filter(|prefix| prefix.eq_ignore_ascii_case(keyword))
+        .map(|_| &text[keyword.len()..])
+}
+
+// Splits on commas outside of string literals.
+fn split_operands(text: &str) -> Vec<String> {
+    let mut operands = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    for c in text.chars() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            (',', None) => {
+                operands.push(current.trim().to_owned());
+                current.clear();
+                continue;
+            },
+            _ => {},
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        operands.push(current.trim().to_owned());
+    }
+    operands
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (position, c) in line.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            (';', None) => return &line[..position],
+            _ => {},
+        }
+    }
+    line
+}
+
+// db/dw/dd/dq. Strings are only allowed in db.
+fn declare_data(directive: &str, operands: &[String]) -> Result<Vec<Instruction>, String> {
+    let mut values: Vec<i128> = Vec::new();
+    for operand in operands {
+        let quoted = operand
+            .strip_prefix('"')
+            .and_then(|text| text.strip_suffix('"'))
+            .or_else(|| operand.strip_prefix('\'').and_then(|text| text.strip_suffix('\'')));
+        match quoted {
+            Some(text) if directive == "db" => values.extend(text.bytes().map(i128::from)),
+            Some(_) => return Err(format!("strings are only allowed in db, not {}", directive)),
+            None => values
+                .push(parse_number(operand).ok_or_else(|| format!("invalid value {}", operand))?),
+        }
+    }
+    let (size, chunk) = match directive {
+        "db" => (1, 16),
+        "dw" => (2, 8),
+        "dd" => (4, 4),
+        _ => (8, 2),
+    };
+    let limit = 1i128 << (size * 8);
+    if let Some(value) = values.iter().find(|value| **value >= limit || **value < -(limit / 2)) {
+        return Err(format!("{} does not fit in {} bytes", value, size));
+    }
+    values
+        .chunks(chunk)
+        .map(|chunk| {
+            let result = match size {
+                1 => Instruction::with_declare_byte(
+                    &chunk.iter().map(|value| *value as u8).collect::<Vec<_>>(),
+                ),
+                2 => Instruction::with_declare_word(
+                    &chunk.iter().map(|value| *value as u16).collect::<Vec<_>>(),
+                ),
+                4 => Instruction::with_declare_dword(
+                    &chunk.iter().map(|value| *value as u32).collect::<Vec<_>>(),
+                ),
+                _ => Instruction::with_declare_qword(
+                    &chunk.iter().map(|value| *value as u64).collect::<Vec<_>>(),
+                ),
+            };
+            result.map_err(|err| err.to_string())
+        })
+        .collect()
+}
+
+fn is_string_operand(kind: OpCodeOperandKind) -> bool {
+    matches!(
+        kind,
+        OpCodeOperandKind::seg_rSI
+            | OpCodeOperandKind::es_rDI
+            | OpCodeOperandKind::seg_rDI
+            | OpCodeOperandKind::seg_rBX_al
+    )
+}
+
+fn is_accumulator(kind: OpCodeOperandKind) -> bool {
+    matches!(
+        kind,
+        OpCodeOperandKind::al
+            | OpCodeOperandKind::ax
+            | OpCodeOperandKind::eax
+            | OpCodeOperandKind::rax
+    )
+}
+
+// The implicit operands of movsb, stosd and friends, and the [ebx+al] of
+// xlatb.
+fn set_string_operand(
+    instruction: &mut Instruction,
+    index: u32,
+    kind: OpCodeOperandKind,
+    bitness: u32,
+) {
+    use OpCodeOperandKind as K;
+    let op_kind = match (kind, bitness) {
+        (K::seg_rSI, 64) => OpKind::MemorySegRSI,
+        (K::seg_rSI, _) => OpKind::MemorySegESI,
+        (K::es_rDI, 64) => OpKind::MemoryESRDI,
+        (K::es_rDI, _) => OpKind::MemoryESEDI,
+        (K::seg_rDI, 64) => OpKind::MemorySegRDI,
+        (K::seg_rDI, _) => OpKind::MemorySegEDI,
+        (K::al | K::ax | K::eax | K::rax, _) => {
+            let register = match kind {
+                K::al => Register::AL,
+                K::ax => Register::AX,
+                K::eax => Register::EAX,
+                _ => Register::RAX,
+            };
+            instruction.set_op_kind(index, OpKind::Register);
+            instruction.set_op_register(index, register);
+            return;
+        },
+        _ => {
+            instruction.set_op_kind(index, OpKind::Memory);
+            instruction.set_memory_base(if bitness == 64 { Register::RBX } else { Register::EBX });
+            instruction.set_memory_index(Register::AL);
+            return;
+        },
+    };
+    instruction.set_op_kind(index, op_kind);
+}
+
+fn is_memory_kind(kind: OpCodeOperandKind) -> bool {
+    use OpCodeOperandKind as K;
+    matches!(
+        kind,
+        K::mem
+            | K::mem_mpx
+            | K::mem_mib
+            | K::sibmem
+            | K::r8_or_mem
+            | K::r16_or_mem
+            | K::r32_or_mem
+            | K::r32_or_mem_mpx
+            | K::r64_or_mem
+            | K::r64_or_mem_mpx
+            | K::mm_or_mem
+            | K::xmm_or_mem
+            | K::ymm_or_mem
+            | K::zmm_or_mem
+            | K::bnd_or_mem_mpx
+            | K::k_or_mem
+    )
+}
+
+fn fits(value: i128, min: i128, max: i128) -> bool {
+    (min..=max).contains(&value)
+}
+
+// Sets operand `index` of `instruction` if `operand` can be encoded as
+// `kind`. Branch targets are set to `branch_target`, labels are patched in
+// later.
+fn set_operand(
+    instruction: &mut Instruction,
+    index: u32,
+    kind: OpCodeOperandKind,
+    operand: &Operand,
+    bitness: u32,
+    branch_target: u64,
+) -> bool {
+    use OpCodeOperandKind as K;
+    let mut set_register = |register: Register| {
+        instruction.set_op_kind(index, OpKind::Register);
+        instruction.set_op_register(index, register);
+        true
+    };
+    match (kind, operand) {
+        (K::r8_or_mem | K::r8_reg | K::r8_opcode, Operand::Register(r)) if r.is_gpr8() => {
+            set_register(*r)
+        },
+        (
+            K::r16_or_mem | K::r16_reg | K::r16_reg_mem | K::r16_rm | K::r16_opcode,
+            Operand::Register(r),
+        ) if r.is_gpr16() => set_register(*r),
+        (
+            K::r32_or_mem
+            | K::r32_or_mem_mpx
+            | K::r32_reg
+            | K::r32_reg_mem
+            | K::r32_rm
+            | K::r32_opcode
+            | K::r32_vvvv,
+            Operand::Register(r),
+        ) if r.is_gpr32() => set_register(*r),
+        (
+            K::r64_or_mem
+            | K::r64_or_mem_mpx
+            | K::r64_reg
+            | K::r64_reg_mem
+            | K::r64_rm
+            | K::r64_opcode
+            | K::r64_vvvv,
+            Operand::Register(r),
+        ) if r.is_gpr64() => set_register(*r),
+        (K::seg_reg, Operand::Register(r)) if r.is_segment_register() => set_register(*r),
+        (K::k_or_mem | K::k_reg | K::kp1_reg | K::k_rm | K::k_vvvv, Operand::Register(r))
+            if r.is_k() =>
+        {
+            set_register(*r)
+        },
+        (K::mm_or_mem | K::mm_reg | K::mm_rm, Operand::Register(r)) if r.is_mm() => {
+            set_register(*r)
+        },
+        (
+            K::xmm_or_mem
+            | K::xmm_reg
+            | K::xmm_rm
+            | K::xmm_vvvv
+            | K::xmmp3_vvvv
+            | K::xmm_is4
+            | K::xmm_is5,
+            Operand::Register(r),
+        ) if r.is_xmm() => set_register(*r),
+        (
+            K::ymm_or_mem | K::ymm_reg | K::ymm_rm | K::ymm_vvvv | K::ymm_is4 | K::ymm_is5,
+            Operand::Register(r),
+        ) if r.is_ymm() => set_register(*r),
+        (
+            K::zmm_or_mem | K::zmm_reg | K::zmm_rm | K::zmm_vvvv | K::zmmp3_vvvv,
+            Operand::Register(r),
+        ) if r.is_zmm() => set_register(*r),
+        (K::tmm_reg | K::tmm_rm | K::tmm_vvvv, Operand::Register(r)) if r.is_tmm() => {
+            set_register(*r)
+        },
+        (K::bnd_or_mem_mpx | K::bnd_reg, Operand::Register(r)) if r.is_bnd() => set_register(*r),
+        (K::cr_reg, Operand::Register(r)) if r.is_cr() => set_register(*r),
+        (K::dr_reg, Operand::Register(r)) if r.is_dr() => set_register(*r),
+        (K::tr_reg, Operand::Register(r)) if r.is_tr() => set_register(*r),
+        (K::sti_opcode, Operand::Register(r)) if r.is_st() => set_register(*r),
+        (K::es, Operand::Register(Register::ES))
+        | (K::cs, Operand::Register(Register::CS))
+        | (K::ss, Operand::Register(Register::SS))
+        | (K::ds, Operand::Register(Register::DS))
+        | (K::fs, Operand::Register(Register::FS))
+        | (K::gs, Operand::Register(Register::GS))
+        | (K::al, Operand::Register(Register::AL))
+        | (K::cl, Operand::Register(Register::CL))
+        | (K::ax, Operand::Register(Register::AX))
+        | (K::dx, Operand::Register(Register::DX))
+        | (K::eax, Operand::Register(Register::EAX))
+        | (K::rax, Operand::Register(Register::RAX))
+        | (K::st0, Operand::Register(Register::ST0)) => {
+            if let Operand::Register(register) = operand {
+                set_register(*register);
+            }
+            true
+        },
+
+        (
+            _,
+            Operand::Memory {
+                segment,
+                base,
+                index: index_register,
+                scale,
+                displacement,
+                label,
+                ..
+            },
+        ) if is_memory_kind(kind)
+            || kind == K::mem_offs
+                && *base == Register::None
+                && *index_register == Register::None =>
+        {
+            instruction.set_op_kind(index, OpKind::Memory);
+            instruction.set_segment_prefix(*segment);
+            if label.is_some() {
+                // The target is set once the labels are placed.
+                instruction.set_memory_base(if bitness == 64 {
+                    Register::RIP
+                } else {
+                    Register::None
+                });
+                instruction.set_memory_displacement64(branch_target);
+                instruction.set_memory_displ_size(if bitness == 64 { 1 } else { 4 });
+                return true;
+            }
+            let address_size = if base.is_gpr64() || index_register.is_gpr64() {
+                64
+            } else if base.is_gpr16() || index_register.is_gpr16() {
+                16
+            } else {
+                bitness
+            };
+            let no_registers = *base == Register::None && *index_register == Register::None;
+            instruction.set_memory_base(*base);
+            instruction.set_memory_index(*index_register);
+            instruction.set_memory_index_scale(*scale);
+            instruction.set_memory_displacement64(match address_size {
+                64 => *displacement as u64,
+                16 => *displacement as u16 as u64,
+                _ => *displacement as u32 as u64,
+            });
+            // Like CodeAssembler: the encoder picks the real size.
+            instruction.set_memory_displ_size(if no_registers {
+                address_size / 8
+            } else if *displacement != 0 {
+                1
+            } else {
+                0
+            });
+            true
+        },
+
+        (K::imm8 | K::imm8_const_1, Operand::Immediate(value)) => {
+            if kind == K::imm8_const_1 && *value != 1 || !fits(*value, -0x80, 0xff) {
+                return false;
+            }
+            // enter's second immediate.
+            if index > 0 && instruction.op_kind(index - 1) == OpKind::Immediate16 {
+                instruction.set_op_kind(index, OpKind::Immediate8_2nd);
+                instruction.set_immediate8_2nd(*value as u8);
+            } else {
+                instruction.set_op_kind(index, OpKind::Immediate8);
+                instruction.set_immediate8(*value as u8);
+            }
+            true
+        },
+        (K::imm8sex16, Operand::Immediate(value))
+            if fits(*value, -0x80, 0x7f) || fits(*value, 0xff80, 0xffff) =>
+        {
+            instruction.set_op_kind(index, OpKind::Immediate8to16);
+            instruction.set_immediate8to16(*value as i8 as i16);
+            true
+        },
+        (K::imm8sex32, Operand::Immediate(value))
+            if fits(*value, -0x80, 0x7f) || fits(*value, 0xffff_ff80, 0xffff_ffff) =>
+        {
+            instruction.set_op_kind(index, OpKind::Immediate8to32);
+            instruction.set_immediate8to32(*value as i8 as i32);
+            true
+        },
+        (K::imm8sex64, Operand::Immediate(value))
+            if fits(*value, -0x80, 0x7f)
+                || fits(*value, 0xffff_ffff_ffff_ff80, 0xffff_ffff_ffff_ffff) =>
+        {
+            instruction.set_op_kind(index, OpKind::Immediate8to64);
+            instruction.set_immediate8to64(*value as i8 as i64);
+            true
+        },
+        (K::imm16, Operand::Immediate(value)) if fits(*value, -0x8000, 0xffff) => {
+            instruction.set_op_kind(index, OpKind::Immediate16);
+            instruction.set_immediate16(*value as u16);
+            true
+        },
+        (K::imm32, Operand::Immediate(value)) if fits(*value, -0x8000_0000, 0xffff_ffff) => {
+            instruction.set_op_kind(index, OpKind::Immediate32);
+            instruction.set_immediate32(*value as u32);
+            true
+        },
+        (K::imm32sex64, Operand::Immediate(value))
+            if fits(*value, -0x8000_0000, 0x7fff_ffff)
+                || fits(*value, 0xffff_ffff_8000_0000, 0xffff_ffff_ffff_ffff) =>
+        {
+            instruction.set_op_kind(index, OpKind::Immediate32to64);
+            instruction.set_immediate32to64(*value as i64 as i32 as i64);
+            true
+        },
+        (K::imm64, Operand::Immediate(value))
+            if fits(*value, i64::MIN as i128, u64::MAX as i128) =>
+        {
+            instruction.set_op_kind(index, OpKind::Immediate64);
+            instruction.set_immediate64(*value as u64);
+            true
+        },
+
+        // The address of a label, e.g. `push message`. Always full width
+        // since the address is not known yet.
+        (K::imm32, Operand::Label(_)) if bitness == 32 => {
+            instruction.set_op_kind(index, OpKind::Immediate32);
+            instruction.set_immediate32(branch_target as u32);
+            true
+        },
+        (K::imm64, Operand::Label(_)) if bitness == 64 => {
+            instruction.set_op_kind(index, OpKind::Immediate64);
+            instruction.set_immediate64(branch_target);
+            true
+        },
+        (K::br32_1 | K::br32_4, Operand::Immediate(_) | Operand::Label(_)) if bitness == 32 => {
+            let target = match operand {
+                Operand::Immediate(value) if fits(*value, 0, 0xffff_ffff) => *value as u64,
+                Operand::Immediate(_) => return false,
+                _ => branch_target,
+            };
+            instruction.set_op_kind(index, OpKind::NearBranch32);
+            instruction.set_near_branch32(target as u32);
+            true
+        },
+        (K::br64_1 | K::br64_4, Operand::Immediate(_) | Operand::Label(_)) if bitness == 64 => {
+            let target = match operand {
+                Operand::Immediate(value) if fits(*value, 0, u64::MAX as i128) => *value as u64,
+                Operand::Immediate(_) => return false,
+                _ => branch_target,
+            };
+            instruction.set_op_kind(index, OpKind::NearBranch64);
+            instruction.set_near_branch64(target);
+            true
+        },
+        _ => false,
+    }
+}
+
+fn is_branch(kind: OpCodeOperandKind) -> bool {
+    matches!(
+        kind,
+        OpCodeOperandKind::br32_1
+            | OpCodeOperandKind::br32_4
+            | OpCodeOperandKind::br64_1
+            | OpCodeOperandKind::br64_4
+    )
+}
+
+fn is_short_branch(kind: OpCodeOperandKind) -> bool {
+    matches!(kind, OpCodeOperandKind::br32_1 | OpCodeOperandKind::br64_1)
+}
+
+// Operand lists the formatter shortens: `imul eax, 0x64` for
+// `imul eax, eax, 0x64`, `fld st, st(1)` and `fstp st(2), st` for the one
+// operand x87 forms and a bare `fxch` for `fxch st(1)`.
+fn expand_shorthand(kinds: &[OpCodeOperandKind], operands: &[Operand]) -> Option<Vec<Operand>> {
+    use OpCodeOperandKind as K;
+    const ST0: Operand = Operand::Register(Register::ST0);
+    match operands {
+        [destination, Operand::Immediate(value)]
+            if kinds.len() == 3 && matches!(destination, Operand::Register(_)) =>
+        {
+            Some(vec![destination.clone(), destination.clone(), Operand::Immediate(*value)])
+        },
+        [ST0, rest @ ..] | [rest @ .., ST0] if kinds.len() == rest.len() => Some(rest.to_vec()),
+        [] if kinds.contains(&K::sti_opcode)
+            && kinds.iter().all(|kind| matches!(kind, K::sti_opcode | K::st0)) =>
+        {
+            Some(
+                kinds
+                    .iter()
+                    .map(|kind| match kind {
+                        K::sti_opcode => Operand::Register(Register::ST1),
+                        _ => ST0,
+                    })
+                    .collect(),
+            )
+        },
+        _ => None,
+    }
+}
+
+struct Prefixes {
+    lock: bool,
+    rep: bool,
+    repne: bool,
+}
+
+// Picks the shortest encoding of `mnemonic` with `operands`. Near branches
+// are preferred over short ones; BlockEncoder shortens them where it can.
+fn encode_instruction(
+    mnemonic: Mnemonic,
+    operands: &[Operand],
+    prefixes: &Prefixes,
+    bitness: u32,
+    base: u64,
+) -> Result<Instruction, String> {
+    let mut encoder = Encoder::new(bitness);
+    let mut best: Option<((bool, usize), Instruction)> = None;
+    let mut memory_sizes = Vec::new();
+    let explicit_size = operands.iter().find_map(|operand| match operand {
+        Operand::Memory { size, .. } => *size,
+        _ => None,
+    });
+    let has_memory = operands.iter().any(|operand| matches!(operand, Operand::Memory { .. }));
+    // An operand size prefix is often shorter but truncates immediates and
+    // branch targets, so 16-bit forms need a 16-bit operand to ask for them.
+    let wants_16_bit = explicit_size == Some(2)
+        || operands
+            .iter()
+            .any(|operand| matches!(operand, Operand::Register(register) if register.is_gpr16()));
+
+    for code in Code::values().filter(|code| code.mnemonic() == mnemonic) {
+        let op_code = code.op_code();
+        let mode = match bitness {
+            64 => op_code.mode64(),
+            _ => op_code.mode32(),
+        };
+        if !op_code.is_instruction() || !mode || (op_code.operand_size() == 16 && !wants_16_bit) {
+            continue;
+        }
+        let kinds: Vec<OpCodeOperandKind> =
+            (0..op_code.op_count()).map(|index| op_code.op_kind(index)).collect();
+        // `lodsb` or, as the formatter writes it, `lodsb [esi]`.
+        let implicit_strings = operands.len() <= kinds.len()
+            && operands.iter().all(|operand| match operand {
+                Operand::Memory { label, .. } => label.is_none(),
+                Operand::Register(register) => register.is_gpr(),
+                _ => false,
+            })
+            && kinds.iter().any(|kind| is_string_operand(*kind))
+            && kinds.iter().all(|kind| is_string_operand(*kind) || is_accumulator(*kind));
+        let expanded = expand_shorthand(&kinds, operands);
+        let operands = expanded.as_deref().unwrap_or(operands);
+        if kinds.len() != operands.len() && !implicit_strings {
+            continue;
+        }
+
+        let mut instruction = Instruction::default();
+        instruction.set_code(code);
+        instruction.set_has_lock_prefix(prefixes.lock);
+        instruction.set_has_rep_prefix(prefixes.rep);
+        instruction.set_has_repne_prefix(prefixes.repne);
+        let all_set = if implicit_strings {
+            for (index, kind) in kinds.iter().enumerate() {
+                set_string_operand(&mut instruction, index as u32, *kind, bitness);
+            }
+            true
+        } else {
+            kinds.iter().zip(operands).enumerate().all(|(index, (kind, operand))| {
+                set_operand(&mut instruction, index as u32, *kind, operand, bitness, base)
+            })
+        };
+        if !all_set {
+            continue;
+        }
+
+        let memory_size = instruction.memory_size().size();
+        if has_memory && memory_size != 0 {
+            match explicit_size {
+                Some(size) if size != memory_size => continue,
+                _ => {},
+            }
+        }
+        let Ok(len) = encoder.encode(&instruction, base) else {
+            continue;
+        };
+        if has_memory && memory_size != 0 && !memory_sizes.contains(&memory_size) {
+            memory_sizes.push(memory_size);
+        }
+        let short_branch = kinds.iter().any(|kind| is_short_branch(*kind))
+            && kinds.iter().any(|kind| is_branch(*kind));
+        let score = (short_branch, len);
+        if best.as_ref().is_none_or(|(best_score, _)| score < *best_score) {
+            best = Some((score, instruction));
+        }
+    }
+
+    if explicit_size.is_none() && memory_sizes.len() > 1 {
+        return Err("operand size is ambiguous, add byte/word/dword/qword ptr".to_owned());
+    }
+    best.map(|(_, instruction)| instruction)
+        .ok_or_else(|| "no encoding matches these operands".to_owned())
+}
+
+// A parsed instruction and what it still needs once all labels are known.
+struct SourceInstruction {
+    line: usize,
+    instruction: Instruction,
+    // Operand index and name of a label branch target.
+    label_target: Option<(u32, String)>,
+}
+
+// A label's address used as an immediate or absolute memory operand.
+struct LabelValue {
+    instruction: usize,
+    operand: u32,
+    // Index of the instruction the label points at.
+    label: usize,
+}
+
+struct Program {
+    instructions: Vec<SourceInstruction>,
+    absolute: Vec<LabelValue>,
+}
+
+fn parse(source: &str, bitness: u32, base: u64) -> Result<Program, AsmError> {
+    let parser = Parser::new();
+    let mut instructions: Vec<SourceInstruction> = Vec::new();
+    // Label name → index of the instruction it points at.
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    let mut pending_labels: Vec<(usize, String)> = Vec::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let number = number + 1;
+        let error = |message: String| AsmError { line: number, message };
+        let mut text = strip_comment(line).trim();
+
+        while let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if !is_identifier(label) || parser.register(label).is_some() {
+                break;
+            }
+            if labels.contains_key(label) || pending_labels.iter().any(|(_, name)| name == label) {
+                return Err(error(format!("label {} is defined twice", label)));
+            }
+            pending_labels.push((number, label.to_owned()));
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let (mut mnemonic, mut rest) =
+            text.split_once(char::is_whitespace).map_or((text, ""), |(m, r)| (m, r.trim()));
+        let mut prefixes = Prefixes { lock: false, rep: false, repne: false };
+        loop {
+            match mnemonic.to_lowercase().as_str() {
+                "lock" => prefixes.lock = true,
+                "rep" | "repe" | "repz" => prefixes.rep = true,
+                "repne" | "repnz" => prefixes.repne = true,
+                _ => break,
+            }
+            (mnemonic, rest) =
+                rest.split_once(char::is_whitespace).map_or((rest, ""), |(m, r)| (m, r.trim()));
+        }
+        let mnemonic = mnemonic.to_lowercase();
+        let operand_texts = split_operands(rest);
+
+        let mut parsed = match mnemonic.as_str() {
+            "db" | "dw" | "dd" | "dq" => declare_data(&mnemonic, &operand_texts)
+                .map_err(error)?
+                .into_iter()
+                .map(|instruction| SourceInstruction {
+                    line: number,
+                    instruction,
+                    label_target: None,
+                })
+                .collect(),
+            _ => {
+                let iced_mnemonic = *parser
+                    .mnemonics
+                    .get(canonical_mnemonic(&mnemonic))
+                    .ok_or_else(|| error(format!("unknown instruction {}", mnemonic)))?;
+                let operands = operand_texts
+                    .iter()
+                    .map(|text| parser.operand(text))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(error)?;
+                let instruction =
+                    encode_instruction(iced_mnemonic, &operands, &prefixes, bitness, base)
+                        .map_err(|message| error(format!("{} {}: {}", mnemonic, rest, message)))?;
+                let label_target =
+                    operands.iter().enumerate().find_map(|(index, operand)| match operand {
+                        Operand::Label(name) | Operand::Memory { label: Some(name), .. } => {
+                            Some((index as u32, name.clone()))
+                        },
+                        _ => None,
+                    });
+                vec![SourceInstruction { line: number, instruction, label_target }]
+            },
+        };
+        for (_, label) in pending_labels.drain(..) {
+            labels.insert(label, instructions.len());
+        }
+        instructions.append(&mut parsed);
+    }
+    if let Some((line, label)) = pending_labels.first() {
+        return Err(AsmError {
+            line: *line,
+            message: format!("label {} has no instruction", label),
+        });
+    }
+
+    // Give labeled instructions their fake IP and point branches at them.
+    let targets: HashMap<usize, u64> =
+        labels.values().map(|index| (*index, LABEL_IP_BASE + *index as u64)).collect();
+    for (index, ip) in &targets {
+        instructions[*index].instruction.set_ip(*ip);
+    }
+    // Branches and RIP-relative operands are resolved by BlockEncoder, label
+    // addresses used as values are filled in by `place`.
+    let mut absolute = Vec::new();
+    for (position, source_instruction) in instructions.iter_mut().enumerate() {
+        let Some((operand, label)) = source_instruction.label_target.clone() else {
+            continue;
+        };
+        let index = *labels.get(&label).ok_or_else(|| AsmError {
+            line: source_instruction.line,
+            message: format!("unknown label {}", label),
+        })?;
+        let target = targets[&index];
+        let instruction = &mut source_instruction.instruction;
+        match instruction.op_kind(operand) {
+            OpKind::NearBranch32 => instruction.set_near_branch32(target as u32),
+            OpKind::NearBranch64 => instruction.set_near_branch64(target),
+            OpKind::Memory if instruction.memory_base() == Register::RIP => {
+                instruction.set_memory_displacement64(target)
+            },
+            _ => absolute.push(LabelValue { instruction: position, operand, label: index }),
+        }
+    }
+    Ok(Program { instructions, absolute })
+}
+
+// Encodes the program at `base`. Label values have a fixed size, so the
+// second pass that fills them in does not move anything.
+fn place(
+    program: &Program,
+    bitness: u32,
+    base: u64,
+) -> Result<(BlockEncoderResult, Vec<Relocation>), AsmError> {
+    let mut instructions: Vec<Instruction> =
+        program.instructions.iter().map(|source| source.instruction).collect();
+    let first = encode_block(&instructions, bitness, base)?;
+    if program.absolute.is_empty() {
+        let relocations = block_relocations(program, &first, base);
+        return Ok((first, relocations));
+    }
+    for value in &program.absolute {
+        let address = base.wrapping_add(first.new_instruction_offsets[value.label] as u64);
+        let instruction = &mut instructions[value.instruction];
+        match instruction.op_kind(value.operand) {
+            OpKind::Immediate32 => instruction.set_immediate32(address as u32),
+            OpKind::Immediate64 => instruction.set_immediate64(address),
+            _ => instruction.set_memory_displacement64(address as u32 as u64),
+        }
+    }
+    let result = encode_block(&instructions, bitness, base)?;
+
+    let mut relocations = block_relocations(program, &result, base);
+    let mut encoder = Encoder::new(bitness);
+    for value in &program.absolute {
+        let offset = result.new_instruction_offsets[value.instruction] as usize;
+        let instruction = &instructions[value.instruction];
+        if encoder.encode(instruction, base.wrapping_add(offset as u64)).is_err() {
+            continue;
+        }
+        let constants = encoder.get_constant_offsets();
+        let (field, size) = match instruction.op_kind(value.operand) {
+            OpKind::Memory => (constants.displacement_offset(), constants.displacement_size()),
+            _ => (constants.immediate_offset(), constants.immediate_size()),
+        };
+        relocations.push(Relocation {
+            offset: offset + field,
+            size,
+            line: program.instructions[value.instruction].line,
+        });
+    }
+    relocations.sort_by_key(|relocation| relocation.offset);
+    Ok((result, relocations))
+}
+
+// Relocations BlockEncoder added itself, e.g. for a branch it had to turn
+// into an indirect jump through an absolute address.
+fn block_relocations(program: &Program, result: &BlockEncoderResult, base: u64) -> Vec<Relocation> {
+    result
+        .reloc_infos
+        .iter()
+        .map(|reloc| {
+            let offset = reloc.address.wrapping_sub(base) as usize;
+            let line = result
+                .new_instruction_offsets
+                .iter()
+                .rposition(|start| *start as usize <= offset)
+                .map_or(0, |index| program.instructions[index].line);
+            // Offset64 is the only kind.
+            Relocation { offset, size: 8, line }
+        })
+        .collect()
+}
+
+fn encode_block(
+    instructions: &[Instruction],
+    bitness: u32,
+    base: u64,
+) -> Result<BlockEncoderResult, AsmError> {
+    BlockEncoder::encode(
+        bitness,
+        InstructionBlock::new(instructions, base),
+        BlockEncoderOptions::RETURN_RELOC_INFOS
+            | BlockEncoderOptions::RETURN_NEW_INSTRUCTION_OFFSETS,
+    )
+    .map_err(|err| AsmError { line: 0, message: err.to_string() })
+}
+
+// Assembles `source` to run at `base`. `bitness` is 32 or 64.
+pub fn assemble(source: &str, bitness: u32, base: u64) -> Result<Assembly, AsmError> {
+    let program = parse(source, bitness, base)?;
+    if program.instructions.is_empty() {
+        return Err(AsmError { line: 0, message: "nothing to assemble".to_owned() });
+    }
+    let (result, relocations) = place(&program, bitness, base)?;
+
+    let offsets = &result.new_instruction_offsets;
+    let assembled: Vec<AssembledInstruction> = program
+        .instructions
+        .iter()
+        .enumerate()
+        .map(|(index, source)| {
+            let offset = offsets[index] as usize;
+            let end = offsets.get(index + 1).map_or(result.code_buffer.len(), |end| *end as usize);
+            AssembledInstruction { line: source.line, offset, len: end - offset }
+        })
+        .collect();
+
+    // Same check as the LoadLibraryW stub's: assemble somewhere else and see
+    // which instructions come out different.
+    let probe_base = if bitness == 32 {
+        (base as u32).wrapping_add(PROBE_OFFSET as u32) as u64
+    } else {
+        base.wrapping_add(PROBE_OFFSET)
+    };
+    let (probe, _) = place(&program, bitness, probe_base)?;
+    let mut position_dependent_lines: Vec<usize> = Vec::new();
+    for instruction in &assembled {
+        let range = instruction.offset..instruction.offset + instruction.len;
+        if probe.code_buffer.get(range.clone()) != result.code_buffer.get(range)
+            && !position_dependent_lines.contains(&instruction.line)
+        {
+            position_dependent_lines.push(instruction.line);
+        }
+    }
+    if probe.code_buffer.len() != result.code_buffer.len() && position_dependent_lines.is_empty() {
+        position_dependent_lines.push(0);
+    }
+
+    Ok(Assembly {
+        bytes: result.code_buffer,
+        instructions: assembled,
+        relocations,
+        position_dependent_lines,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use iced_x86::{MemoryOperand, RepPrefixKind};
+
+    use super::*;
+
+    const BASE: u64 = 0x1000_0000;
+
+    // What iced's own encoder makes of `instructions` placed one after
+    // another at BASE.
+    fn encode(bitness: u32, instructions: &[Instruction]) -> Vec<u8> {
+        let mut encoder = Encoder::new(bitness);
+        let mut ip = BASE;
+        for instruction in instructions {
+            ip += encoder.encode(instruction, ip).unwrap() as u64;
+        }
+        encoder.take_buffer()
+    }
+
+    fn assembled(source: &str, bitness: u32) -> Vec<u8> {
+        assemble(source, bitness, BASE).unwrap_or_else(|err| panic!("{}: {}", source, err)).bytes
+    }
+
+    fn error(source: &str, bitness: u32) -> AsmError {
+        match assemble(source, bitness, BASE) {
+            Ok(assembly) => panic!("{} assembled to {}", source, hex_bytes(&assembly.bytes)),
+            Err(err) => err,
+        }
+    }
+
+    fn memory(base: Register, index: Register, scale: u32, displacement: i64) -> MemoryOperand {
+        let displ_size = if displacement == 0 { 0 } else { 1 };
+        MemoryOperand::new(base, index, scale, displacement, displ_size, false, Register::None)
+    }
+
+    #[test]
+    fn lines_match_the_encoder() {
+        use Register::*;
+        let with1 = |code, op: Register| Instruction::with1(code, op).unwrap();
+        let with2 =
+            |code, op0: Register, op1: Register| Instruction::with2(code, op0, op1).unwrap();
+        let load =
+            |code, op0: Register, op1: MemoryOperand| Instruction::with2(code, op0, op1).unwrap();
+        let store =
+            |code, op0: MemoryOperand, op1: Register| Instruction::with2(code, op0, op1).unwrap();
+
+        let table = [
+            (64, "nop", Instruction::with(Code::Nopd)),
+            (64, "ret", Instruction::with(Code::Retnq)),
+            (64, "push rbx", with1(Code::Push_r64, RBX)),
+            (64, "mov rax, rbx", with2(Code::Mov_rm64_r64, RAX, RBX)),
+            (64, "XOR ECX, ECX", with2(Code::Xor_rm32_r32, ECX, ECX)),
+            (64, "mov r8b, r9b", with2(Code::Mov_rm8_r8, R8L, R9L)),
+            (
+                64,
+                "mov eax, 0x1337",
+                Instruction::with2(Code::Mov_r32_imm32, EAX, 0x1337u32).unwrap(),
+            ),
+            (64, "add rsp, 28h", Instruction::with2(Code::Add_rm64_imm8, RSP, 0x28i32).unwrap()),
+            (64, "sub rsp, 4096", Instruction::with2(Code::Sub_rm64_imm32, RSP, 4096i32).unwrap()),
+            (
+                64,
+                "mov rax, 0x1122334455667788",
+                Instruction::with2(Code::Mov_r64_imm64, RAX, 0x1122334455667788u64).unwrap(),
+            ),
+            (64, "mov rcx, [rsp+8]", load(Code::Mov_r64_rm64, RCX, memory(RSP, None, 1, 8))),
+            (
+                64,
+                "mov qword ptr [rax+rcx*8-0x10], rdx",
+                store(Code::Mov_rm64_r64, memory(RAX, RCX, 8, -0x10), RDX),
+            ),
+            (
+                64,
+                "movzx eax, byte ptr [rcx]",
+                load(Code::Movzx_r32_rm8, EAX, memory(RCX, None, 1, 0)),
+            ),
+            (
+                64,
+                "call qword ptr [rax]",
+                Instruction::with1(Code::Call_rm64, memory(RAX, None, 1, 0)).unwrap(),
+            ),
+            (64, "lock xadd [rcx], eax", {
+                let mut instruction = store(Code::Xadd_rm32_r32, memory(RCX, None, 1, 0), EAX);
+                instruction.set_has_lock_prefix(true);
+                instruction
+            }),
+            (64, "rep movsb", Instruction::with_movsb(64, None, RepPrefixKind::Repe).unwrap()),
+            (32, "ret 4", Instruction::with1(Code::Retnd_imm16, 4u32).unwrap()),
+            (32, "inc ecx", with1(Code::Inc_r32, ECX)),
+            (32, "push 0x1234", Instruction::with1(Code::Pushd_imm32, 0x1234i32).unwrap()),
+            (64, "push 0x1234", Instruction::with1(Code::Pushq_imm32, 0x1234i32).unwrap()),
+            (32, "push ax", with1(Code::Push_r16, Register::AX)),
+            (
+                32,
+                "push word ptr [eax]",
+                Instruction::with1(Code::Push_rm16, memory(Register::EAX, Register::None, 1, 0))
+                    .unwrap(),
+            ),
+            (32, "mov eax, [esp+4]", load(Code::Mov_r32_rm32, EAX, memory(ESP, None, 1, 4))),
+            (
+                32,
+                "mov eax, dword ptr fs:[0x30]",
+                load(
+                    Code::Mov_EAX_moffs32,
+                    EAX,
+                    MemoryOperand::new(None, None, 1, 0x30, 4, false, FS),
+                ),
+            ),
+            (32, "fld st(1)", with1(Code::Fld_sti, ST1)),
+        ];
+        for (bitness, source, instruction) in table {
+            assert_eq!(
+                hex_bytes(&assembled(source, bitness)),
+                hex_bytes(&encode(bitness, &[instruction])),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn labels_match_the_encoder() {
+        let branch = |code, target| Instruction::with_branch(code, target).unwrap();
+        assert_eq!(
+            assembled("start: nop\n jmp start", 64),
+            encode(64, &[Instruction::with(Code::Nopd), branch(Code::Jmp_rel8_64, BASE)])
+        );
+        assert_eq!(
+            assembled("jz done\n xor eax, eax\ndone: ret", 64),
+            encode(64, &[
+                branch(Code::Je_rel8_64, BASE + 4),
+                Instruction::with2(Code::Xor_rm32_r32, Register::EAX, Register::EAX).unwrap(),
+                Instruction::with(Code::Retnq),
+            ])
+        );
+        // Too far for rel8, and a rel16 jump would truncate EIP.
+        let mut far = String::from("jmp far\n");
+        for _ in 0..0x80 {
+            far.push_str("nop\n");
+        }
+        far.push_str("far: ret 4");
+        let mut expected = vec![branch(Code::Jmp_rel32_32, BASE + 5 + 0x80)];
+        expected.extend((0..0x80).map(|_| Instruction::with(Code::Nopd)));
+        expected.push(Instruction::with1(Code::Retnd_imm16, 4u32).unwrap());
+        assert_eq!(assembled(&far, 32), encode(32, &expected));
+        // RIP-relative in 64-bit code, an absolute address in 32-bit code.
+        assert_eq!(
+            assembled("lea rcx, [rip+data]\n ret\ndata: dd 0x11223344", 64),
+            encode(64, &[
+                Instruction::with2(
+                    Code::Lea_r64_m,
+                    Register::RCX,
+                    MemoryOperand::with_base_displ(Register::RIP, (BASE + 8) as i64),
+                )
+                .unwrap(),
+                Instruction::with(Code::Retnq),
+                Instruction::with_declare_dword_1(0x11223344),
+            ])
+        );
+        assert_eq!(
+            assembled("push data\n ret 4\ndata: db 'ok', 0", 32),
+            encode(32, &[
+                Instruction::with1(Code::Pushd_imm32, (BASE + 8) as u32).unwrap(),
+                Instruction::with1(Code::Retnd_imm16, 4u32).unwrap(),
+                Instruction::with_declare_byte(b"ok\0").unwrap(),
+            ])
+        );
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let table = [
+            ("", 0, "nothing to assemble"),
+            ("nop\nfrob eax", 2, "unknown instruction frob"),
+            ("mov eax, [ecx*3]", 1, "scale must be 1, 2, 4 or 8"),
+            ("jmp nowhere", 1, "unknown label nowhere"),
+            ("a: nop\na: nop", 2, "label a is defined twice"),
+            ("nop\nend:", 2, "label end has no instruction"),
+            ("mov eax, [rip+8]", 1, "RIP-relative operands need a label"),
+            ("mov eax, byte eax", 1, "eax is not a memory operand"),
+            // Case folding that changes the length of the text must not
+            // break slicing.
+            ("inc byte [İİİ]", 1, "invalid address term İİİ"),
+            ("jmp \u{212a}short x", 1, "cannot parse operand"),
+        ];
+        for (source, line, message) in table {
+            let err = error(source, 64);
+            assert_eq!(err.line, line, "{}: {}", source, err);
+            assert!(err.message.contains(message), "{}: {}", source, err);
+        }
+    }
+
+    #[test]
+    fn hex_bytes_are_one_byte_per_token() {
+        let table = [
+            ("90 90 c3", Some(vec![0x90, 0x90, 0xc3])),
+            ("9090C3", Some(vec![0x90, 0x90, 0xc3])),
+            ("0x1, 0x2", Some(vec![0x01, 0x02])),
+            ("0x90,0xC3", Some(vec![0x90, 0xc3])),
+            ("\\x90\\x9\\xc3", Some(vec![0x90, 0x09, 0xc3])),
+            ("f", Some(vec![0x0f])),
+            ("0x123", None),
+            ("909", None),
+            ("zz", None),
+            ("+1", None),
+            (" , ", None),
+        ];
+        for (text, bytes) in table {
+            assert_eq!(parse_hex_bytes(text), bytes, "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+#!/usr/bin/env python3
+# Regenerates the Authenticode test fixtures next to this script from
+# unsigned.dll, a minimal no_std cdylib built for x86_64-pc-windows-msvc.
+# Needs the `cryptography` package: python3 generate.py unsigned.dll
+import datetime
+import hashlib
+import struct
+import sys
+from pathlib import Path
+
+from cryptography import x509
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import padding, rsa
+from cryptography.x509.oid import ExtendedKeyUsageOID, NameOID
+
+
+def length(n):
+    if n < 0x80:
+        return bytes([n])
+    body = n.to_bytes((n.bit_length() + 7) // 8, "big")
+    return bytes([0x80 | len(body)]) + body
+
+
+def tlv(tag, *parts):
+    body = b"".join(parts)
+    return bytes([tag]) + length(len(body)) + body
+
+
+def seq(*parts):
+    return tlv(0x30, *parts)
+
+
+def set_of(*parts):
+    return tlv(0x31, *sorted(parts))
+
+
+def oid(dotted):
+    numbers = [int(part) for part in dotted.split(".")]
+    body = bytes([numbers[0] * 40 + numbers[1]])
+    for number in numbers[2:]:
+        chunk = [number & 0x7F]
+        number >>= 7
+        while number:
+            chunk.append(0x80 | (number & 0x7F))
+            number >>= 7
+        body += bytes(reversed(chunk))
+    return tlv(0x06, body)
+
+
+def integer(value):
+    return tlv(0x02, value.to_bytes(value.bit_length() // 8 + 1, "big", signed=True))
+
+
+def octets(data):
+    return tlv(0x04, data)
+
+
+NULL = b"\x05\x00"
+SHA256 = seq(oid("2.16.840.1.101.3.4.2.1"), NULL)
+RSA = seq(oid("1.2.840.113549.1.1.1"), NULL)
+SIGNED_DATA = "1.2.840.113549.1.7.2"
+SPC_INDIRECT_DATA = "1.3.6.1.4.1.311.2.1.4"
+TST_INFO = "1.2.840.113549.1.9.16.1.4"
+
+
+def when(year, month=1, day=1):
+    return datetime.datetime(year, month, day, tzinfo=datetime.timezone.utc)
+
+
+def certificate(name, issuer=None, ca=False, usage=None, not_before=when(2000), not_after=when(2099)):
+    key = rsa.generate_private_key(public_exponent=65537, key_size=2048)
+    subject = x509.Name([x509.NameAttribute(NameOID.COMMON_NAME, name)])
+    issuer_name, issuer_key = (issuer[0].subject, issuer[1]) if issuer else (subject, key)
+    builder = (
+        x509.CertificateBuilder()
+        .subject_name(subject)
+        .issuer_name(issuer_name)
+        .public_key(key.public_key())
+        .serial_number(x509.random_serial_number())
+        .not_valid_before(not_before)
+        .not_valid_after(not_after)
+        .add_extension(x509.BasicConstraints(ca=ca, path_length=None), critical=True)
+    )
+    if usage:
+        builder = builder.add_extension(x509.ExtendedKeyUsage([usage]), critical=False)
+    return builder.sign(issuer_key, hashes.SHA256()), key
+
+
+def der(cert):
+    return cert.public_bytes(serialization.Encoding.DER)
+
+
+def signer_info(cert, key, content_type, digest, unsigned=b""):
+    attributes = [
+        seq(oid("1.2.840.113549.1.9.3"), set_of(oid(content_type))),
+        seq(oid("1.2.840.113549.1.9.4"), set_of(octets(digest))),
+    ]
+    signed_attrs = set_of(*attributes)
+    signature = key.sign(signed_attrs, padding.PKCS1v15(), hashes.SHA256())
+    sid = seq(cert.issuer.public_bytes(), integer(cert.serial_number))
+    return seq(
+        integer(1),
+        sid,
+        SHA256,
+        b"\xa0" + signed_attrs[1:],
+        RSA,
+        octets(signature),
+        tlv(0xA1, unsigned) if unsigned else b"",
+    ), signature
+
+
+def signed_data(content_type, content, certs, signer):
+    return seq(
+        oid(SIGNED_DATA),
+        tlv(
+            0xA0,
+            seq(
+                integer(1),
+                set_of(SHA256),
+                seq(oid(content_type), tlv(0xA0, content)),
+                tlv(0xA0, *sorted(der(cert) for cert in certs)),
+                set_of(signer),
+            ),
+        ),
+    )
+
+
+def timestamp_token(signature, tsa, time, forge=False):
+    imprint = seq(SHA256, octets(hashlib.sha256(signature).digest()))
+    gen_time = tlv(0x18, time.strftime("%Y%m%d%H%M%SZ").encode())
+    tst_info = seq(integer(1), oid("1.2.3.4"), imprint, integer(1), gen_time)
+    info, tsa_signature = signer_info(*tsa, TST_INFO, hashlib.sha256(tst_info).digest())
+    if forge:
+        info = info.replace(tsa_signature, bytes([tsa_signature[0] ^ 1]) + tsa_signature[1:])
+    return signed_data(TST_INFO, octets(tst_info), [tsa[0]], info)
+
+
+def sign(image, chain, timestamp=None):
+    image = bytearray(image + b"\0" * (-len(image) % 8))
+    nt_headers = struct.unpack_from("<I", image, 0x3C)[0]
+    optional_header = nt_headers + 24
+    checksum = optional_header + 64
+    security = optional_header + (112 if image[optional_header + 1] == 2 else 96) + 4 * 8
+    offset = len(image)
+
+    digest = hashlib.sha256(
+        image[:checksum] + image[checksum + 4 : security] + image[security + 8 :]
+    ).digest()
+    image_data = seq(
+        oid("1.3.6.1.4.1.311.2.1.15"),
+        seq(tlv(0x03, b"\x00"), tlv(0xA0, tlv(0xA2, tlv(0x80, "<<<Obsolete>>>".encode("utf-16-be"))))),
+    )
+    indirect = seq(image_data, seq(SHA256, octets(digest)))
+    # The message digest covers the content without its SEQUENCE header.
+    header = 1 + len(length(len(indirect) - 2))
+    content_digest = hashlib.sha256(indirect[header:]).digest()
+
+    signer_cert, signer_key = chain[0]
+    info, signature = signer_info(signer_cert, signer_key, SPC_INDIRECT_DATA, content_digest)
+    if timestamp:
+        token = timestamp_token(signature, *timestamp)
+        unsigned = seq(oid("1.3.6.1.4.1.311.3.3.1"), set_of(token))
+        info, _ = signer_info(
+            signer_cert, signer_key, SPC_INDIRECT_DATA, content_digest, unsigned
+        )
+        # PKCS#1 v1.5 is deterministic, so the token still matches.
+        assert _ == signature
+    blob = signed_data(SPC_INDIRECT_DATA, indirect, [cert for cert, _ in chain], info)
+    table = struct.pack("<IHH", 8 + len(blob), 0x0200, 2) + blob
+    table += b"\0" * (-len(table) % 8)
+    struct.pack_into("<II", image, security, offset, len(table))
+    return bytes(image + table)
+
+
+def main():
+    unsigned = Path(sys.argv[1]).read_bytes()
+    out = Path(__file__).parent
+
+    root = certificate("Fixture Root CA", ca=True)
+    intermediate = certificate("Fixture Code Signing CA", root, ca=True)
+    leaf = certificate("Fixture Publisher", intermediate, usage=ExtendedKeyUsageOID.CODE_SIGNING)
+    not_ca = certificate("Fixture Not A CA", root)
+    behind_not_ca = certificate("Fixture Rogue Publisher", not_ca, usage=ExtendedKeyUsageOID.CODE_SIGNING)
+    server = certificate("Fixture Web Server", intermediate, usage=ExtendedKeyUsageOID.SERVER_AUTH)
+    expired = certificate(
+        "Fixture Expired Publisher",
+        intermediate,
+        usage=ExtendedKeyUsageOID.CODE_SIGNING,
+        not_before=when(2020),
+        not_after=when(2021),
+    )
+    tsa = certificate("Fixture Timestamping", root, usage=ExtendedKeyUsageOID.TIME_STAMPING)
+
+    signed = sign(unsigned, [leaf, intermediate])
+    tampered = bytearray(signed)
+    tampered[0x400] ^= 0xFF
+    fixtures = {
+        "root.cer": der(root[0]),
+        "unsigned.dll": unsigned,
+        "signed.dll": signed,
+        "tampered.dll": bytes(tampered),
+        "bad_chain.dll": sign(unsigned, [behind_not_ca, not_ca]),
+        "no_code_signing.dll": sign(unsigned, [server, intermediate]),
+        "timestamped.dll": sign(unsigned, [expired, intermediate], (tsa, when(2020, 6))),
+        "forged_timestamp.dll": sign(
+            unsigned, [expired, intermediate], (tsa, when(2020, 6), True)
+        ),
+    }
+    for name, data in fixtures.items():
+        (out / name).write_bytes(data)
+
+
+if __name__ == "__main__":
+    main()
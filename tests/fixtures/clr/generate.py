@@ -0,0 +1,214 @@
+#!/usr/bin/env python3
+# Writes managed.dll, a minimal IL-only .NET Framework 4 class library for the
+# CLR metadata tests. Equivalent to compiling:
+#
+#   namespace Fixture {
+#       public class Entry {
+#           public static int Run(string argument) { return 0; }
+#           public int Instance(string argument) { return 0; }
+#           public static void Setup() {}
+#       }
+#       public class Empty {}
+#   }
+#   public class Loader {
+#       public static int Start(string argument) { return 0; }
+#   }
+import struct
+from pathlib import Path
+
+IMAGE_BASE = 0x10000000
+SECTION_ALIGNMENT = 0x2000
+FILE_ALIGNMENT = 0x200
+TEXT_RVA = 0x2000
+RELOC_RVA = 0x4000
+
+
+def align(data, alignment):
+    return data + b"\0" * (-len(data) % alignment)
+
+
+class Heap:
+    def __init__(self, first):
+        self.data = bytearray(first)
+
+    def add(self, item):
+        offset = len(self.data)
+        self.data += item
+        return offset
+
+
+strings = Heap(b"\0")
+blobs = Heap(b"\0")
+
+
+def string(text):
+    return strings.add(text.encode() + b"\0")
+
+
+def blob(data):
+    return blobs.add(bytes([len(data)]) + data)
+
+
+# Method bodies in the tiny header format, at the start of .text after the
+# import address table and the CLI header.
+BODIES = {
+    "return 0": bytes([0x16, 0x2A]),  # ldc.i4.0; ret
+    "return": bytes([0x2A]),  # ret
+}
+STATIC_STRING_TO_INT = bytes([0x00, 0x01, 0x08, 0x0E])
+INSTANCE_STRING_TO_INT = bytes([0x20, 0x01, 0x08, 0x0E])
+STATIC_VOID = bytes([0x00, 0x00, 0x01])
+
+PUBLIC_STATIC = 0x0096  # Public | Static | HideBySig
+PUBLIC_INSTANCE = 0x0086  # Public | HideBySig
+METHODS = [
+    ("Run", PUBLIC_STATIC, STATIC_STRING_TO_INT, "return 0"),
+    ("Instance", PUBLIC_INSTANCE, INSTANCE_STRING_TO_INT, "return 0"),
+    ("Setup", PUBLIC_STATIC, STATIC_VOID, "return"),
+    ("Start", PUBLIC_STATIC, STATIC_STRING_TO_INT, "return 0"),
+]
+# (flags, name, namespace, extends System.Object, first method)
+PUBLIC_CLASS = 0x00100001  # Public | BeforeFieldInit
+TYPES = [
+    (0, "<Module>", "", False, 1),
+    (PUBLIC_CLASS, "Entry", "Fixture", True, 1),
+    (PUBLIC_CLASS, "Empty", "Fixture", True, 4),
+    (PUBLIC_CLASS, "Loader", "", True, 4),
+]
+
+
+def metadata(body_rvas):
+    u16 = lambda value: struct.pack("<H", value)
+    u32 = lambda value: struct.pack("<I", value)
+    tables = {}
+    tables[0x00] = [u16(0) + u16(string("Fixture.dll")) + u16(1) + u16(0) + u16(0)]
+    # ResolutionScope: AssemblyRef 1, tag 2.
+    tables[0x01] = [u16((1 << 2) | 2) + u16(string("Object")) + u16(string("System"))]
+    tables[0x02] = [
+        u32(flags)
+        + u16(string(name))
+        + u16(string(namespace) if namespace else 0)
+        + u16((1 << 2) | 1 if extends else 0)  # TypeRef 1, tag 1
+        + u16(1)
+        + u16(first)
+        for flags, name, namespace, extends, first in TYPES
+    ]
+    tables[0x06] = [
+        u32(body_rvas[body]) + u16(0) + u16(flags) + u16(string(name)) + u16(blob(signature)) + u16(1)
+        for name, flags, signature, body in METHODS
+    ]
+    tables[0x20] = [
+        u32(0x8004) + u16(1) + u16(0) + u16(0) + u16(0) + u32(0) + u16(0)
+        + u16(string("Fixture")) + u16(0)
+    ]
+    token = blob(bytes.fromhex("b77a5c561934e089"))
+    tables[0x23] = [
+        u16(4) + u16(0) + u16(0) + u16(0) + u32(0) + u16(token) + u16(string("mscorlib"))
+        + u16(0) + u16(0)
+    ]
+    valid = sum(1 << table for table in tables)
+    table_stream = (
+        u32(0) + bytes([2, 0, 0, 1]) + struct.pack("<QQ", valid, 0x000016003301FA00)
+        + b"".join(u32(len(tables[table])) for table in sorted(tables))
+        + b"".join(b"".join(tables[table]) for table in sorted(tables))
+    )
+    streams = [
+        ("#~", align(table_stream, 4)),
+        ("#Strings", align(bytes(strings.data), 4)),
+        ("#US", align(b"\0", 4)),
+        ("#GUID", bytes(range(16))),
+        ("#Blob", align(bytes(blobs.data), 4)),
+    ]
+    version = align(b"v4.0.30319\0", 4)
+    header_size = 16 + len(version) + 4 + sum(
+        8 + len(align(name.encode() + b"\0", 4)) for name, _ in streams
+    )
+    root = b"BSJB" + u16(1) + u16(1) + u32(0) + u32(len(version)) + version
+    root += u16(0) + u16(len(streams))
+    offset = header_size
+    for name, data in streams:
+        root += u32(offset) + u32(len(data)) + align(name.encode() + b"\0", 4)
+        offset += len(data)
+    return root + b"".join(data for _, data in streams)
+
+
+def text_section():
+    # IAT (2 entries), CLI header, bodies, metadata, imports, entry stub.
+    iat_rva = TEXT_RVA
+    cli_rva = iat_rva + 8
+    bodies_rva = cli_rva + 72
+    body_rvas, bodies = {}, b""
+    for name, code in BODIES.items():
+        body_rvas[name] = bodies_rva + len(bodies)
+        bodies += bytes([(len(code) << 2) | 2]) + code
+    bodies = align(bodies, 4)
+    metadata_rva = bodies_rva + len(bodies)
+    meta = metadata(body_rvas)
+    import_rva = metadata_rva + len(meta)
+    # Import descriptor + null descriptor, lookup table, hint/name, DLL name.
+    lookup_rva = import_rva + 40
+    hint_rva = lookup_rva + 8
+    name_rva = hint_rva + 14
+    stub_rva = align_rva(name_rva + 12, 4) + 2
+    imports = struct.pack("<IIIII", lookup_rva, 0, 0, name_rva, iat_rva) + b"\0" * 20
+    imports += struct.pack("<II", hint_rva, 0)
+    imports += b"\0\0_CorDllMain\0" + b"mscoree.dll\0"
+    imports = align(imports, 4) + b"\0\0"
+    stub = b"\xFF\x25" + struct.pack("<I", IMAGE_BASE + iat_rva)
+    cli = struct.pack("<IHH", 72, 2, 5) + struct.pack("<II", metadata_rva, len(meta))
+    cli += struct.pack("<II", 1, 0) + b"\0" * 48  # ILONLY, no entry point token
+    iat = struct.pack("<II", hint_rva, 0)
+    data = iat + cli + bodies + meta + imports + stub
+    assert TEXT_RVA + len(data) - len(stub) == stub_rva
+    return data, {
+        "import": (import_rva, 40),
+        "iat": (iat_rva, 8),
+        "cli": (cli_rva, 72),
+        "entry": stub_rva,
+        "fixup": stub_rva + 2,
+    }
+
+
+def align_rva(rva, alignment):
+    return rva + (-rva % alignment)
+
+
+def main():
+    text, layout = text_section()
+    page = layout["fixup"] & ~0xFFF
+    reloc = struct.pack("<IIHH", page, 12, (3 << 12) | (layout["fixup"] - page), 0)
+
+    headers_size = FILE_ALIGNMENT
+    text_raw = align(text, FILE_ALIGNMENT)
+    reloc_raw = align(reloc, FILE_ALIGNMENT)
+    directories = [(0, 0)] * 16
+    directories[1] = layout["import"]
+    directories[5] = (RELOC_RVA, len(reloc))
+    directories[12] = layout["iat"]
+    directories[14] = layout["cli"]
+
+    dos = b"MZ" + b"\0" * 0x3A + struct.pack("<I", 0x80)
+    dos = dos.ljust(0x80, b"\0")
+    file_header = struct.pack("<HHIIIHH", 0x014C, 2, 0, 0, 0, 0xE0, 0x2102)
+    optional = struct.pack(
+        "<HBBIIIIIIIIIHHHHHHIIIIHHIIIIII",
+        0x10B, 8, 0, len(text_raw), len(reloc_raw), 0, layout["entry"], TEXT_RVA, RELOC_RVA,
+        IMAGE_BASE, SECTION_ALIGNMENT, FILE_ALIGNMENT, 4, 0, 0, 0, 4, 0, 0,
+        RELOC_RVA + SECTION_ALIGNMENT, headers_size, 0, 3, 0x8540,
+        0x100000, 0x1000, 0x100000, 0x1000, 0, 16,
+    )
+    optional += b"".join(struct.pack("<II", *directory) for directory in directories)
+    sections = struct.pack(
+        "<8sIIIIIIHHI", b".text", len(text), TEXT_RVA, len(text_raw), headers_size, 0, 0, 0, 0,
+        0x60000020,
+    )
+    sections += struct.pack(
+        "<8sIIIIIIHHI", b".reloc", len(reloc), RELOC_RVA, len(reloc_raw),
+        headers_size + len(text_raw), 0, 0, 0, 0, 0x42000040,
+    )
+    headers = align(dos + b"PE\0\0" + file_header + optional + sections, FILE_ALIGNMENT)
+    (Path(__file__).parent / "managed.dll").write_bytes(headers + text_raw + reloc_raw)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,5 @@
+#!/bin/sh
+# Rebuilds libfixture.so for the ELF inspection tests.
+set -e
+cd "$(dirname "$0")"
+gcc -shared -fPIC -O2 -s -Wl,-soname,libfixture.so.1 -Wl,--no-as-needed -o libfixture.so fixture.c -lm
@@ -0,0 +1,15 @@
+/* Source of libfixture.so, the shared object the ELF inspection tests read. */
+#include <math.h>
+
+int initialized;
+double scale = 2.0;
+
+__attribute__((constructor)) static void fixture_init(void) { initialized = 1; }
+
+int fixture_add(int a, int b) { return a + b; }
+
+double fixture_scale(double value) { return floor(value * scale); }
+
+static int fixture_hidden(void) { return 0; }
+
+int (*fixture_keep)(void) = fixture_hidden;